@@ -0,0 +1,5 @@
+// The migrations are compiled into the binary, so a new one has to build it
+// again
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,12 @@
+ALTER TABLE categories DROP CONSTRAINT IF EXISTS categories_name_workspace_id_key;
+ALTER TABLE categories ADD CONSTRAINT categories_name_user_id_key UNIQUE(name, user_id);
+ALTER TABLE labels DROP CONSTRAINT IF EXISTS labels_name_workspace_id_key;
+ALTER TABLE labels ADD CONSTRAINT labels_name_user_id_key UNIQUE(name, user_id);
+
+ALTER TABLE categories DROP COLUMN IF EXISTS workspace_id;
+ALTER TABLE labels DROP COLUMN IF EXISTS workspace_id;
+ALTER TABLE notes DROP COLUMN IF EXISTS workspace_id;
+ALTER TABLE users DROP COLUMN IF EXISTS workspace_id;
+
+DROP TABLE IF EXISTS workspaces_users;
+DROP TABLE IF EXISTS workspaces;
@@ -0,0 +1,40 @@
+CREATE TABLE IF NOT EXISTS workspaces(
+    id SERIAL PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    personal BOOLEAN NOT NULL DEFAULT FALSE,
+    user_id INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS workspaces_users(
+    id SERIAL PRIMARY KEY NOT NULL,
+    workspace_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    UNIQUE(workspace_id, user_id)
+);
+
+ALTER TABLE users ADD COLUMN workspace_id INTEGER;
+ALTER TABLE notes ADD COLUMN workspace_id INTEGER;
+ALTER TABLE labels ADD COLUMN workspace_id INTEGER;
+ALTER TABLE categories ADD COLUMN workspace_id INTEGER;
+
+-- Every existing user gets a personal workspace holding their data
+INSERT INTO workspaces (name, personal, user_id, created_at, updated_at)
+    SELECT email, TRUE, id, NOW(), NOW() FROM users;
+INSERT INTO workspaces_users (workspace_id, user_id, role)
+    SELECT id, user_id, 'owner' FROM workspaces WHERE personal;
+UPDATE users u SET workspace_id = w.id
+    FROM workspaces w WHERE w.personal AND w.user_id = u.id;
+UPDATE notes n SET workspace_id = w.id
+    FROM workspaces w WHERE w.personal AND w.user_id = n.user_id;
+UPDATE labels l SET workspace_id = w.id
+    FROM workspaces w WHERE w.personal AND w.user_id = l.user_id;
+UPDATE categories c SET workspace_id = w.id
+    FROM workspaces w WHERE w.personal AND w.user_id = c.user_id;
+
+ALTER TABLE labels DROP CONSTRAINT IF EXISTS labels_name_user_id_key;
+ALTER TABLE labels ADD CONSTRAINT labels_name_workspace_id_key UNIQUE(name, workspace_id);
+ALTER TABLE categories DROP CONSTRAINT IF EXISTS categories_name_user_id_key;
+ALTER TABLE categories ADD CONSTRAINT categories_name_workspace_id_key UNIQUE(name, workspace_id);
@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS notes_remind_at_idx;
+ALTER TABLE notes DROP COLUMN IF EXISTS reminded_at;
+ALTER TABLE notes DROP COLUMN IF EXISTS snoozed_until;
+ALTER TABLE notes DROP COLUMN IF EXISTS recurrence;
+ALTER TABLE notes DROP COLUMN IF EXISTS remind_at;
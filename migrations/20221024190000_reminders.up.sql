@@ -0,0 +1,5 @@
+ALTER TABLE notes ADD COLUMN remind_at TIMESTAMP;
+ALTER TABLE notes ADD COLUMN recurrence TEXT;
+ALTER TABLE notes ADD COLUMN snoozed_until TIMESTAMP;
+ALTER TABLE notes ADD COLUMN reminded_at TIMESTAMP;
+CREATE INDEX IF NOT EXISTS notes_remind_at_idx ON notes(remind_at) WHERE remind_at IS NOT NULL;
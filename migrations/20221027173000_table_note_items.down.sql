@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS note_items;
+ALTER TABLE notes DROP COLUMN IF EXISTS kind;
@@ -0,0 +1,12 @@
+ALTER TABLE notes ADD COLUMN kind TEXT NOT NULL DEFAULT 'text';
+
+CREATE TABLE IF NOT EXISTS note_items(
+    id SERIAL PRIMARY KEY NOT NULL,
+    note_id INTEGER NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    checked BOOLEAN NOT NULL DEFAULT FALSE,
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS note_items_note_id_idx ON note_items(note_id, position);
@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS note_links(
+    source_id INTEGER NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    target_id INTEGER NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (source_id, target_id)
+);
+CREATE INDEX IF NOT EXISTS note_links_target_id_idx ON note_links(target_id);
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS templates_categories;
+DROP TABLE IF EXISTS templates_labels;
+DROP TABLE IF EXISTS templates;
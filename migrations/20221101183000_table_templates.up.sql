@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS templates(
+    id SERIAL PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'text',
+    user_id INTEGER NOT NULL,
+    workspace_id INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL,
+    UNIQUE(name, workspace_id)
+);
+
+CREATE TABLE IF NOT EXISTS templates_labels(
+    template_id INTEGER NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+    label_id INTEGER NOT NULL REFERENCES labels(id) ON DELETE CASCADE,
+    PRIMARY KEY (template_id, label_id)
+);
+
+CREATE TABLE IF NOT EXISTS templates_categories(
+    template_id INTEGER NOT NULL REFERENCES templates(id) ON DELETE CASCADE,
+    category_id INTEGER NOT NULL REFERENCES categories(id) ON DELETE CASCADE,
+    PRIMARY KEY (template_id, category_id)
+);
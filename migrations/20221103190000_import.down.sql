@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS attachments;
+DROP INDEX IF EXISTS notes_workspace_id_source_idx;
+ALTER TABLE notes DROP COLUMN IF EXISTS source;
+ALTER TABLE notes DROP COLUMN IF EXISTS color;
+ALTER TABLE notes DROP COLUMN IF EXISTS trashed;
+ALTER TABLE notes DROP COLUMN IF EXISTS archived;
+ALTER TABLE notes DROP COLUMN IF EXISTS pinned;
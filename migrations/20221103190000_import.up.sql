@@ -0,0 +1,18 @@
+ALTER TABLE notes ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE notes ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE notes ADD COLUMN trashed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE notes ADD COLUMN color TEXT;
+-- Where an imported note comes from, so importing again skips it
+ALTER TABLE notes ADD COLUMN source TEXT;
+CREATE UNIQUE INDEX IF NOT EXISTS notes_workspace_id_source_idx ON notes(workspace_id, source);
+
+CREATE TABLE IF NOT EXISTS attachments(
+    id SERIAL PRIMARY KEY NOT NULL,
+    note_id INTEGER NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+    filename TEXT NOT NULL,
+    mime_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    data BYTEA NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS attachments_note_id_idx ON attachments(note_id);
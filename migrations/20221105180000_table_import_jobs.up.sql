@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS import_jobs(
+    id SERIAL PRIMARY KEY NOT NULL,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL,
+    total_bytes BIGINT NOT NULL DEFAULT 0,
+    read_bytes BIGINT NOT NULL DEFAULT 0,
+    created INTEGER NOT NULL DEFAULT 0,
+    skipped INTEGER NOT NULL DEFAULT 0,
+    failed JSONB NOT NULL DEFAULT '[]',
+    error TEXT,
+    user_id INTEGER NOT NULL,
+    workspace_id INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,11 @@
+-- Tokens for clients that authenticate with basic auth, like the ones of
+-- the Nextcloud Notes API. Only the md5 of the token is kept.
+CREATE TABLE IF NOT EXISTS app_tokens(
+    id SERIAL PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL,
+    token TEXT NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    used_at TIMESTAMP
+);
+CREATE INDEX IF NOT EXISTS app_tokens_user_id_idx ON app_tokens(user_id);
@@ -0,0 +1,5 @@
+DROP TRIGGER IF EXISTS note_items_version ON note_items;
+DROP FUNCTION IF EXISTS note_items_next_version();
+DROP TRIGGER IF EXISTS notes_version ON notes;
+DROP FUNCTION IF EXISTS notes_next_version();
+ALTER TABLE notes DROP COLUMN IF EXISTS version;
@@ -0,0 +1,27 @@
+-- Every change of a note, or of the items of a checklist, gives it a new
+-- version. WebDAV clients get it as the ETag of the note.
+ALTER TABLE notes ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+CREATE OR REPLACE FUNCTION notes_next_version() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notes_version BEFORE UPDATE ON notes
+    FOR EACH ROW EXECUTE FUNCTION notes_next_version();
+
+CREATE OR REPLACE FUNCTION note_items_next_version() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        UPDATE notes SET version = version WHERE id = OLD.note_id;
+    ELSE
+        UPDATE notes SET version = version WHERE id = NEW.note_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER note_items_version AFTER INSERT OR UPDATE OR DELETE ON note_items
+    FOR EACH ROW EXECUTE FUNCTION note_items_next_version();
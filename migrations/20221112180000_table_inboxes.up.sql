@@ -0,0 +1,8 @@
+-- The address of each user to receive notes by email. Mail sent to
+-- `key@domain`, or `key+label@domain`, becomes a note.
+CREATE TABLE IF NOT EXISTS inboxes(
+    id SERIAL PRIMARY KEY NOT NULL,
+    key TEXT NOT NULL UNIQUE,
+    user_id INTEGER NOT NULL UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL
+);
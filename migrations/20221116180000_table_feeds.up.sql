@@ -0,0 +1,13 @@
+-- Labels and categories published as Atom and RSS feeds. Feeds that are not
+-- public are only read with their token.
+CREATE TABLE IF NOT EXISTS feeds(
+    id SERIAL PRIMARY KEY NOT NULL,
+    label_id INTEGER UNIQUE REFERENCES labels(id) ON DELETE CASCADE,
+    category_id INTEGER UNIQUE REFERENCES categories(id) ON DELETE CASCADE,
+    token TEXT NOT NULL UNIQUE,
+    public BOOLEAN NOT NULL DEFAULT FALSE,
+    workspace_id INTEGER NOT NULL REFERENCES workspaces(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL,
+    CHECK ((label_id IS NULL) <> (category_id IS NULL))
+);
@@ -0,0 +1,3 @@
+-- A disabled user can not log in, neither with its password nor with its
+-- app tokens.
+ALTER TABLE users ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,85 @@
+use std::{fs::{self, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+
+/// The server and the credentials saved by `notis login`. Instead of the
+/// password it keeps an app token, that can be revoked, to get a new token
+/// when the last one expires. Still, the file is only readable by its owner.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Config{
+    pub url: String,
+    pub email: String,
+    /// Empty in the files of older versions, that kept the password
+    #[serde(default)]
+    pub app_token: String,
+    pub token: Option<String>,
+}
+
+impl Config{
+    /// `~/.config/notisbak/config.toml`, or where the system keeps them
+    pub fn default_path() -> PathBuf{
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("notisbak")
+            .join("config.toml")
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self>{
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()>{
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let content = toml::to_string(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path)?;
+        // The mode only applies to new files
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(content.as_bytes())
+    }
+}
+
+#[test]
+fn test_config(){
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("notisbak").join("config.toml");
+    let config = Config{
+        url: "https://notes.example.com".to_string(),
+        email: "tucorreo@tuservidor.es".to_string(),
+        app_token: "hG3kP0qZ8sW1yT5uV7xA9bC2dE4fJ6mN".to_string(),
+        token: None,
+    };
+    #[cfg(unix)]
+    {
+        // Even over a file that others could read
+        use std::os::unix::fs::PermissionsExt;
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    }
+    config.save(&path).unwrap();
+    assert_eq!(Config::load(&path).unwrap(), config);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    // The password of older versions is not read
+    let old: Config = toml::from_str("url = \"https://notes.example.com\"\nemail = \"a@b.c\"\npassword = \"secreto\"\n").unwrap();
+    assert!(old.app_token.is_empty());
+}
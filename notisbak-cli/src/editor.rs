@@ -0,0 +1,43 @@
+use std::{env, fs, io, process::Command};
+
+/// The text given to the editor: the title in the first line, a blank line
+/// and the body
+pub fn to_text(title: &str, body: &str) -> String{
+    format!("{}\n\n{}", title, body)
+}
+
+/// The title and the body of the text back from the editor
+pub fn from_text(text: &str) -> (String, String){
+    let (title, body) = text.split_once('\n').unwrap_or((text, ""));
+    let body = body.strip_prefix('\n').unwrap_or(body);
+    (title.trim().to_string(), body.trim_end().to_string())
+}
+
+/// Open `text` in `$VISUAL` or `$EDITOR`, `vi` without them, and return what
+/// the user saved
+pub fn edit(text: &str) -> io::Result<String>{
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let file = tempfile::Builder::new().suffix(".md").tempfile()?;
+    fs::write(file.path(), text)?;
+    // The editor may come with arguments, like `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program)
+        .args(words)
+        .arg(file.path())
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{} exited with {}", editor, status)));
+    }
+    fs::read_to_string(file.path())
+}
+
+#[test]
+fn test_text(){
+    let text = to_text("Lista de la compra", "- pan\n- leche");
+    assert_eq!(from_text(&text), ("Lista de la compra".to_string(), "- pan\n- leche".to_string()));
+    assert_eq!(from_text("Solo título\n"), ("Solo título".to_string(), String::new()));
+    assert_eq!(from_text("Título\nsin línea en blanco\n"), ("Título".to_string(), "sin línea en blanco".to_string()));
+}
@@ -0,0 +1,352 @@
+//! `notis`, the notes of a notisbak server from the shell.
+//!
+//! ```sh
+//! notis login --url https://notes.example.com --email tucorreo@tuservidor.es
+//! echo "- pan\n- leche" | notis new "Lista de la compra" --label casa
+//! notis ls --label casa
+//! notis edit 12
+//! ```
+mod config;
+mod editor;
+mod output;
+mod tui;
+
+use std::{error::Error, io::{self, IsTerminal, Read}, path::{Path, PathBuf}, process};
+
+use clap::{Parser, Subcommand};
+use notisbak_client::{Client, Include, NewNote, Note, NoteDetail, UpdateNote};
+
+use config::Config;
+
+pub type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(name = "notis", version, about = "Take notes in a notisbak server")]
+struct Cli{
+    /// Where the server and the credentials are saved
+    #[arg(long, global = true, env = "NOTISBAK_CONFIG")]
+    config: Option<PathBuf>,
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command{
+    /// Log in and save the credentials
+    Login{
+        #[arg(long)]
+        url: String,
+        #[arg(long)]
+        email: String,
+        /// Read the password from stdin instead of asking for it
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Create a note. The body is read from stdin when it is not a terminal.
+    New{
+        title: String,
+        #[arg(long)]
+        label: Vec<String>,
+        #[arg(long)]
+        category: Option<String>,
+    },
+    /// List the notes
+    Ls{
+        #[arg(long)]
+        label: Option<String>,
+        #[arg(long)]
+        category: Option<String>,
+        /// Also the archived notes and the ones in the trash
+        #[arg(long, short)]
+        all: bool,
+    },
+    /// Show a note
+    Show{
+        id: i32,
+    },
+    /// Edit the title and the body of a note with $EDITOR
+    Edit{
+        id: i32,
+    },
+    /// Delete a note
+    Rm{
+        id: i32,
+    },
+    /// Labels of a note
+    #[command(subcommand)]
+    Label(LabelCommand),
+    /// Category of a note
+    #[command(subcommand)]
+    Category(CategoryCommand),
+    /// Notes with the text in the title or the body
+    Search{
+        text: String,
+    },
+    /// Browse and edit the notes in the terminal, also without connection
+    Tui,
+}
+
+#[derive(Subcommand)]
+enum LabelCommand{
+    /// Label a note, creating the label when there is none with that name
+    Add{
+        id: i32,
+        name: String,
+    },
+    /// Remove a label from a note
+    Rm{
+        id: i32,
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CategoryCommand{
+    /// Set the only category of a note, creating it when there is none with
+    /// that name
+    Set{
+        id: i32,
+        name: String,
+    },
+}
+
+pub const WITH_ALL: Include = Include{
+    labels: true,
+    categories: true,
+};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main(){
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        // The reader of a pipeline, like `head`, has all it wanted
+        if e.downcast_ref::<io::Error>().map(io::Error::kind) == Some(io::ErrorKind::BrokenPipe) {
+            return;
+        }
+        eprintln!("notis: {}", e);
+        process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()>{
+    let path = cli.config.unwrap_or_else(Config::default_path);
+    if let Command::Login{url, email, password_stdin} = cli.command {
+        return login(&path, url, email, password_stdin).await;
+    }
+    let mut config = Config::load(&path)
+        .map_err(|e| format!("can not read {} ({}), run `notis login` first", path.display(), e))?;
+    if config.app_token.is_empty() {
+        return Err(format!("{} is from an older version, run `notis login` again", path.display()).into());
+    }
+    // Without a token the first request gets one with the app token
+    let mut client = Client::new(&config.url)
+        .with_app_token(&config.email, &config.app_token);
+    if let Some(token) = &config.token {
+        client = client.with_token(token);
+    }
+    let result = match cli.command {
+        Command::Tui => tui::run(&client, &config).await,
+        command => execute(&client, command, cli.json).await,
+    };
+    // Keep the token when it was refreshed, even if the command failed
+    if client.token() != config.token {
+        config.token = client.token();
+        config.save(&path)?;
+    }
+    result
+}
+
+async fn login(path: &Path, url: String, email: String, password_stdin: bool) -> Result<()>{
+    let password = match password_stdin {
+        true => {
+            let mut password = String::new();
+            io::stdin().read_line(&mut password)?;
+            password.trim_end_matches(['\r', '\n']).to_string()
+        },
+        false => rpassword::prompt_password("Password: ")?,
+    };
+    let client = Client::new(&url);
+    client.login(&email, &password).await?;
+    // The password is not kept, an app token that can be revoked instead
+    let hostname = std::env::var("HOSTNAME").unwrap_or_default();
+    let name = match hostname.is_empty() {
+        true => "notis".to_string(),
+        false => format!("notis {}", hostname),
+    };
+    let app_token = client.create_app_token(&name).await?;
+    Config{
+        url,
+        email,
+        app_token: app_token.token,
+        token: client.token(),
+    }.save(path)?;
+    eprintln!("Credentials saved in {}", path.display());
+    Ok(())
+}
+
+async fn execute(client: &Client, command: Command, json: bool) -> Result<()>{
+    match command {
+        Command::Login{..} | Command::Tui => unreachable!(),
+        Command::New{title, label, category} => {
+            let body = match io::stdin().is_terminal() {
+                true => None,
+                false => {
+                    let mut body = String::new();
+                    io::stdin().read_to_string(&mut body)?;
+                    Some(body)
+                },
+            };
+            let note = client.create_note(&NewNote{
+                title,
+                body,
+                kind: None,
+                remind_at: None,
+                recurrence: None,
+            }).await?;
+            for name in label {
+                add_label(client, note.id, &name).await?;
+            }
+            if let Some(name) = category {
+                set_category(client, note.id, &name).await?;
+            }
+            show(client, note.id, json).await
+        },
+        Command::Ls{label, category, all} => {
+            let notes = client.notes_with(WITH_ALL).await?
+                .into_iter()
+                .filter(|detail| all || !(detail.note.archived || detail.note.trashed))
+                .filter(|detail| has(&detail.labels, label.as_deref(), |label| &label.name))
+                .filter(|detail| has(&detail.categories, category.as_deref(), |category| &category.name))
+                .collect::<Vec<_>>();
+            list(&notes, json)
+        },
+        Command::Show{id} => show(client, id, json).await,
+        Command::Edit{id} => {
+            let note = client.note(id).await?;
+            let text = editor::edit(&editor::to_text(&note.title, &note.body))?;
+            let (title, body) = editor::from_text(&text);
+            if title.is_empty() {
+                return Err("the title can not be empty, the note was not changed".into());
+            }
+            if title == note.title && body == note.body {
+                eprintln!("No changes");
+                return Ok(());
+            }
+            client.update_note(&UpdateNote{
+                id,
+                title,
+                body: Some(body),
+                kind: None,
+                pinned: None,
+                archived: None,
+                trashed: None,
+                color: None,
+                version: Some(note.version),
+            }).await?;
+            show(client, id, json).await
+        },
+        Command::Rm{id} => {
+            let note = client.delete_note(id).await?;
+            match json {
+                true => output::json(&note)?,
+                false => eprintln!("Deleted {} {}", note.id, note.title),
+            }
+            Ok(())
+        },
+        Command::Label(LabelCommand::Add{id, name}) => {
+            add_label(client, id, &name).await?;
+            show(client, id, json).await
+        },
+        Command::Label(LabelCommand::Rm{id, name}) => {
+            let label = client.note_labels(id).await?
+                .into_iter()
+                .find(|label| label.name == name)
+                .ok_or_else(|| format!("the note {} has no label {}", id, name))?;
+            client.remove_label(id, label.id).await?;
+            show(client, id, json).await
+        },
+        Command::Category(CategoryCommand::Set{id, name}) => {
+            set_category(client, id, &name).await?;
+            show(client, id, json).await
+        },
+        Command::Search{text} => {
+            let notes = client.notes_with(WITH_ALL).await?
+                .into_iter()
+                .filter(|detail| has_text(&detail.note, &text))
+                .collect::<Vec<_>>();
+            list(&notes, json)
+        },
+    }
+}
+
+fn list(notes: &[NoteDetail], json: bool) -> Result<()>{
+    match json {
+        true => output::json(&notes),
+        false => output::notes(notes),
+    }?;
+    Ok(())
+}
+
+async fn show(client: &Client, id: i32, json: bool) -> Result<()>{
+    let note = client.note_with(id, WITH_ALL).await?;
+    match json {
+        true => output::json(&note),
+        false => output::note(&note),
+    }?;
+    Ok(())
+}
+
+/// Without a name every note has it
+fn has<T>(items: &Option<Vec<T>>, name: Option<&str>, name_of: impl Fn(&T) -> &String) -> bool{
+    match name {
+        Some(name) => items.iter().flatten().any(|item| name_of(item) == name),
+        None => true,
+    }
+}
+
+/// Whether the title or the body have the text, ignoring case
+fn has_text(note: &Note, text: &str) -> bool{
+    let text = text.to_lowercase();
+    note.title.to_lowercase().contains(&text) || note.body.to_lowercase().contains(&text)
+}
+
+async fn add_label(client: &Client, id: i32, name: &str) -> Result<()>{
+    let label = match client.labels().await?.into_iter().find(|label| label.name == name) {
+        Some(label) => label,
+        None => client.create_label(name).await?,
+    };
+    client.add_label(id, label.id).await?;
+    Ok(())
+}
+
+async fn set_category(client: &Client, id: i32, name: &str) -> Result<()>{
+    let category = match client.categories().await?.into_iter().find(|category| category.name == name) {
+        Some(category) => category,
+        None => client.create_category(name).await?,
+    };
+    let mut linked = false;
+    for current in client.note_categories(id).await? {
+        match current.id == category.id {
+            true => linked = true,
+            false => {
+                client.remove_category(id, current.id).await?;
+            },
+        }
+    }
+    if !linked {
+        client.add_category(id, category.id).await?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_cli(){
+    use clap::CommandFactory;
+    Cli::command().debug_assert();
+    let cli = Cli::parse_from(["notis", "ls", "--label", "casa", "--json"]);
+    assert!(cli.json);
+    assert!(matches!(cli.command, Command::Ls{label: Some(label), category: None, all: false} if label == "casa"));
+}
@@ -0,0 +1,58 @@
+use std::io::{self, Write};
+
+use comfy_table::{presets::UTF8_FULL_CONDENSED, Table};
+use notisbak_client::{Label, NoteDetail};
+use serde::Serialize;
+
+pub fn json<T: Serialize>(value: &T) -> io::Result<()>{
+    writeln!(io::stdout().lock(), "{}", serde_json::to_string_pretty(value)?)
+}
+
+fn names(labels: &Option<Vec<Label>>) -> String{
+    labels.iter()
+        .flatten()
+        .map(|label| label.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn notes(notes: &[NoteDetail]) -> io::Result<()>{
+    let mut table = Table::new();
+    table.load_preset(UTF8_FULL_CONDENSED)
+        .set_header(vec!["Id", "Title", "Labels", "Category", "Modified"]);
+    for detail in notes {
+        let note = &detail.note;
+        let title = match note.pinned {
+            true => format!("📌 {}", note.title),
+            false => note.title.clone(),
+        };
+        let categories = detail.categories.iter()
+            .flatten()
+            .map(|category| category.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        table.add_row(vec![
+            note.id.to_string(),
+            title,
+            names(&detail.labels),
+            categories,
+            note.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+        ]);
+    }
+    writeln!(io::stdout().lock(), "{}", table)
+}
+
+pub fn note(detail: &NoteDetail) -> io::Result<()>{
+    let note = &detail.note;
+    let mut out = io::stdout().lock();
+    writeln!(out, "# {}", note.title)?;
+    let labels = names(&detail.labels);
+    if !labels.is_empty() {
+        writeln!(out, "Labels: {}", labels)?;
+    }
+    if let Some(category) = detail.categories.iter().flatten().next() {
+        writeln!(out, "Category: {}", category.name)?;
+    }
+    writeln!(out)?;
+    writeln!(out, "{}", note.body)
+}
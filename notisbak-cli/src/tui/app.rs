@@ -0,0 +1,260 @@
+use notisbak_client::{Category, Client, Error, Label, NewNote, NoteDetail, UpdateNote};
+use ratatui::{crossterm::event::{KeyCode, KeyEvent, KeyModifiers}, widgets::ListState};
+use tui_textarea::TextArea;
+
+use super::cache::Cache;
+use crate::{editor, Result, WITH_ALL};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Focus{
+    Labels,
+    Categories,
+    Notes,
+}
+
+/// A note open in the preview pane to change it
+pub struct Editing{
+    /// None for a new note
+    pub id: Option<i32>,
+    pub text: TextArea<'static>,
+}
+
+fn new_note(title: &str, body: &str) -> NewNote{
+    NewNote{
+        title: title.to_string(),
+        body: Some(body.to_string()),
+        kind: None,
+        remind_at: None,
+        recurrence: None,
+    }
+}
+
+/// What the event loop has to do after a key
+pub enum Action{
+    None,
+    Sync,
+    Quit,
+}
+
+pub struct App{
+    cache: Cache,
+    pub notes: Vec<NoteDetail>,
+    pub labels: Vec<Label>,
+    pub categories: Vec<Category>,
+    pub focus: Focus,
+    /// The first item of the sidebars is every note
+    pub label_state: ListState,
+    pub category_state: ListState,
+    pub note_state: ListState,
+    pub editing: Option<Editing>,
+    pub online: bool,
+    pub status: String,
+}
+
+impl App{
+    pub fn new(cache: Cache) -> rusqlite::Result<Self>{
+        let mut app = Self{
+            cache,
+            notes: Vec::new(),
+            labels: Vec::new(),
+            categories: Vec::new(),
+            focus: Focus::Notes,
+            label_state: ListState::default().with_selected(Some(0)),
+            category_state: ListState::default().with_selected(Some(0)),
+            note_state: ListState::default().with_selected(Some(0)),
+            editing: None,
+            online: false,
+            status: String::new(),
+        };
+        app.reload()?;
+        Ok(app)
+    }
+
+    fn reload(&mut self) -> rusqlite::Result<()>{
+        self.notes = self.cache.notes()?;
+        self.labels = self.cache.labels()?;
+        self.categories = self.cache.categories()?;
+        Ok(())
+    }
+
+    pub fn pending(&self) -> usize{
+        self.cache.changes().map(|changes| changes.len()).unwrap_or(0)
+    }
+
+    /// The notes of the label or the category selected in the sidebars
+    pub fn visible(&self) -> Vec<&NoteDetail>{
+        let label = self.label_state.selected()
+            .filter(|index| *index > 0)
+            .and_then(|index| self.labels.get(index - 1));
+        let category = self.category_state.selected()
+            .filter(|index| *index > 0)
+            .and_then(|index| self.categories.get(index - 1));
+        self.notes.iter()
+            .filter(|detail| !detail.note.archived && !detail.note.trashed)
+            .filter(|detail| label.map_or(true, |label|
+                detail.labels.iter().flatten().any(|item| item.id == label.id)))
+            .filter(|detail| category.map_or(true, |category|
+                detail.categories.iter().flatten().any(|item| item.id == category.id)))
+            .collect()
+    }
+
+    pub fn selected(&self) -> Option<&NoteDetail>{
+        self.note_state.selected().and_then(|index| self.visible().get(index).copied())
+    }
+
+    /// Send the changes made without connection and read everything from
+    /// the server. Without connection it keeps working with the cache.
+    pub async fn sync(&mut self, client: &Client){
+        match self.push_and_pull(client).await {
+            Ok(conflicts) if conflicts.is_empty() => {
+                self.online = true;
+                self.status = "Synced".to_string();
+            },
+            Ok(conflicts) => {
+                self.online = true;
+                self.status = format!("Changed in the server, your version is in a copy: {}", conflicts.join(", "));
+            },
+            Err(e) if matches!(e.downcast_ref(), Some(Error::Http(_))) => {
+                self.online = false;
+                self.status = format!("Offline: {}", e);
+            },
+            Err(e) => {
+                self.online = true;
+                self.status = e.to_string();
+            },
+        }
+        if let Err(e) = self.reload() {
+            self.status = e.to_string();
+        }
+    }
+
+    /// The titles of the notes changed in the server since they were edited
+    /// here are answered. The local edit of those is kept as a new note.
+    async fn push_and_pull(&mut self, client: &Client) -> Result<Vec<String>>{
+        let mut conflicts = Vec::new();
+        for change in self.cache.pending()? {
+            if change.note_id < 0 {
+                let note = client.create_note(&new_note(&change.title, &change.body)).await?;
+                self.cache.sent(change.note_id, note.id, change.seq)?;
+                continue;
+            }
+            let update = client.update_note(&UpdateNote{
+                id: change.note_id,
+                title: change.title.clone(),
+                body: Some(change.body.clone()),
+                kind: None,
+                pinned: None,
+                archived: None,
+                trashed: None,
+                color: None,
+                version: change.version,
+            }).await;
+            match update {
+                // Deleted somewhere else, the edit is lost with it
+                Ok(_) | Err(Error::NotFound(_)) => {},
+                Err(Error::Conflict(_)) => {
+                    let title = format!("{} (conflict)", change.title);
+                    client.create_note(&new_note(&title, &change.body)).await?;
+                    conflicts.push(change.title);
+                },
+                Err(e) => return Err(e.into()),
+            }
+            self.cache.sent(change.note_id, change.note_id, change.seq)?;
+        }
+        let notes = client.notes_with(WITH_ALL).await?;
+        let labels = client.labels().await?;
+        let categories = client.categories().await?;
+        self.cache.replace(&notes, &labels, &categories)?;
+        Ok(conflicts)
+    }
+
+    pub fn on_key(&mut self, key: KeyEvent) -> Action{
+        if self.editing.is_some() {
+            return self.on_editing_key(key);
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Action::Quit,
+            KeyCode::Tab => self.focus = match self.focus {
+                Focus::Labels => Focus::Categories,
+                Focus::Categories => Focus::Notes,
+                Focus::Notes => Focus::Labels,
+            },
+            KeyCode::BackTab => self.focus = match self.focus {
+                Focus::Labels => Focus::Notes,
+                Focus::Categories => Focus::Labels,
+                Focus::Notes => Focus::Categories,
+            },
+            KeyCode::Down | KeyCode::Char('j') => self.step(1),
+            KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+            KeyCode::Char('s') => return Action::Sync,
+            KeyCode::Char('n') => self.open(None, "", ""),
+            KeyCode::Enter | KeyCode::Char('e') => {
+                if let Some(detail) = self.selected() {
+                    let (id, title, body) = (detail.note.id, detail.note.title.clone(), detail.note.body.clone());
+                    self.open(Some(id), &title, &body);
+                }
+            },
+            _ => {},
+        }
+        Action::None
+    }
+
+    /// Move the selection of the focused list
+    fn step(&mut self, by: isize){
+        let (state, len) = match self.focus {
+            Focus::Labels => (&mut self.label_state, self.labels.len() + 1),
+            Focus::Categories => (&mut self.category_state, self.categories.len() + 1),
+            Focus::Notes => {
+                let len = self.visible().len();
+                (&mut self.note_state, len)
+            },
+        };
+        if len == 0 {
+            return;
+        }
+        let index = state.selected().unwrap_or(0) as isize + by;
+        state.select(Some(index.clamp(0, len as isize - 1) as usize));
+        if self.focus != Focus::Notes {
+            self.note_state.select(Some(0));
+        }
+    }
+
+    fn open(&mut self, id: Option<i32>, title: &str, body: &str){
+        let lines = editor::to_text(title, body).lines().map(String::from).collect();
+        self.editing = Some(Editing{
+            id,
+            text: TextArea::new(lines),
+        });
+        self.status = "Ctrl-S saves, Esc discards".to_string();
+    }
+
+    fn on_editing_key(&mut self, key: KeyEvent) -> Action{
+        let editing = self.editing.as_mut().unwrap();
+        match key.code {
+            KeyCode::Esc => {
+                self.editing = None;
+                self.status = "Discarded".to_string();
+                Action::None
+            },
+            KeyCode::Char('s') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                let (title, body) = editor::from_text(&editing.text.lines().join("\n"));
+                if title.is_empty() {
+                    self.status = "The title can not be empty".to_string();
+                    return Action::None;
+                }
+                let id = editing.id;
+                self.editing = None;
+                if let Err(e) = self.cache.edit(id, &title, &body).and_then(|_| self.reload()) {
+                    self.status = e.to_string();
+                    return Action::None;
+                }
+                Action::Sync
+            },
+            _ => {
+                editing.text.input(key);
+                Action::None
+            },
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::path::Path;
+
+use chrono::Utc;
+use notisbak_client::{Category, Label, Note, NoteDetail, NoteKind};
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// The notes, labels and categories of the server, as they were in the
+/// last sync, and the edits made since then, in order
+pub struct Cache{
+    connection: Connection,
+}
+
+/// An edit that the server does not have yet. The notes created without
+/// connection have negative ids until they are sent.
+#[derive(Debug, PartialEq)]
+pub struct Change{
+    pub seq: i64,
+    pub note_id: i32,
+    pub title: String,
+    pub body: String,
+    /// The version of the server the edit started from, none for a new note
+    pub version: Option<i32>,
+}
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS notes(
+        id INTEGER PRIMARY KEY,
+        detail TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS labels(
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS categories(
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS changes(
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        note_id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        body TEXT NOT NULL,
+        version INTEGER
+    );";
+
+fn to_json(detail: &NoteDetail) -> rusqlite::Result<String>{
+    serde_json::to_string(detail)
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn from_json(json: String) -> rusqlite::Result<NoteDetail>{
+    serde_json::from_str(&json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+impl Cache{
+    pub fn open(path: &Path) -> rusqlite::Result<Self>{
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        Self::with(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> rusqlite::Result<Self>{
+        Self::with(Connection::open_in_memory()?)
+    }
+
+    fn with(connection: Connection) -> rusqlite::Result<Self>{
+        connection.execute_batch(SCHEMA)?;
+        // Caches made before the changes kept their version
+        let columns: Vec<String> = connection.prepare("SELECT name FROM pragma_table_info('changes')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        if !columns.iter().any(|column| column == "version") {
+            connection.execute("ALTER TABLE changes ADD COLUMN version INTEGER", [])?;
+        }
+        Ok(Self{connection})
+    }
+
+    /// The notes, pinned first and then the last modified
+    pub fn notes(&self) -> rusqlite::Result<Vec<NoteDetail>>{
+        let mut statement = self.connection.prepare("SELECT detail FROM notes")?;
+        let mut notes = statement.query_map([], |row| row.get(0))?
+            .map(|json| json.and_then(from_json))
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        notes.sort_by_key(|detail| std::cmp::Reverse((detail.note.pinned, detail.note.updated_at)));
+        Ok(notes)
+    }
+
+    pub fn labels(&self) -> rusqlite::Result<Vec<Label>>{
+        let mut statement = self.connection.prepare("SELECT id, name FROM labels ORDER BY name")?;
+        let labels = statement.query_map([], |row| Ok(Label{
+            id: row.get(0)?,
+            name: row.get(1)?,
+        }))?.collect();
+        labels
+    }
+
+    pub fn categories(&self) -> rusqlite::Result<Vec<Category>>{
+        let mut statement = self.connection.prepare("SELECT id, name FROM categories ORDER BY name")?;
+        let categories = statement.query_map([], |row| Ok(Category{
+            id: row.get(0)?,
+            name: row.get(1)?,
+        }))?.collect();
+        categories
+    }
+
+    /// Everything as the server has it now. The notes with changes still
+    /// to send keep the local version.
+    pub fn replace(&mut self, notes: &[NoteDetail], labels: &[Label], categories: &[Category]) -> rusqlite::Result<()>{
+        let transaction = self.connection.transaction()?;
+        transaction.execute("DELETE FROM notes WHERE id NOT IN (SELECT note_id FROM changes)", [])?;
+        transaction.execute("DELETE FROM labels", [])?;
+        transaction.execute("DELETE FROM categories", [])?;
+        for detail in notes {
+            transaction.execute("INSERT OR IGNORE INTO notes (id, detail) VALUES (?, ?)",
+                params![detail.note.id, to_json(detail)?])?;
+        }
+        for label in labels {
+            transaction.execute("INSERT INTO labels (id, name) VALUES (?, ?)", params![label.id, label.name])?;
+        }
+        for category in categories {
+            transaction.execute("INSERT INTO categories (id, name) VALUES (?, ?)", params![category.id, category.name])?;
+        }
+        transaction.commit()
+    }
+
+    /// Change the title and the body of a note, or create it with `None`,
+    /// and keep the change to send it
+    pub fn edit(&mut self, id: Option<i32>, title: &str, body: &str) -> rusqlite::Result<i32>{
+        let now = Utc::now().naive_utc();
+        let transaction = self.connection.transaction()?;
+        let detail = match id {
+            Some(id) => {
+                let json: String = transaction.query_row("SELECT detail FROM notes WHERE id = ?",
+                    [id], |row| row.get(0))?;
+                let mut detail = from_json(json)?;
+                detail.note.title = title.to_string();
+                detail.note.body = body.to_string();
+                detail.note.updated_at = now;
+                detail
+            },
+            None => {
+                let lowest: Option<i32> = transaction.query_row("SELECT MIN(id) FROM notes", [],
+                    |row| row.get(0)).optional()?.flatten();
+                NoteDetail{
+                    note: Note{
+                        id: lowest.unwrap_or(0).min(0) - 1,
+                        title: title.to_string(),
+                        body: body.to_string(),
+                        kind: NoteKind::Text,
+                        progress: None,
+                        pinned: false,
+                        archived: false,
+                        trashed: false,
+                        color: None,
+                        remind_at: None,
+                        recurrence: None,
+                        created_at: now,
+                        updated_at: now,
+                        version: 0,
+                    },
+                    labels: Some(Vec::new()),
+                    categories: Some(Vec::new()),
+                }
+            },
+        };
+        transaction.execute("INSERT OR REPLACE INTO notes (id, detail) VALUES (?, ?)",
+            params![detail.note.id, to_json(&detail)?])?;
+        // The local copy keeps the version of the server until the next sync
+        let version = (detail.note.id > 0).then_some(detail.note.version);
+        transaction.execute("INSERT INTO changes (note_id, title, body, version) VALUES (?, ?, ?, ?)",
+            params![detail.note.id, title, body, version])?;
+        transaction.commit()?;
+        Ok(detail.note.id)
+    }
+
+    pub fn changes(&self) -> rusqlite::Result<Vec<Change>>{
+        let mut statement = self.connection.prepare("SELECT seq, note_id, title, body, version FROM changes ORDER BY seq")?;
+        let changes = statement.query_map([], |row| Ok(Change{
+            seq: row.get(0)?,
+            note_id: row.get(1)?,
+            title: row.get(2)?,
+            body: row.get(3)?,
+            version: row.get(4)?,
+        }))?.collect();
+        changes
+    }
+
+    /// The changes to send, one per note: its last title and body, from the
+    /// version of its first change
+    pub fn pending(&self) -> rusqlite::Result<Vec<Change>>{
+        let mut pending: Vec<Change> = Vec::new();
+        for change in self.changes()? {
+            match pending.iter_mut().find(|item| item.note_id == change.note_id) {
+                Some(item) => {
+                    item.seq = change.seq;
+                    item.title = change.title;
+                    item.body = change.body;
+                },
+                None => pending.push(change),
+            }
+        }
+        Ok(pending)
+    }
+
+    /// The changes of the note `local_id` up to `seq` are in the server, as
+    /// the note `id`. A note created without connection gets that id now.
+    pub fn sent(&mut self, local_id: i32, id: i32, seq: i64) -> rusqlite::Result<()>{
+        let transaction = self.connection.transaction()?;
+        if local_id != id {
+            let json: String = transaction.query_row("SELECT detail FROM notes WHERE id = ?",
+                [local_id], |row| row.get(0))?;
+            let mut detail = from_json(json)?;
+            detail.note.id = id;
+            transaction.execute("DELETE FROM notes WHERE id = ?", [local_id])?;
+            transaction.execute("INSERT OR REPLACE INTO notes (id, detail) VALUES (?, ?)",
+                params![id, to_json(&detail)?])?;
+            transaction.execute("UPDATE changes SET note_id = ? WHERE note_id = ?", [id, local_id])?;
+        }
+        transaction.execute("DELETE FROM changes WHERE note_id = ? AND seq <= ?", params![id, seq])?;
+        transaction.commit()
+    }
+}
+
+#[test]
+fn test_cache(){
+    let mut cache = Cache::in_memory().unwrap();
+    let local = cache.edit(None, "Sin conexión", "- pan").unwrap();
+    assert_eq!(local, -1);
+    assert_eq!(cache.edit(None, "Otra", "").unwrap(), -2);
+    cache.edit(Some(local), "Sin conexión", "- pan\n- leche").unwrap();
+
+    // A sync that does not know them yet keeps them
+    cache.replace(&[], &[Label{id: 1, name: "casa".to_string()}], &[]).unwrap();
+    assert_eq!(cache.notes().unwrap().len(), 2);
+    assert_eq!(cache.labels().unwrap()[0].name, "casa");
+
+    let changes = cache.changes().unwrap();
+    assert_eq!(changes.iter().map(|change| change.note_id).collect::<Vec<_>>(), vec![-1, -2, -1]);
+    let pending = cache.pending().unwrap();
+    assert_eq!(pending.iter().map(|change| change.note_id).collect::<Vec<_>>(), vec![-1, -2]);
+    assert_eq!((pending[0].seq, pending[0].body.as_str()), (changes[2].seq, "- pan\n- leche"));
+    cache.sent(local, 7, pending[0].seq).unwrap();
+    let changes = cache.changes().unwrap();
+    assert_eq!(changes.iter().map(|change| change.note_id).collect::<Vec<_>>(), vec![-2]);
+    assert!(cache.notes().unwrap().iter().any(|detail| detail.note.id == 7));
+
+    // Edits of a note of the server start from its version
+    let mut synced = cache.notes().unwrap().into_iter().find(|detail| detail.note.id == 7).unwrap();
+    synced.note.version = 3;
+    cache.replace(&[synced], &[], &[]).unwrap();
+    cache.edit(Some(7), "Sin conexión", "- pan").unwrap();
+    assert_eq!(cache.pending().unwrap()[1].version, Some(3));
+}
@@ -0,0 +1,71 @@
+//! `notis tui`, the notes in the terminal. It works on a SQLite copy of
+//! the notes, so it can be used without connection, and sends the edits
+//! when the server is back. When a note changed on both sides, the local
+//! edit wins.
+mod app;
+mod cache;
+mod ui;
+
+use std::{path::PathBuf, time::{Duration, Instant}};
+
+use notisbak_client::Client;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+
+use crate::{config::Config, Result};
+use app::{Action, App};
+use cache::Cache;
+
+/// How often to try again without connection
+const RETRY: Duration = Duration::from_secs(30);
+
+/// Where the copy of the notes of an account is kept
+fn cache_path(config: &Config) -> PathBuf{
+    let account: String = format!("{}-{}", config.email, config.url)
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    dirs::cache_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("notisbak")
+        .join(format!("{}.sqlite", account))
+}
+
+pub async fn run(client: &Client, config: &Config) -> Result<()>{
+    let mut app = App::new(Cache::open(&cache_path(config))?)?;
+    let mut terminal = ratatui::init();
+    app.status = "Syncing…".to_string();
+    terminal.draw(|frame| ui::draw(frame, &mut app))?;
+    app.sync(client).await;
+    let mut synced = Instant::now();
+    let result = loop {
+        if let Err(e) = terminal.draw(|frame| ui::draw(frame, &mut app)) {
+            break Err(e.into());
+        }
+        if !app.online && synced.elapsed() > RETRY {
+            app.sync(client).await;
+            synced = Instant::now();
+        }
+        match event::poll(Duration::from_millis(250)) {
+            Ok(false) => continue,
+            Ok(true) => {},
+            Err(e) => break Err(e.into()),
+        }
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(e) => break Err(e.into()),
+        };
+        match app.on_key(key) {
+            Action::None => {},
+            Action::Sync => {
+                app.status = "Syncing…".to_string();
+                let _ = terminal.draw(|frame| ui::draw(frame, &mut app));
+                app.sync(client).await;
+                synced = Instant::now();
+            },
+            Action::Quit => break Ok(()),
+        }
+    };
+    ratatui::restore();
+    result
+}
@@ -0,0 +1,115 @@
+use ratatui::{layout::{Constraint, Layout}, style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span}, widgets::{Block, List, ListItem, Paragraph, Wrap}, Frame};
+
+use super::app::{App, Focus};
+
+fn block(title: &str, focused: bool) -> Block<'_>{
+    let block = Block::bordered().title(title);
+    match focused {
+        true => block.border_style(Style::new().fg(Color::Yellow)),
+        false => block,
+    }
+}
+
+fn highlight() -> Style{
+    Style::new().add_modifier(Modifier::REVERSED)
+}
+
+/// "All" first, to see the notes of every one
+fn sidebar_items<'a>(names: impl Iterator<Item = &'a str>) -> Vec<ListItem<'a>>{
+    std::iter::once("All")
+        .chain(names)
+        .map(ListItem::new)
+        .collect()
+}
+
+pub fn draw(frame: &mut Frame, app: &mut App){
+    let [main, status] = Layout::vertical([Constraint::Min(3), Constraint::Length(1)])
+        .areas(frame.area());
+    let [sidebar, notes, preview] = Layout::horizontal([
+        Constraint::Percentage(20),
+        Constraint::Percentage(30),
+        Constraint::Percentage(50),
+    ]).areas(main);
+    let [labels, categories] = Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .areas(sidebar);
+
+    let list = List::new(sidebar_items(app.labels.iter().map(|label| label.name.as_str())))
+        .block(block("Labels", app.focus == Focus::Labels))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, labels, &mut app.label_state);
+    let list = List::new(sidebar_items(app.categories.iter().map(|category| category.name.as_str())))
+        .block(block("Categories", app.focus == Focus::Categories))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, categories, &mut app.category_state);
+
+    let visible = app.visible();
+    let items: Vec<ListItem> = visible.iter()
+        .map(|detail| {
+            let note = &detail.note;
+            let mut spans = Vec::new();
+            if note.pinned {
+                spans.push(Span::raw("📌 "));
+            }
+            spans.push(Span::raw(note.title.clone()));
+            // Created without connection, still not in the server
+            if note.id < 0 {
+                spans.push(Span::raw(" *").dim());
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let title = format!("Notes ({})", items.len());
+    let list = List::new(items)
+        .block(block(&title, app.focus == Focus::Notes))
+        .highlight_style(highlight());
+    frame.render_stateful_widget(list, notes, &mut app.note_state);
+
+    match app.editing.as_mut() {
+        Some(editing) => {
+            editing.text.set_block(block("Editing", true));
+            frame.render_widget(&editing.text, preview);
+        },
+        None => {
+            let text = match app.selected() {
+                Some(detail) => {
+                    let names = |names: Vec<&str>| names.join(", ");
+                    let mut lines = vec![Line::from(detail.note.title.clone()).bold()];
+                    let labels = names(detail.labels.iter().flatten().map(|label| label.name.as_str()).collect());
+                    if !labels.is_empty() {
+                        lines.push(Line::from(format!("Labels: {}", labels)).dim());
+                    }
+                    let categories = names(detail.categories.iter().flatten().map(|category| category.name.as_str()).collect());
+                    if !categories.is_empty() {
+                        lines.push(Line::from(format!("Category: {}", categories)).dim());
+                    }
+                    lines.push(Line::default());
+                    lines.extend(detail.note.body.lines().map(|line| Line::from(line.to_string())));
+                    lines
+                },
+                None => vec![Line::from("No notes").dim()],
+            };
+            let paragraph = Paragraph::new(text)
+                .block(block("Note", false))
+                .wrap(Wrap{trim: false});
+            frame.render_widget(paragraph, preview);
+        },
+    }
+
+    let connection = match app.online {
+        true => Span::raw("● online").green(),
+        false => Span::raw("○ offline").red(),
+    };
+    let pending = match app.pending() {
+        0 => String::new(),
+        pending => format!(" · {} changes to send", pending),
+    };
+    let line = Line::from(vec![
+        connection,
+        Span::raw(pending),
+        Span::raw(" · "),
+        Span::raw(app.status.clone()),
+        Span::raw("  [Tab] pane [e] edit [n] new [s] sync [q] quit").dim(),
+    ]);
+    frame.render_widget(Paragraph::new(line), status);
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use reqwest::StatusCode;
+
+/// What went wrong talking with the server. The server answers errors with
+/// their status and a message as the body.
+#[derive(Debug)]
+pub enum Error{
+    /// 400, like a recurrence that does not parse
+    BadRequest(String),
+    /// 401, without a valid token or with wrong credentials
+    Unauthorized(String),
+    /// 403, without workspace or in a read only one
+    Forbidden(String),
+    /// 404
+    NotFound(String),
+    /// 409, like a label with the name of another
+    Conflict(String),
+    /// Any other status
+    Status{
+        status: u16,
+        message: String,
+    },
+    /// The server could not be reached, or its answer read
+    Http(reqwest::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error{
+    pub(crate) fn from_status(status: StatusCode, message: String) -> Error{
+        match status {
+            StatusCode::BAD_REQUEST => Error::BadRequest(message),
+            StatusCode::UNAUTHORIZED => Error::Unauthorized(message),
+            StatusCode::FORBIDDEN => Error::Forbidden(message),
+            StatusCode::NOT_FOUND => Error::NotFound(message),
+            StatusCode::CONFLICT => Error::Conflict(message),
+            status => Error::Status{
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+
+    /// The status the server answered with, when it answered
+    pub fn status(&self) -> Option<u16>{
+        match self {
+            Error::BadRequest(_) => Some(400),
+            Error::Unauthorized(_) => Some(401),
+            Error::Forbidden(_) => Some(403),
+            Error::NotFound(_) => Some(404),
+            Error::Conflict(_) => Some(409),
+            Error::Status{status, ..} => Some(*status),
+            Error::Http(e) => e.status().map(|status| status.as_u16()),
+        }
+    }
+}
+
+impl fmt::Display for Error{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        match self {
+            Error::BadRequest(message) => write!(f, "Bad request: {}", message),
+            Error::Unauthorized(message) => write!(f, "Unauthorized: {}", message),
+            Error::Forbidden(message) => write!(f, "Forbidden: {}", message),
+            Error::NotFound(message) => write!(f, "Not found: {}", message),
+            Error::Conflict(message) => write!(f, "Conflict: {}", message),
+            Error::Status{status, message} => write!(f, "Error {}: {}", status, message),
+            Error::Http(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>{
+        match self {
+            Error::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error{
+    fn from(e: reqwest::Error) -> Self{
+        Error::Http(e)
+    }
+}
+
+#[test]
+fn test_from_status() {
+    let error = Error::from_status(StatusCode::FORBIDDEN, "Read only workspace".to_string());
+    assert!(matches!(error, Error::Forbidden(_)));
+    assert_eq!(error.to_string(), "Forbidden: Read only workspace");
+    let error = Error::from_status(StatusCode::SERVICE_UNAVAILABLE, "Later".to_string());
+    assert_eq!(error.status(), Some(503));
+}
@@ -0,0 +1,322 @@
+//! Typed async client of the notisbak API, with the same types as the
+//! server.
+//!
+//! ```no_run
+//! # async fn example() -> notisbak_client::Result<()> {
+//! use notisbak_client::{Client, NewNote};
+//!
+//! let client = Client::new("https://notes.example.com");
+//! client.login("tucorreo@tuservidor.es", "secreto").await?;
+//! let note = client.create_note(&NewNote{
+//!     title: "Lista de la compra".to_string(),
+//!     body: None,
+//!     kind: None,
+//!     remind_at: None,
+//!     recurrence: None,
+//! }).await?;
+//! let label = client.create_label("casa").await?;
+//! client.add_label(note.id, label.id).await?;
+//! # Ok(())
+//! # }
+//! ```
+mod error;
+
+use std::sync::RwLock;
+
+use futures_util::{stream, Stream, TryStreamExt};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+pub use error::{Error, Result};
+pub use notisbak::model::{category::{Category, NewCategory}, label::{Label,
+    NewLabel}, note::{Include, NewNote, Note, NoteDetail, NoteKind,
+    UpdateNote}, note_category::NoteCategory, note_label::NoteLabel,
+    user::Credentials, app_token::{CreatedAppToken, NewAppToken}};
+use notisbak::routes::users::AuthResponse;
+
+/// What gets a new token, with the email of the user
+#[derive(Clone)]
+enum Secret{
+    Password(String),
+    AppToken(String),
+}
+
+/// The token in use, and the credentials to get a new one when it expires
+#[derive(Default)]
+struct Session{
+    token: Option<String>,
+    credentials: Option<(String, Secret)>,
+}
+
+pub struct Client{
+    http: reqwest::Client,
+    base: String,
+    session: RwLock<Session>,
+}
+
+impl Client{
+    /// A client of the server at `base`, like `https://notes.example.com`
+    pub fn new(base: &str) -> Self{
+        Self{
+            http: reqwest::Client::new(),
+            base: base.trim_end_matches('/').to_string(),
+            session: RwLock::new(Session::default()),
+        }
+    }
+
+    /// Use a token of the API got elsewhere. It is not refreshed.
+    pub fn with_token(self, token: &str) -> Self{
+        self.set_token(Some(token.to_string()));
+        self
+    }
+
+    /// Keep the credentials to get a new token when the one in use expires,
+    /// without logging in now
+    pub fn with_credentials(self, email: &str, password: &str) -> Self{
+        self.session.write().unwrap().credentials = Some((email.to_string(), Secret::Password(password.to_string())));
+        self
+    }
+
+    /// Keep an app token to get a new token when the one in use expires,
+    /// without getting one now. Unlike the password, it can be revoked.
+    pub fn with_app_token(self, email: &str, app_token: &str) -> Self{
+        self.session.write().unwrap().credentials = Some((email.to_string(), Secret::AppToken(app_token.to_string())));
+        self
+    }
+
+    pub fn token(&self) -> Option<String>{
+        self.session.read().unwrap().token.clone()
+    }
+
+    fn set_token(&self, token: Option<String>){
+        self.session.write().unwrap().token = token;
+    }
+
+    /// Register a user, with its personal workspace, and log in as it
+    pub async fn register(&self, email: &str, password: &str) -> Result<()>{
+        self.authenticate("/auth/register", email, password).await
+    }
+
+    /// Log in. The credentials are kept to refresh the token when the
+    /// server does not take it anymore.
+    pub async fn login(&self, email: &str, password: &str) -> Result<()>{
+        self.authenticate("/auth/login", email, password).await
+    }
+
+    /// Get a token with an app token, and keep it to refresh the token
+    pub async fn login_with_app_token(&self, email: &str, app_token: &str) -> Result<()>{
+        let response = self.http.post(format!("{}/auth/token", self.base))
+            .basic_auth(email, Some(app_token))
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::from_status(status, response.text().await.unwrap_or_default()));
+        }
+        let response: AuthResponse = response.json().await?;
+        let mut session = self.session.write().unwrap();
+        session.token = response.token;
+        session.credentials = Some((email.to_string(), Secret::AppToken(app_token.to_string())));
+        Ok(())
+    }
+
+    /// Log in again with the credentials of the last login
+    pub async fn refresh(&self) -> Result<()>{
+        let credentials = self.session.read().unwrap().credentials.clone();
+        match credentials {
+            Some((email, Secret::Password(password))) => self.login(&email, &password).await,
+            Some((email, Secret::AppToken(app_token))) => self.login_with_app_token(&email, &app_token).await,
+            None => Err(Error::Unauthorized("Without credentials to refresh the token".to_string())),
+        }
+    }
+
+    async fn authenticate(&self, path: &str, email: &str, password: &str) -> Result<()>{
+        let credentials = Credentials{
+            email: email.to_string(),
+            password: password.to_string(),
+        };
+        let response: AuthResponse = self.attempt(Method::POST, path, Some(&credentials)).await?;
+        let mut session = self.session.write().unwrap();
+        session.token = response.token;
+        session.credentials = Some((credentials.email, Secret::Password(credentials.password)));
+        Ok(())
+    }
+
+    async fn attempt<T: DeserializeOwned, B: Serialize>(&self, method: Method, path: &str, body: Option<&B>) -> Result<T>{
+        let mut request = self.http.request(method, format!("{}{}", self.base, path));
+        if let Some(token) = self.token() {
+            request = request.bearer_auth(token);
+        }
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(response.json().await?)
+        }else{
+            Err(Error::from_status(status, response.text().await.unwrap_or_default()))
+        }
+    }
+
+    /// Send the request, and once again with a new token when the server
+    /// does not take the one in use
+    async fn call<T: DeserializeOwned, B: Serialize>(&self, method: Method, path: &str, body: Option<&B>) -> Result<T>{
+        match self.attempt(method.clone(), path, body).await {
+            Err(Error::Unauthorized(_)) if self.session.read().unwrap().credentials.is_some() => {
+                self.refresh().await?;
+                self.attempt(method, path, body).await
+            },
+            result => result,
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T>{
+        self.call(Method::GET, path, None::<&Value>).await
+    }
+
+    async fn delete<T: DeserializeOwned>(&self, path: &str) -> Result<T>{
+        self.call(Method::DELETE, path, None::<&Value>).await
+    }
+
+    /// A new app token. It is only answered now.
+    pub async fn create_app_token(&self, name: &str) -> Result<CreatedAppToken>{
+        self.call(Method::POST, "/api/v1/tokens", Some(&NewAppToken{name: name.to_string()})).await
+    }
+
+    pub async fn notes(&self) -> Result<Vec<Note>>{
+        self.get("/api/v1/notes").await
+    }
+
+    /// The notes with their labels or categories
+    pub async fn notes_with(&self, include: Include) -> Result<Vec<NoteDetail>>{
+        self.get(&format!("/api/v1/notes?include={}", include)).await
+    }
+
+    /// The notes in pages of `size`, by id, until there are no more
+    pub fn note_pages(&self, size: i64) -> impl Stream<Item = Result<Vec<Note>>> + '_{
+        // The state is the id after which the next page starts, or None
+        // when the last page has been read
+        stream::try_unfold(Some(None), move |after: Option<Option<i32>>| async move {
+            let after = match after {
+                Some(after) => after,
+                None => return Ok(None),
+            };
+            let path = match after {
+                Some(id) => format!("/api/v1/notes?limit={}&after={}", size, id),
+                None => format!("/api/v1/notes?limit={}", size),
+            };
+            let page: Vec<Note> = self.get(&path).await?;
+            if page.is_empty() {
+                return Ok(None);
+            }
+            let next = match (page.len() as i64) < size {
+                true => None,
+                false => Some(page.last().map(|note| note.id)),
+            };
+            Ok(Some((page, next)))
+        })
+    }
+
+    /// Every note, read in pages of `size`
+    pub fn all_notes(&self, size: i64) -> impl Stream<Item = Result<Note>> + '_{
+        self.note_pages(size)
+            .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
+    pub async fn note(&self, id: i32) -> Result<Note>{
+        self.get(&format!("/api/v1/notes/{}", id)).await
+    }
+
+    /// A note with its labels or categories
+    pub async fn note_with(&self, id: i32, include: Include) -> Result<NoteDetail>{
+        self.get(&format!("/api/v1/notes/{}?include={}", id, include)).await
+    }
+
+    pub async fn create_note(&self, note: &NewNote) -> Result<Note>{
+        self.call(Method::POST, "/api/v1/notes", Some(note)).await
+    }
+
+    /// Change the fields of the note that are set, keeping the rest
+    pub async fn update_note(&self, note: &UpdateNote) -> Result<Note>{
+        self.call(Method::PUT, "/api/v1/notes", Some(note)).await
+    }
+
+    pub async fn delete_note(&self, id: i32) -> Result<Note>{
+        self.delete(&format!("/api/v1/notes/{}", id)).await
+    }
+
+    pub async fn labels(&self) -> Result<Vec<Label>>{
+        self.get("/api/v1/labels").await
+    }
+
+    pub async fn label(&self, id: i32) -> Result<Label>{
+        self.get(&format!("/api/v1/labels/{}", id)).await
+    }
+
+    pub async fn create_label(&self, name: &str) -> Result<Label>{
+        let label = NewLabel{
+            name: name.to_string(),
+        };
+        self.call(Method::POST, "/api/v1/labels", Some(&label)).await
+    }
+
+    pub async fn update_label(&self, label: &Label) -> Result<Label>{
+        self.call(Method::PUT, "/api/v1/labels", Some(label)).await
+    }
+
+    pub async fn delete_label(&self, id: i32) -> Result<Label>{
+        self.delete(&format!("/api/v1/labels/{}", id)).await
+    }
+
+    pub async fn categories(&self) -> Result<Vec<Category>>{
+        self.get("/api/v1/categories").await
+    }
+
+    pub async fn category(&self, id: i32) -> Result<Category>{
+        self.get(&format!("/api/v1/categories/{}", id)).await
+    }
+
+    pub async fn create_category(&self, name: &str) -> Result<Category>{
+        let category = NewCategory{
+            name: name.to_string(),
+        };
+        self.call(Method::POST, "/api/v1/categories", Some(&category)).await
+    }
+
+    pub async fn update_category(&self, category: &Category) -> Result<Category>{
+        self.call(Method::PUT, "/api/v1/categories", Some(category)).await
+    }
+
+    pub async fn delete_category(&self, id: i32) -> Result<Category>{
+        self.delete(&format!("/api/v1/categories/{}", id)).await
+    }
+
+    /// The labels of a note
+    pub async fn note_labels(&self, note_id: i32) -> Result<Vec<Label>>{
+        self.get(&format!("/api/v1/notes/{}/labels/", note_id)).await
+    }
+
+    pub async fn add_label(&self, note_id: i32, label_id: i32) -> Result<NoteLabel>{
+        self.call(Method::PUT, &format!("/api/v1/notes/{}/labels/{}", note_id, label_id), None::<&Value>).await
+    }
+
+    pub async fn remove_label(&self, note_id: i32, label_id: i32) -> Result<NoteLabel>{
+        self.delete(&format!("/api/v1/notes/{}/labels/{}", note_id, label_id)).await
+    }
+
+    /// The categories of a note
+    pub async fn note_categories(&self, note_id: i32) -> Result<Vec<Category>>{
+        self.get(&format!("/api/v1/notes/{}/categories/", note_id)).await
+    }
+
+    pub async fn add_category(&self, note_id: i32, category_id: i32) -> Result<NoteCategory>{
+        self.call(Method::PUT, &format!("/api/v1/notes/{}/categories/{}", note_id, category_id), None::<&Value>).await
+    }
+
+    pub async fn remove_category(&self, note_id: i32, category_id: i32) -> Result<NoteCategory>{
+        self.delete(&format!("/api/v1/notes/{}/categories/{}", note_id, category_id)).await
+    }
+}
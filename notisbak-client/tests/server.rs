@@ -0,0 +1,165 @@
+//! The client against the server running in the same process. It needs the
+//! PostgreSQL of `DATABASE_URL`, so these tests are ignored unless asked for
+//! with `cargo test -- --ignored`.
+use std::{env, time::{SystemTime, UNIX_EPOCH}};
+
+use actix_web::{App, HttpServer, web::Data};
+use futures_util::TryStreamExt;
+use notisbak::{admin, config, graphql, model::user::User, routes};
+use notisbak_client::{Client, Error, Include, NewNote, UpdateNote};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+
+/// The address of a server on a free port and its pool
+async fn spawn_server() -> (String, PgPool){
+    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL is required by these tests");
+    if env::var("SECRET").is_err() {
+        env::set_var("SECRET", "client-tests");
+    }
+    if env::var("EXPIRATION").is_err() {
+        env::set_var("EXPIRATION", "3600");
+    }
+    let pool = PgPoolOptions::new()
+        .max_connections(2)
+        .connect(&db_url)
+        .await
+        .expect("pool failed");
+    admin::migrate_up(&pool, false).await.unwrap();
+    let schema = graphql::schema();
+    let server_pool = pool.clone();
+    let server = HttpServer::new(move ||{
+        App::new()
+            .app_data(Data::new(server_pool.clone()))
+            .app_data(Data::new(schema.clone()))
+            .app_data(Data::new(config::get().clone()))
+            .configure(routes::config)
+    })
+    .workers(1)
+    .bind("127.0.0.1:0")
+    .unwrap();
+    let address = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    (format!("http://{}", address), pool)
+}
+
+fn new_note(title: &str) -> NewNote{
+    NewNote{
+        title: title.to_string(),
+        body: Some(format!("Body of {}", title)),
+        kind: None,
+        remind_at: None,
+        recurrence: None,
+    }
+}
+
+#[actix_web::test]
+#[ignore = "needs DATABASE_URL"]
+async fn test_client() {
+    let (base, pool) = spawn_server().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let email = format!("client-{}@example.com", nanos);
+    let client = Client::new(&base);
+    client.register(&email, "secreto").await.unwrap();
+    assert!(client.token().is_some());
+
+    let mut ids = Vec::new();
+    for i in 0..5 {
+        ids.push(client.create_note(&new_note(&format!("Note {}", i))).await.unwrap().id);
+    }
+    let label = client.create_label("work").await.unwrap();
+    let category = client.create_category("home").await.unwrap();
+    let link = client.add_label(ids[0], label.id).await.unwrap();
+    assert_eq!((link.note_id, link.label_id), (ids[0], label.id));
+    client.add_category(ids[0], category.id).await.unwrap();
+
+    let detail = client.note_with(ids[0], Include{labels: true, categories: true}).await.unwrap();
+    assert_eq!(detail.note.title, "Note 0");
+    assert_eq!(detail.labels.unwrap()[0].name, "work");
+    assert_eq!(detail.categories.unwrap()[0].name, "home");
+    assert_eq!(client.note_labels(ids[0]).await.unwrap().len(), 1);
+    client.remove_label(ids[0], label.id).await.unwrap();
+    assert!(client.note_labels(ids[0]).await.unwrap().is_empty());
+
+    let note = client.update_note(&UpdateNote{
+        id: ids[1],
+        title: "Renamed".to_string(),
+        body: None,
+        kind: None,
+        pinned: Some(true),
+        archived: None,
+        trashed: None,
+        color: None,
+        version: None,
+    }).await.unwrap();
+    assert_eq!((note.title.as_str(), note.body.as_str(), note.pinned), ("Renamed", "Body of Note 1", true));
+    // Changed since the version read, it is not updated
+    let stale = UpdateNote{
+        id: ids[1],
+        title: "Stale".to_string(),
+        body: None,
+        kind: None,
+        pinned: None,
+        archived: None,
+        trashed: None,
+        color: None,
+        version: Some(note.version - 1),
+    };
+    assert!(matches!(client.update_note(&stale).await, Err(Error::Conflict(_))));
+
+    let pages: Vec<_> = client.note_pages(2).try_collect().await.unwrap();
+    assert_eq!(pages.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+    let notes: Vec<_> = client.all_notes(3).try_collect().await.unwrap();
+    assert_eq!(notes.iter().map(|note| note.id).collect::<Vec<_>>(), ids);
+
+    assert!(matches!(client.note(-1).await, Err(Error::NotFound(_))));
+    assert!(matches!(client.create_label("work").await, Err(Error::Conflict(_))));
+    assert!(matches!(client.create_note(&NewNote{recurrence: Some("never".to_string()), ..new_note("x")}).await,
+        Err(Error::BadRequest(_))));
+
+    // A token the server does not take is refreshed with the credentials
+    let client = client.with_token("not-a-token");
+    assert_eq!(client.notes().await.unwrap().len(), 5);
+    assert_ne!(client.token().as_deref(), Some("not-a-token"));
+    // but without them it is an error
+    let anonymous = Client::new(&base).with_token("not-a-token");
+    assert!(matches!(anonymous.notes().await, Err(Error::Unauthorized(_))));
+    assert!(matches!(Client::new(&base).login(&email, "wrong").await, Err(Error::Unauthorized(_))));
+
+    // A disabled user can not use the tokens it already has
+    let pool = Data::new(pool);
+    let user = User::get_by_email(&pool, &email).await.unwrap();
+    User::set_disabled(&pool, user.id, true).await.unwrap();
+    assert!(matches!(client.notes().await, Err(Error::Forbidden(_))));
+    User::set_disabled(&pool, user.id, false).await.unwrap();
+    assert_eq!(client.notes().await.unwrap().len(), 5);
+
+    assert_eq!(client.delete_note(ids[4]).await.unwrap().id, ids[4]);
+    client.delete_label(label.id).await.unwrap();
+    client.delete_category(category.id).await.unwrap();
+}
+
+#[actix_web::test]
+#[ignore = "needs DATABASE_URL"]
+async fn test_feed_of_other_workspace() {
+    let (base, _pool) = spawn_server().await;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+    let owner = Client::new(&base);
+    owner.register(&format!("feed-owner-{}@example.com", nanos), "secreto").await.unwrap();
+    let other = Client::new(&base);
+    other.register(&format!("feed-other-{}@example.com", nanos), "secreto").await.unwrap();
+    let label = owner.create_label("published").await.unwrap();
+
+    let http = reqwest::Client::new();
+    let publish = |token: String| http.post(format!("{}/api/v1/feeds", base))
+        .bearer_auth(token)
+        .json(&serde_json::json!({"source": "label", "source_id": label.id}))
+        .send();
+    let created = publish(owner.token().unwrap()).await.unwrap();
+    assert_eq!(created.status(), 201);
+    let feed: serde_json::Value = created.json().await.unwrap();
+    // The label of another workspace is not found, nor its feed answered
+    let answer = publish(other.token().unwrap()).await.unwrap();
+    assert_eq!(answer.status(), 404);
+    assert!(!answer.text().await.unwrap().contains(feed["token"].as_str().unwrap()));
+
+    owner.delete_label(label.id).await.unwrap();
+}
@@ -0,0 +1,243 @@
+//! What the operator does from the command line, besides serving: the
+//! migrations and the figures of the server.
+use std::{collections::HashMap, fmt};
+
+use actix_web::web;
+use sqlx::{query, Connection, Error, Row, migrate::{Migrate, MigrateError, Migrator},
+    pool::PoolConnection, postgres::{PgConnection, PgPool, PgRow}, Postgres};
+
+/// The migrations, compiled into the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MigrationState{
+    Applied,
+    Pending,
+    /// Applied with other content than the one of the binary
+    Changed,
+    /// Applied, but not in the binary
+    Missing,
+}
+
+impl MigrationState{
+    pub fn as_str(&self) -> &'static str{
+        match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::Changed => "changed",
+            MigrationState::Missing => "missing",
+        }
+    }
+}
+
+/// A migration of the binary or of the database, and how they compare
+#[derive(Debug)]
+pub struct MigrationStatus{
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+pub async fn migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError>{
+    let mut conn = pool.acquire().await?;
+    status(&mut conn).await
+}
+
+async fn status(conn: &mut PgConnection) -> Result<Vec<MigrationStatus>, MigrateError>{
+    conn.ensure_migrations_table().await?;
+    let mut applied: HashMap<i64, Vec<u8>> = conn.list_applied_migrations().await?
+        .into_iter()
+        .map(|migration| (migration.version, migration.checksum.into_owned()))
+        .collect();
+    let mut status: Vec<MigrationStatus> = MIGRATOR.iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| MigrationStatus{
+            version: migration.version,
+            description: migration.description.to_string(),
+            state: match applied.remove(&migration.version) {
+                None => MigrationState::Pending,
+                Some(checksum) if checksum == *migration.checksum => MigrationState::Applied,
+                Some(_) => MigrationState::Changed,
+            },
+        })
+        .collect();
+    status.extend(applied.into_keys().map(|version| MigrationStatus{
+        version,
+        description: String::new(),
+        state: MigrationState::Missing,
+    }));
+    status.sort_by_key(|migration| migration.version);
+    Ok(status)
+}
+
+fn is_drift(migration: &MigrationStatus) -> bool{
+    matches!(migration.state, MigrationState::Changed | MigrationState::Missing)
+}
+
+/// The migrations applied that differ from the ones of the binary
+pub async fn drift(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError>{
+    Ok(migration_status(pool).await?
+        .into_iter()
+        .filter(is_drift)
+        .collect())
+}
+
+fn refuse_drift(status: &[MigrationStatus]) -> Result<(), MigrateError>{
+    match status.iter().find(|migration| is_drift(migration)) {
+        Some(migration) if migration.state == MigrationState::Changed => Err(MigrateError::VersionMismatch(migration.version)),
+        Some(migration) => Err(MigrateError::VersionMissing(migration.version)),
+        None => Ok(()),
+    }
+}
+
+/// The migrations of the binary, up or down ones
+fn migrations(down: bool) -> impl Iterator<Item = &'static sqlx::migrate::Migration>{
+    MIGRATOR.iter().filter(move |migration| migration.migration_type.is_down_migration() == down)
+}
+
+/// Release the lock of the migrations taken for `result`, whatever it is
+async fn unlock(mut conn: PoolConnection<Postgres>, result: Result<Vec<i64>, MigrateError>) -> Result<Vec<i64>, MigrateError>{
+    if let Err(e) = conn.unlock().await {
+        // Closing the session releases the lock too
+        let _ = conn.detach().close().await;
+        result?;
+        return Err(e);
+    }
+    result
+}
+
+/// Under the lock, so two instances do not apply the same ones
+async fn up(conn: &mut PgConnection, allow_drift: bool) -> Result<Vec<i64>, MigrateError>{
+    let status = status(conn).await?;
+    if !allow_drift {
+        refuse_drift(&status)?;
+    }
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+    let pending: Vec<i64> = status.iter()
+        .filter(|migration| migration.state == MigrationState::Pending)
+        .map(|migration| migration.version)
+        .collect();
+    for migration in migrations(false).filter(|migration| pending.contains(&migration.version)) {
+        conn.apply(migration).await?;
+    }
+    Ok(pending)
+}
+
+async fn down(conn: &mut PgConnection, steps: usize, allow_drift: bool) -> Result<Vec<i64>, MigrateError>{
+    let mut applied = status(conn).await?;
+    if !allow_drift {
+        refuse_drift(&applied)?;
+    }
+    if let Some(version) = conn.dirty_version().await? {
+        return Err(MigrateError::Dirty(version));
+    }
+    applied.retain(|migration| migration.state != MigrationState::Pending);
+    let reverted = applied.split_off(applied.len().saturating_sub(steps));
+    let mut versions = Vec::new();
+    for status in reverted.iter().rev() {
+        // Without it in the binary there is nothing to revert it with
+        let migration = migrations(true)
+            .find(|migration| migration.version == status.version)
+            .ok_or(MigrateError::VersionMissing(status.version))?;
+        conn.revert(migration).await?;
+        versions.push(status.version);
+    }
+    Ok(versions)
+}
+
+/// Apply the pending migrations, and return their versions. With drift it
+/// fails, unless it is allowed.
+pub async fn migrate_up(pool: &PgPool, allow_drift: bool) -> Result<Vec<i64>, MigrateError>{
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+    let result = up(&mut conn, allow_drift).await;
+    unlock(conn, result).await
+}
+
+/// Revert the last `steps` migrations applied, and return their versions
+pub async fn migrate_down(pool: &PgPool, steps: usize, allow_drift: bool) -> Result<Vec<i64>, MigrateError>{
+    let mut conn = pool.acquire().await?;
+    conn.lock().await?;
+    let result = down(&mut conn, steps, allow_drift).await;
+    unlock(conn, result).await
+}
+
+/// Figures of the whole server
+#[derive(Debug, Clone)]
+pub struct Stats{
+    pub users: i64,
+    pub disabled_users: i64,
+    pub workspaces: i64,
+    pub notes: i64,
+    pub archived_notes: i64,
+    pub trashed_notes: i64,
+    pub labels: i64,
+    pub categories: i64,
+    pub attachments: i64,
+    pub pending_reminders: i64,
+    pub pending_deliveries: i64,
+    /// Bytes of the database on disk
+    pub database_size: i64,
+}
+
+impl Stats{
+    pub async fn read(pool: &web::Data<PgPool>) -> Result<Stats, Error>{
+        query(r#"SELECT
+            (SELECT COUNT(*) FROM users) AS users,
+            (SELECT COUNT(*) FROM users WHERE disabled) AS disabled_users,
+            (SELECT COUNT(*) FROM workspaces) AS workspaces,
+            (SELECT COUNT(*) FROM notes) AS notes,
+            (SELECT COUNT(*) FROM notes WHERE archived) AS archived_notes,
+            (SELECT COUNT(*) FROM notes WHERE trashed) AS trashed_notes,
+            (SELECT COUNT(*) FROM labels) AS labels,
+            (SELECT COUNT(*) FROM categories) AS categories,
+            (SELECT COUNT(*) FROM attachments) AS attachments,
+            (SELECT COUNT(*) FROM notes WHERE remind_at IS NOT NULL) AS pending_reminders,
+            (SELECT COUNT(*) FROM webhook_deliveries WHERE status = 'pending') AS pending_deliveries,
+            pg_database_size(current_database()) AS database_size"#)
+            .map(|row: PgRow| Stats{
+                users: row.get("users"),
+                disabled_users: row.get("disabled_users"),
+                workspaces: row.get("workspaces"),
+                notes: row.get("notes"),
+                archived_notes: row.get("archived_notes"),
+                trashed_notes: row.get("trashed_notes"),
+                labels: row.get("labels"),
+                categories: row.get("categories"),
+                attachments: row.get("attachments"),
+                pending_reminders: row.get("pending_reminders"),
+                pending_deliveries: row.get("pending_deliveries"),
+                database_size: row.get("database_size"),
+            })
+            .fetch_one(pool.get_ref())
+            .await
+    }
+}
+
+impl fmt::Display for Stats{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        writeln!(f, "Users:              {} ({} disabled)", self.users, self.disabled_users)?;
+        writeln!(f, "Workspaces:         {}", self.workspaces)?;
+        writeln!(f, "Notes:              {} ({} archived, {} in the trash)",
+            self.notes, self.archived_notes, self.trashed_notes)?;
+        writeln!(f, "Labels:             {}", self.labels)?;
+        writeln!(f, "Categories:         {}", self.categories)?;
+        writeln!(f, "Attachments:        {}", self.attachments)?;
+        writeln!(f, "Pending reminders:  {}", self.pending_reminders)?;
+        writeln!(f, "Pending deliveries: {}", self.pending_deliveries)?;
+        write!(f, "Database size:      {:.1} MB", self.database_size as f64 / (1024.0 * 1024.0))
+    }
+}
+
+#[test]
+fn test_migrator(){
+    let versions = |down: bool| MIGRATOR.iter()
+        .filter(|migration| migration.migration_type.is_down_migration() == down)
+        .map(|migration| migration.version)
+        .collect::<Vec<_>>();
+    // Every migration can be reverted from the admin CLI
+    assert!(!versions(false).is_empty());
+    assert_eq!(versions(false), versions(true));
+}
@@ -0,0 +1,85 @@
+use chrono::{NaiveDateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+use crate::model::webhook::WebhookDelivery;
+
+/// Events pending to be read by a slow subscriber before it starts to lose them
+const CAPACITY: usize = 256;
+
+static BUS: Lazy<Sender<Event>> = Lazy::new(|| broadcast::channel(CAPACITY).0);
+
+/// Something that happened in a workspace, delivered to realtime subscribers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event{
+    pub kind: String,
+    pub workspace_id: i32,
+    pub payload: Value,
+    pub created_at: NaiveDateTime,
+    /// Only for this member of the workspace, when it is personal
+    #[serde(skip)]
+    pub user_id: Option<i32>,
+}
+
+impl Event{
+    pub fn new(kind: &str, workspace_id: i32, payload: Value) -> Self{
+        Self{
+            kind: kind.to_string(),
+            workspace_id,
+            payload,
+            created_at: Utc::now().naive_utc(),
+            user_id: None,
+        }
+    }
+
+    /// An event that only `user_id` gets
+    pub fn for_user(kind: &str, workspace_id: i32, user_id: i32, payload: Value) -> Self{
+        Self{
+            user_id: Some(user_id),
+            ..Self::new(kind, workspace_id, payload)
+        }
+    }
+
+    /// Whether a subscriber to the workspace as `user_id` gets it
+    pub fn is_for(&self, workspace_id: i32, user_id: i32) -> bool{
+        self.workspace_id == workspace_id && self.user_id.map_or(true, |id| id == user_id)
+    }
+}
+
+/// Send an event to every subscriber. Nobody listening is not an error.
+pub fn publish(event: Event){
+    let _ = BUS.send(event);
+}
+
+pub fn subscribe() -> Receiver<Event>{
+    BUS.subscribe()
+}
+
+/// Publish that `kind` happened to `value`, like `note.created` with the
+/// note. It is queued for the webhooks first, in the database, so they get
+/// it even when a slow subscriber of the bus loses it.
+pub async fn notify<T: Serialize>(pool: &PgPool, kind: &str, workspace_id: i32, value: &T) -> Result<(), sqlx::Error>{
+    let payload = match serde_json::to_value(value) {
+        Ok(payload) => payload,
+        Err(e) => {
+            log::error!("Can not publish {}: {}", kind, e);
+            return Ok(());
+        },
+    };
+    let event = Event::new(kind, workspace_id, payload);
+    WebhookDelivery::enqueue(pool, &event).await?;
+    publish(event);
+    Ok(())
+}
+
+#[test]
+fn test_is_for() {
+    let event = Event::new("note.created", 1, Value::Null);
+    assert!(event.is_for(1, 7) && !event.is_for(2, 7));
+    let event = Event::for_user("reminder.due", 1, 7, Value::Null);
+    assert!(event.is_for(1, 7) && !event.is_for(1, 8));
+    assert!(!serde_json::to_string(&event).unwrap().contains("user_id"));
+}
@@ -0,0 +1,337 @@
+//! A versioned archive with everything in a workspace, to back it up or to
+//! move it to another instance. It is restored by [`crate::importer::archive`].
+//!
+//! The ids in the archive are the ones of the exported workspace: notes
+//! point to their labels and categories by them, and restoring gives them
+//! new ids.
+use std::collections::HashMap;
+
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use utoipa::ToSchema;
+
+use crate::model::{category::Category, label::Label, note::NoteKind};
+
+/// Version of the archives written now. Older versions can still be read.
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedItem{
+    #[schema(example = "Comprar pan")]
+    pub text: String,
+    pub checked: bool,
+}
+
+/// Base64 in the archive
+mod base64_data{
+    use super::*;
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error>{
+        serializer.serialize_str(&base64::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error>{
+        let text = String::deserialize(deserializer)?;
+        base64::decode(text).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedAttachment{
+    #[schema(example = "foto.jpg")]
+    pub filename: String,
+    #[schema(example = "image/jpeg")]
+    pub mime_type: String,
+    /// The content of the file in base64
+    #[serde(with = "base64_data")]
+    #[schema(value_type = String)]
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedNote{
+    pub id: i32,
+    #[schema(example = "Titulo")]
+    pub title: String,
+    #[schema(example = "Contenido")]
+    pub body: String,
+    pub kind: NoteKind,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub trashed: bool,
+    pub color: Option<String>,
+    pub remind_at: Option<NaiveDateTime>,
+    pub recurrence: Option<String>,
+    #[serde(default)]
+    pub snoozed_until: Option<NaiveDateTime>,
+    /// When the reminder was last sent
+    #[serde(default)]
+    pub reminded_at: Option<NaiveDateTime>,
+    /// Where the note was imported from
+    #[schema(example = "keep:Compra")]
+    pub source: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    #[serde(default)]
+    pub items: Vec<ArchivedItem>,
+    /// Ids of the labels of the note in the archive
+    #[serde(default)]
+    pub labels: Vec<i32>,
+    /// Ids of the categories of the note in the archive
+    #[serde(default)]
+    pub categories: Vec<i32>,
+    #[serde(default)]
+    pub attachments: Vec<ArchivedAttachment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Archive{
+    #[schema(example = 1)]
+    pub version: u32,
+    pub exported_at: NaiveDateTime,
+    pub labels: Vec<Label>,
+    pub categories: Vec<Category>,
+    pub notes: Vec<ArchivedNote>,
+}
+
+/// A line of an archive in JSON Lines. The header goes first.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Record{
+    Header{
+        version: u32,
+        exported_at: NaiveDateTime,
+    },
+    Label(Label),
+    Category(Category),
+    Note(Box<ArchivedNote>),
+}
+
+/// Notes read from the database at once, so a workspace with its
+/// attachments is not held in memory when it is streamed
+const PAGE: i64 = 50;
+
+/// The ids of what is associated to each of the notes
+async fn note_ids(pool: &web::Data<PgPool>, join: &str, column: &str, notes: &[i32]) -> Result<HashMap<i32, Vec<i32>>, Error>{
+    let sql = format!("SELECT note_id, {column} FROM {join} WHERE note_id = ANY($1) ORDER BY {column}");
+    let rows: Vec<(i32, i32)> = query(&sql)
+        .bind(notes.to_vec())
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_all(pool.get_ref())
+        .await?;
+    let mut ids: HashMap<i32, Vec<i32>> = HashMap::new();
+    for (note_id, id) in rows {
+        ids.entry(note_id).or_default().push(id);
+    }
+    Ok(ids)
+}
+
+/// The labels and the categories of the workspace
+pub async fn labels_and_categories(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<(Vec<Label>, Vec<Category>), Error>{
+    let mut labels = Label::all(pool.clone(), workspace_id).await?;
+    labels.sort_by_key(|label| label.id);
+    let mut categories = Category::all(pool.clone(), workspace_id).await?;
+    categories.sort_by_key(|category| category.id);
+    Ok((labels, categories))
+}
+
+/// The next page of notes of the workspace, trashed ones included, with
+/// ids after `after`. Empty after the last one.
+pub async fn notes_after(pool: &web::Data<PgPool>, workspace_id: i32, after: i32) -> Result<Vec<ArchivedNote>, Error>{
+    let mut notes = query(r#"SELECT id, title, body, kind, pinned, archived, trashed, color,
+        remind_at, recurrence, snoozed_until, reminded_at, source, created_at, updated_at
+        FROM notes WHERE workspace_id = $1 AND id > $2 ORDER BY id LIMIT $3"#)
+        .bind(workspace_id)
+        .bind(after)
+        .bind(PAGE)
+        .map(|row: PgRow| ArchivedNote{
+            id: row.get("id"),
+            title: row.get("title"),
+            body: row.get("body"),
+            kind: row.get::<String, _>("kind").parse().unwrap_or_default(),
+            pinned: row.get("pinned"),
+            archived: row.get("archived"),
+            trashed: row.get("trashed"),
+            color: row.get("color"),
+            remind_at: row.get("remind_at"),
+            recurrence: row.get("recurrence"),
+            snoozed_until: row.get("snoozed_until"),
+            reminded_at: row.get("reminded_at"),
+            source: row.get("source"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            items: Vec::new(),
+            labels: Vec::new(),
+            categories: Vec::new(),
+            attachments: Vec::new(),
+        })
+        .fetch_all(pool.get_ref())
+        .await?;
+    if notes.is_empty() {
+        return Ok(notes);
+    }
+    let ids: Vec<i32> = notes.iter().map(|note| note.id).collect();
+    let mut note_labels = note_ids(pool, "notes_labels", "label_id", &ids).await?;
+    let mut note_categories = note_ids(pool, "notes_categories", "category_id", &ids).await?;
+    let mut items: HashMap<i32, Vec<ArchivedItem>> = HashMap::new();
+    let rows = query(r#"SELECT i.note_id, i.text, i.checked FROM note_items i
+        INNER JOIN notes n ON n.id = i.note_id
+        WHERE i.note_id = ANY($1) AND n.kind = 'checklist'
+        ORDER BY i.note_id, i.position, i.id"#)
+        .bind(&ids)
+        .map(|row: PgRow| (row.get::<i32, _>("note_id"), ArchivedItem{
+            text: row.get("text"),
+            checked: row.get("checked"),
+        }))
+        .fetch_all(pool.get_ref())
+        .await?;
+    for (note_id, item) in rows {
+        items.entry(note_id).or_default().push(item);
+    }
+    let mut attachments: HashMap<i32, Vec<ArchivedAttachment>> = HashMap::new();
+    let rows = query(r#"SELECT note_id, filename, mime_type, data FROM attachments
+        WHERE note_id = ANY($1)
+        ORDER BY id"#)
+        .bind(&ids)
+        .map(|row: PgRow| (row.get::<i32, _>("note_id"), ArchivedAttachment{
+            filename: row.get("filename"),
+            mime_type: row.get("mime_type"),
+            data: row.get("data"),
+        }))
+        .fetch_all(pool.get_ref())
+        .await?;
+    for (note_id, attachment) in rows {
+        attachments.entry(note_id).or_default().push(attachment);
+    }
+    for note in notes.iter_mut() {
+        note.items = items.remove(&note.id).unwrap_or_default();
+        note.labels = note_labels.remove(&note.id).unwrap_or_default();
+        note.categories = note_categories.remove(&note.id).unwrap_or_default();
+        note.attachments = attachments.remove(&note.id).unwrap_or_default();
+    }
+    Ok(notes)
+}
+
+/// Everything in the workspace, trashed notes included
+pub async fn load(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Archive, Error>{
+    let (labels, categories) = labels_and_categories(pool, workspace_id).await?;
+    let mut notes = Vec::new();
+    loop {
+        let page = notes_after(pool, workspace_id, notes.last().map_or(0, |note: &ArchivedNote| note.id)).await?;
+        if page.is_empty() {
+            break;
+        }
+        notes.extend(page);
+    }
+    Ok(Archive{
+        version: VERSION,
+        exported_at: Utc::now().naive_utc(),
+        labels,
+        categories,
+        notes,
+    })
+}
+
+fn to_lines(records: impl Iterator<Item = Record>) -> serde_json::Result<Vec<u8>>{
+    let mut data = Vec::new();
+    for record in records {
+        serde_json::to_writer(&mut data, &record)?;
+        data.push(b'\n');
+    }
+    Ok(data)
+}
+
+/// The first lines of an archive in JSON Lines: the header, the labels and
+/// the categories
+pub fn jsonl_head(exported_at: NaiveDateTime, labels: Vec<Label>, categories: Vec<Category>) -> serde_json::Result<Vec<u8>>{
+    let header = Record::Header{
+        version: VERSION,
+        exported_at,
+    };
+    to_lines(std::iter::once(header)
+        .chain(labels.into_iter().map(Record::Label))
+        .chain(categories.into_iter().map(Record::Category)))
+}
+
+/// A line per note of an archive in JSON Lines, after the head
+pub fn jsonl_notes(notes: Vec<ArchivedNote>) -> serde_json::Result<Vec<u8>>{
+    to_lines(notes.into_iter().map(|note| Record::Note(Box::new(note))))
+}
+
+/// The archive in JSON Lines: a header and then one label, category or
+/// note per line
+pub fn to_jsonl(archive: Archive) -> serde_json::Result<Vec<u8>>{
+    let mut data = jsonl_head(archive.exported_at, archive.labels, archive.categories)?;
+    data.extend(jsonl_notes(archive.notes)?);
+    Ok(data)
+}
+
+fn from_jsonl(data: &[u8]) -> Result<Archive, String>{
+    let mut archive: Option<Archive> = None;
+    for (number, line) in data.split(|byte| *byte == b'\n').enumerate() {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let record = serde_json::from_slice(line)
+            .map_err(|e| format!("Line {}: {}", number + 1, e))?;
+        match (archive.as_mut(), record) {
+            (None, Record::Header{version, exported_at}) => archive = Some(Archive{
+                version,
+                exported_at,
+                labels: Vec::new(),
+                categories: Vec::new(),
+                notes: Vec::new(),
+            }),
+            (None, _) => return Err("The archive does not start with a header".to_string()),
+            (Some(_), Record::Header{..}) => return Err(format!("Line {}: Header repeated", number + 1)),
+            (Some(archive), Record::Label(label)) => archive.labels.push(label),
+            (Some(archive), Record::Category(category)) => archive.categories.push(category),
+            (Some(archive), Record::Note(note)) => archive.notes.push(*note),
+        }
+    }
+    archive.ok_or_else(|| "The archive is empty".to_string())
+}
+
+/// Read an archive either in JSON or in JSON Lines
+pub fn parse(data: &[u8]) -> Result<Archive, String>{
+    let first_line = data.split(|byte| *byte == b'\n')
+        .find(|line| !line.iter().all(u8::is_ascii_whitespace))
+        .unwrap_or_default();
+    let archive = match serde_json::from_slice::<Record>(first_line) {
+        Ok(Record::Header{..}) => from_jsonl(data)?,
+        _ => serde_json::from_slice(data).map_err(|e| e.to_string())?,
+    };
+    match archive.version {
+        1..=VERSION => Ok(archive),
+        version => Err(format!("Unsupported archive version {}", version)),
+    }
+}
+
+#[test]
+fn test_jsonl() {
+    let archive = parse(br#"{"version": 1, "exported_at": "2022-11-07T10:00:00",
+        "labels": [{"id": 3, "name": "casa"}], "categories": [],
+        "notes": [{"id": 7, "title": "Compra", "body": "", "kind": "checklist",
+            "color": null, "remind_at": null, "recurrence": null, "source": null,
+            "created_at": "2022-11-07T09:00:00", "updated_at": "2022-11-07T09:00:00",
+            "items": [{"text": "Pan", "checked": true}], "labels": [3],
+            "attachments": [{"filename": "a.txt", "mime_type": "text/plain", "data": "aG9sYQ=="}]}]}"#).unwrap();
+    assert_eq!(archive.notes[0].attachments[0].data, b"hola");
+    let data = to_jsonl(archive).unwrap();
+    let text = String::from_utf8(data.clone()).unwrap();
+    assert_eq!(text.lines().count(), 3);
+    assert!(text.starts_with(r#"{"type":"header","version":1,"#));
+    let archive = parse(&data).unwrap();
+    assert_eq!(archive.labels[0].name, "casa");
+    assert_eq!(archive.notes[0].items[0].text, "Pan");
+    assert_eq!(archive.notes[0].labels, vec![3]);
+    assert_eq!(archive.notes[0].attachments[0].data, b"hola");
+    assert!(parse(br#"{"type":"note"}"#).is_err());
+    assert!(parse(br#"{"version": 9, "exported_at": "2022-11-07T10:00:00", "labels": [], "categories": [], "notes": []}"#)
+        .unwrap_err().contains("version 9"));
+}
@@ -0,0 +1,119 @@
+//! Atom and RSS feeds of notes, with their markdown rendered as HTML. Raw
+//! HTML in the notes is escaped, so the feeds only have what markdown makes.
+use chrono::NaiveDateTime;
+use pulldown_cmark::{html, Event, Options, Parser};
+use quick_xml::escape::escape;
+
+/// What the feed is about
+pub struct Channel{
+    pub title: String,
+    /// Address of the feed itself
+    pub link: String,
+    /// Stable id of the feed, like `urn:notisbak:feed:labels:1`
+    pub id: String,
+    pub updated: NaiveDateTime,
+}
+
+pub struct Entry{
+    pub id: i32,
+    pub title: String,
+    /// The body as markdown
+    pub markdown: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl Entry{
+    /// Stable id of the entry, the same in every feed
+    pub fn guid(&self) -> String{
+        format!("urn:notisbak:note:{}", self.id)
+    }
+}
+
+pub fn to_html(markdown: &str) -> String{
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS)
+        .map(|event| match event {
+            Event::Html(html) => Event::Text(html),
+            event => event,
+        });
+    let mut out = String::new();
+    html::push_html(&mut out, parser);
+    out
+}
+
+/// A date as RFC 3339, for Atom
+fn rfc3339(time: NaiveDateTime) -> String{
+    time.and_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// A date as RFC 822, for RSS and HTTP
+pub fn rfc822(time: NaiveDateTime) -> String{
+    time.and_utc().format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+pub fn atom(channel: &Channel, entries: &[Entry]) -> String{
+    let mut xml = format!(concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#, "\n",
+        r#"<feed xmlns="http://www.w3.org/2005/Atom">"#, "\n",
+        "<id>{}</id>\n<title>{}</title>\n<updated>{}</updated>\n",
+        r#"<link rel="self" href="{}"/>"#, "\n",
+        "<generator>notisbak</generator>\n"),
+        escape(&channel.id), escape(&channel.title), rfc3339(channel.updated), escape(&channel.link));
+    for entry in entries {
+        xml.push_str(&format!(concat!(
+            "<entry>\n<id>{}</id>\n<title>{}</title>\n",
+            "<published>{}</published>\n<updated>{}</updated>\n",
+            r#"<content type="html">{}</content>"#, "\n</entry>\n"),
+            entry.guid(), escape(&entry.title), rfc3339(entry.created_at),
+            rfc3339(entry.updated_at), escape(&to_html(&entry.markdown))));
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+pub fn rss(channel: &Channel, entries: &[Entry]) -> String{
+    let mut xml = format!(concat!(
+        r#"<?xml version="1.0" encoding="utf-8"?>"#, "\n",
+        r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">"#, "\n<channel>\n",
+        "<title>{title}</title>\n<link>{link}</link>\n<description>{title}</description>\n",
+        r#"<atom:link rel="self" type="application/rss+xml" href="{link}"/>"#, "\n",
+        "<lastBuildDate>{updated}</lastBuildDate>\n<generator>notisbak</generator>\n"),
+        title = escape(&channel.title), link = escape(&channel.link), updated = rfc822(channel.updated));
+    for entry in entries {
+        xml.push_str(&format!(concat!(
+            "<item>\n<title>{}</title>\n",
+            r#"<guid isPermaLink="false">{}</guid>"#, "\n",
+            "<pubDate>{}</pubDate>\n<description>{}</description>\n</item>\n"),
+            escape(&entry.title), entry.guid(), rfc822(entry.updated_at),
+            escape(&to_html(&entry.markdown))));
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+#[test]
+fn test_feeds() {
+    let time = NaiveDateTime::parse_from_str("2022-11-16 18:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let channel = Channel{
+        title: "Cambios & novedades".to_string(),
+        link: "https://notes.example.com/feeds/labels/1.atom".to_string(),
+        id: "urn:notisbak:feed:labels:1".to_string(),
+        updated: time,
+    };
+    let entries = vec![Entry{
+        id: 7,
+        title: "Versión 1.2".to_string(),
+        markdown: "**Nuevo** <script>alert(1)</script>\n\n- [x] feeds".to_string(),
+        created_at: time,
+        updated_at: time,
+    }];
+    assert_eq!(to_html("**Nuevo** <b>"), "<p><strong>Nuevo</strong> &lt;b&gt;</p>\n");
+    let atom = atom(&channel, &entries);
+    assert!(atom.contains("<title>Cambios &amp; novedades</title>"));
+    assert!(atom.contains("<id>urn:notisbak:note:7</id>"));
+    assert!(atom.contains("<updated>2022-11-16T18:00:00Z</updated>"));
+    assert!(atom.contains("&lt;strong&gt;Nuevo&lt;/strong&gt; &amp;lt;script&amp;gt;"));
+    let rss = rss(&channel, &entries);
+    assert!(rss.contains(r#"<guid isPermaLink="false">urn:notisbak:note:7</guid>"#));
+    assert!(rss.contains("<pubDate>Wed, 16 Nov 2022 18:00:00 GMT</pubDate>"));
+}
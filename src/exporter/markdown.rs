@@ -0,0 +1,90 @@
+//! A zip with a markdown file per note, the counterpart of
+//! [`crate::importer::markdown`]. The first category of a note is its folder.
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use zip::{ZipWriter, DateTime, write::FileOptions, result::ZipResult};
+
+use crate::exporter::ExportedNote;
+use crate::importer::markdown::{FrontMatter, List, DATE_FORMAT};
+use crate::model::{note::NoteKind, note_item};
+
+/// A name that is valid as a file or folder in any system
+pub fn file_name(name: &str) -> String{
+    let name: String = name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_matches('.').trim();
+    match name.is_empty() {
+        true => "untitled".to_string(),
+        false => name.to_string(),
+    }
+}
+
+/// Where the note goes in the zip, not taken by another note yet
+fn path(note: &ExportedNote, taken: &mut HashSet<String>) -> String{
+    let folder: Vec<String> = note.categories.first()
+        .map(|category| category.split('/').map(file_name).collect())
+        .unwrap_or_default();
+    let name = file_name(&note.note.title);
+    let mut count = 1;
+    loop {
+        let file = match count {
+            1 => format!("{}.md", name),
+            n => format!("{} ({}).md", name, n),
+        };
+        let path = folder.iter().cloned().chain([file]).collect::<Vec<String>>().join("/");
+        if taken.insert(path.to_lowercase()) {
+            return path;
+        }
+        count += 1;
+    }
+}
+
+pub fn to_markdown(note: &ExportedNote) -> Result<String, serde_yaml::Error>{
+    let list = |values: &[String]| match values.is_empty() {
+        true => None,
+        false => Some(List::Many(values.to_vec())),
+    };
+    let front_matter = FrontMatter{
+        title: Some(note.note.title.clone()),
+        kind: Some(note.note.kind).filter(|kind| *kind == NoteKind::Checklist),
+        tags: list(&note.labels),
+        categories: list(&note.categories),
+        created: Some(note.note.created_at.format(DATE_FORMAT).to_string()),
+        updated: Some(note.note.updated_at.format(DATE_FORMAT).to_string()),
+    };
+    Ok(format!("---\n{}---\n\n{}\n", serde_yaml::to_string(&front_matter)?,
+        note_item::to_markdown(&note.note, &note.items)))
+}
+
+fn zip_time(time: NaiveDateTime) -> DateTime{
+    DateTime::from_date_and_time(time.year() as u16, time.month() as u8, time.day() as u8,
+        time.hour() as u8, time.minute() as u8, time.second() as u8)
+        .unwrap_or_default()
+}
+
+pub fn write(notes: &[ExportedNote]) -> ZipResult<Vec<u8>>{
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut taken = HashSet::new();
+    for note in notes {
+        let text = to_markdown(note)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let options = FileOptions::default()
+            .last_modified_time(zip_time(note.note.updated_at));
+        zip.start_file(path(note, &mut taken), options)?;
+        zip.write_all(text.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+#[test]
+fn test_file_name() {
+    assert_eq!(file_name("Lista: viaje/2022?"), "Lista- viaje-2022-");
+    assert_eq!(file_name(" .. "), "untitled");
+}
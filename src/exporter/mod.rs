@@ -0,0 +1,54 @@
+pub mod archive;
+pub mod feed;
+pub mod markdown;
+
+use std::collections::HashMap;
+
+use actix_web::web;
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+
+use crate::model::{note::Note, note_item::NoteItem};
+
+/// A note with everything needed to write it out of the application
+pub struct ExportedNote{
+    pub note: Note,
+    pub items: Vec<NoteItem>,
+    pub labels: Vec<String>,
+    pub categories: Vec<String>,
+}
+
+/// Names of the labels or categories of each note of the workspace
+async fn names(pool: &web::Data<PgPool>, table: &str, join: &str, column: &str, workspace_id: i32) -> Result<HashMap<i32, Vec<String>>, Error>{
+    let sql = format!("SELECT j.note_id, t.name FROM {join} j INNER JOIN {table} t ON t.id = j.{column} WHERE t.workspace_id = $1 ORDER BY t.name");
+    let rows: Vec<(i32, String)> = query(&sql)
+        .bind(workspace_id)
+        .map(|row: PgRow| (row.get("note_id"), row.get("name")))
+        .fetch_all(pool.get_ref())
+        .await?;
+    let mut names: HashMap<i32, Vec<String>> = HashMap::new();
+    for (note_id, name) in rows {
+        names.entry(note_id).or_default().push(name);
+    }
+    Ok(names)
+}
+
+/// Every note of the workspace but the trashed ones, oldest first
+pub async fn notes(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Vec<ExportedNote>, Error>{
+    let mut notes = Note::all(pool.clone(), workspace_id).await?;
+    notes.retain(|note| !note.trashed);
+    notes.sort_by_key(|note| note.id);
+    let mut labels = names(pool, "labels", "notes_labels", "label_id", workspace_id).await?;
+    let mut categories = names(pool, "categories", "notes_categories", "category_id", workspace_id).await?;
+    let mut items: HashMap<i32, Vec<NoteItem>> = HashMap::new();
+    for item in NoteItem::all_in_workspace(pool, workspace_id).await? {
+        items.entry(item.note_id).or_default().push(item);
+    }
+    Ok(notes.into_iter()
+        .map(|note| ExportedNote{
+            items: items.remove(&note.id).unwrap_or_default(),
+            labels: labels.remove(&note.id).unwrap_or_default(),
+            categories: categories.remove(&note.id).unwrap_or_default(),
+            note,
+        })
+        .collect())
+}
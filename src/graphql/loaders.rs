@@ -0,0 +1,94 @@
+//! Batched loads of the relations, so a query over many notes reads their
+//! labels or categories with a single SELECT instead of one per note.
+use std::{collections::HashMap, hash::Hash, sync::Arc};
+
+use actix_web::web;
+use async_graphql::dataloader::Loader;
+use sqlx::PgPool;
+
+use crate::model::{category::Category, label::Label, note::{LinkedPage, Note}};
+
+/// Loaders of one request, scoped to the workspace of its user
+pub struct Scope{
+    pub pool: web::Data<PgPool>,
+    pub workspace_id: i32,
+}
+
+/// Group the pairs by their key, keeping every key asked for
+fn group<K: Copy + Eq + Hash, T>(keys: &[K], pairs: Vec<(K, T)>) -> HashMap<K, Vec<T>>{
+    let mut groups: HashMap<K, Vec<T>> = keys.iter()
+        .map(|key| (*key, Vec::new()))
+        .collect();
+    for (key, value) in pairs {
+        groups.entry(key).or_default().push(value);
+    }
+    groups
+}
+
+/// Labels of the notes, by note id
+pub struct NoteLabels(pub Scope);
+
+impl Loader<i32> for NoteLabels{
+    type Value = Vec<Label>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error>{
+        Label::for_notes(&self.0.pool, keys, self.0.workspace_id)
+            .await
+            .map(|pairs| group(keys, pairs))
+            .map_err(Arc::new)
+    }
+}
+
+/// Categories of the notes, by note id
+pub struct NoteCategories(pub Scope);
+
+impl Loader<i32> for NoteCategories{
+    type Value = Vec<Category>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Self::Value>, Self::Error>{
+        Category::for_notes(&self.0.pool, keys, self.0.workspace_id)
+            .await
+            .map(|pairs| group(keys, pairs))
+            .map_err(Arc::new)
+    }
+}
+
+/// Notes with the labels, by page of each label
+pub struct LabelNotes(pub Scope);
+
+impl Loader<LinkedPage> for LabelNotes{
+    type Value = Vec<Note>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[LinkedPage]) -> Result<HashMap<LinkedPage, Self::Value>, Self::Error>{
+        Note::for_labels(&self.0.pool, keys, self.0.workspace_id)
+            .await
+            .map(|pairs| group(keys, pairs))
+            .map_err(Arc::new)
+    }
+}
+
+/// Notes in the categories, by page of each category
+pub struct CategoryNotes(pub Scope);
+
+impl Loader<LinkedPage> for CategoryNotes{
+    type Value = Vec<Note>;
+    type Error = Arc<sqlx::Error>;
+
+    async fn load(&self, keys: &[LinkedPage]) -> Result<HashMap<LinkedPage, Self::Value>, Self::Error>{
+        Note::for_categories(&self.0.pool, keys, self.0.workspace_id)
+            .await
+            .map(|pairs| group(keys, pairs))
+            .map_err(Arc::new)
+    }
+}
+
+#[test]
+fn test_group() {
+    let groups = group(&[1, 2, 3], vec![(1, "a"), (3, "b"), (1, "c")]);
+    assert_eq!(groups[&1], vec!["a", "c"]);
+    assert!(groups[&2].is_empty());
+    assert_eq!(groups[&3], vec!["b"]);
+}
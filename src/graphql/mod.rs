@@ -0,0 +1,87 @@
+//! GraphQL API over the notes, labels and categories of the active workspace
+//! of the user, with the same token as the REST API.
+mod loaders;
+mod mutation;
+mod query;
+mod types;
+
+use actix_web::web;
+use async_graphql::{dataloader::DataLoader, Context, EmptySubscription,
+    Error, Request, Schema};
+use sqlx::PgPool;
+
+use crate::model::workspace::Member;
+use loaders::{CategoryNotes, LabelNotes, NoteCategories, NoteLabels, Scope};
+use mutation::Mutation;
+use query::Query;
+
+/// Deepest nesting of a query, like notes > labels > notes > categories
+const DEPTH: usize = 10;
+/// Most fields a query can resolve, counting those of each note of a page
+/// as many times as notes the page can have
+const COMPLEXITY: usize = 10_000;
+
+pub type NotisbakSchema = Schema<Query, Mutation, EmptySubscription>;
+
+pub fn schema() -> NotisbakSchema{
+    Schema::build(Query, Mutation, EmptySubscription)
+        .limit_depth(DEPTH)
+        .limit_complexity(COMPLEXITY)
+        .finish()
+}
+
+/// Who runs a request and in which workspace
+pub struct Session{
+    pub pool: web::Data<PgPool>,
+    pub user_id: i32,
+    pub workspace_id: i32,
+    pub can_write: bool,
+}
+
+impl Session{
+    /// The session, when its member is allowed to write
+    pub fn writer<'a>(ctx: &Context<'a>) -> Result<&'a Session, Error>{
+        let session = ctx.data_unchecked::<Session>();
+        match session.can_write {
+            true => Ok(session),
+            false => Err(Error::new("Read only workspace")),
+        }
+    }
+}
+
+fn not_found(_: sqlx::Error) -> Error{
+    Error::new("Not found")
+}
+
+fn spawn(future: futures_util::future::BoxFuture<'static, ()>){
+    actix_web::rt::spawn(future);
+}
+
+/// Run the request as the member, with loaders of its own
+pub fn session(request: Request, pool: web::Data<PgPool>, member: &Member) -> Request{
+    let scope = || Scope{
+        pool: pool.clone(),
+        workspace_id: member.workspace_id,
+    };
+    request
+        .data(DataLoader::new(NoteLabels(scope()), spawn))
+        .data(DataLoader::new(NoteCategories(scope()), spawn))
+        .data(DataLoader::new(LabelNotes(scope()), spawn))
+        .data(DataLoader::new(CategoryNotes(scope()), spawn))
+        .data(Session{
+            pool: pool.clone(),
+            user_id: member.user_id,
+            workspace_id: member.workspace_id,
+            can_write: member.can_write(),
+        })
+}
+
+#[test]
+fn test_schema() {
+    let sdl = schema().sdl();
+    assert!(sdl.contains("type Note {"));
+    assert!(sdl.contains("labels: [Label!]!"));
+    assert!(sdl.contains("notes(after: String, before: String, first: Int, last: Int): NoteConnection!"));
+    assert!(sdl.contains("addLabelToNote(noteId: Int!, labelId: Int!): Note!"));
+    assert!(sdl.contains("notes(first: Int, after: Int): [Note!]!"));
+}
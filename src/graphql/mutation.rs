@@ -0,0 +1,130 @@
+use async_graphql::{Context, Object, Result};
+use serde_json::json;
+
+use crate::graphql::{types::{CategoryNode, LabelNode, NewNoteInput, NoteNode,
+    UpdateNoteInput}, not_found, Session};
+use crate::model::{category::Category, label::Label, note::{NewNote, Note,
+    NoteKind}, note_category::NoteCategory, note_label::NoteLabel,
+    reminder::validate_recurrence};
+
+pub struct Mutation;
+
+/// The same operations as the REST API, for members allowed to write
+#[Object]
+impl Mutation{
+    async fn create_note(&self, ctx: &Context<'_>, note: NewNoteInput) -> Result<NoteNode>{
+        let session = Session::writer(ctx)?;
+        validate_recurrence(&note.recurrence)?;
+        let note = NewNote{
+            title: note.title,
+            body: note.body,
+            kind: note.kind.map(Into::into),
+            remind_at: note.remind_at,
+            recurrence: note.recurrence,
+        };
+        Ok(NoteNode(Note::new(session.pool.clone(), note, session.workspace_id, session.user_id).await?))
+    }
+
+    /// Update a note. With `rewrite_links` the `[[title]]` links to it are
+    /// rewritten when its title changes.
+    async fn update_note(&self, ctx: &Context<'_>, note: UpdateNoteInput,
+            #[graphql(default = false)] rewrite_links: bool) -> Result<NoteNode>{
+        let session = Session::writer(ctx)?;
+        let content = json!({
+            "id": note.id,
+            "title": note.title,
+            "body": note.body,
+            "kind": note.kind.map(|kind| NoteKind::from(kind).as_str()),
+            "pinned": note.pinned,
+            "archived": note.archived,
+            "trashed": note.trashed,
+            "color": note.color,
+        });
+        Note::update(session.pool.clone(), content, session.workspace_id, rewrite_links)
+            .await
+            .map(NoteNode)
+            .map_err(not_found)
+    }
+
+    async fn delete_note(&self, ctx: &Context<'_>, id: i32) -> Result<NoteNode>{
+        let session = Session::writer(ctx)?;
+        Note::delete(session.pool.clone(), id, session.workspace_id)
+            .await
+            .map(NoteNode)
+            .map_err(not_found)
+    }
+
+    async fn add_label_to_note(&self, ctx: &Context<'_>, note_id: i32, label_id: i32) -> Result<NoteNode>{
+        let session = Session::writer(ctx)?;
+        NoteLabel::new(session.pool.clone(), note_id, label_id, session.workspace_id)
+            .await
+            .map_err(not_found)?;
+        Ok(NoteNode(Note::get(session.pool.clone(), note_id, session.workspace_id).await?))
+    }
+
+    async fn remove_label_from_note(&self, ctx: &Context<'_>, note_id: i32, label_id: i32) -> Result<NoteNode>{
+        let session = Session::writer(ctx)?;
+        NoteLabel::delete(session.pool.clone(), note_id, label_id, session.workspace_id)
+            .await
+            .map_err(not_found)?;
+        Ok(NoteNode(Note::get(session.pool.clone(), note_id, session.workspace_id).await?))
+    }
+
+    async fn add_category_to_note(&self, ctx: &Context<'_>, note_id: i32, category_id: i32) -> Result<NoteNode>{
+        let session = Session::writer(ctx)?;
+        NoteCategory::new(session.pool.clone(), note_id, category_id, session.workspace_id)
+            .await
+            .map_err(not_found)?;
+        Ok(NoteNode(Note::get(session.pool.clone(), note_id, session.workspace_id).await?))
+    }
+
+    async fn remove_category_from_note(&self, ctx: &Context<'_>, note_id: i32, category_id: i32) -> Result<NoteNode>{
+        let session = Session::writer(ctx)?;
+        NoteCategory::delete(session.pool.clone(), note_id, category_id, session.workspace_id)
+            .await
+            .map_err(not_found)?;
+        Ok(NoteNode(Note::get(session.pool.clone(), note_id, session.workspace_id).await?))
+    }
+
+    async fn create_label(&self, ctx: &Context<'_>, name: String) -> Result<LabelNode>{
+        let session = Session::writer(ctx)?;
+        Ok(LabelNode(Label::new(&session.pool, &name, session.workspace_id, session.user_id).await?))
+    }
+
+    async fn update_label(&self, ctx: &Context<'_>, id: i32, name: String) -> Result<LabelNode>{
+        let session = Session::writer(ctx)?;
+        Label::update(session.pool.clone(), Label{id, name}, session.workspace_id)
+            .await
+            .map(LabelNode)
+            .map_err(not_found)
+    }
+
+    async fn delete_label(&self, ctx: &Context<'_>, id: i32) -> Result<LabelNode>{
+        let session = Session::writer(ctx)?;
+        Label::delete(session.pool.clone(), id, session.workspace_id)
+            .await
+            .map(LabelNode)
+            .map_err(not_found)
+    }
+
+    async fn create_category(&self, ctx: &Context<'_>, name: String) -> Result<CategoryNode>{
+        let session = Session::writer(ctx)?;
+        Ok(CategoryNode(Category::new(session.pool.clone(), &name, session.workspace_id, session.user_id).await?))
+    }
+
+    async fn update_category(&self, ctx: &Context<'_>, id: i32, name: String) -> Result<CategoryNode>{
+        let session = Session::writer(ctx)?;
+        Category::update(session.pool.clone(), Category{id, name}, session.workspace_id)
+            .await
+            .map(CategoryNode)
+            .map_err(not_found)
+    }
+
+    async fn delete_category(&self, ctx: &Context<'_>, id: i32) -> Result<CategoryNode>{
+        let session = Session::writer(ctx)?;
+        Category::delete(session.pool.clone(), id, session.workspace_id)
+            .await
+            .map(CategoryNode)
+            .map_err(not_found)
+    }
+}
@@ -0,0 +1,105 @@
+use async_graphql::{connection::{self, Connection, Edge}, Context, Object,
+    Result};
+
+use crate::graphql::{types::{CategoryNode, LabelNode, NoteNode, UserNode},
+    not_found, Session};
+use crate::model::{category::Category, label::Label, note::Note, user::User};
+
+/// Notes in a page when the client does not say how many
+const PAGE: usize = 20;
+/// Most notes in a page
+const MAX_PAGE: usize = 100;
+
+/// Notes in a page of `size`, with the default and the limit
+pub fn page_size(size: Option<i32>) -> usize{
+    size.map_or(PAGE, |size| size.max(1) as usize).min(MAX_PAGE)
+}
+
+/// What a page of notes costs: the fields asked for each note it can have
+pub fn page_complexity(size: Option<i32>, child_complexity: usize) -> usize{
+    page_size(size).saturating_mul(child_complexity)
+}
+
+/// The notes of the workspace as a cursor connection, in id order
+pub async fn notes(ctx: &Context<'_>, after: Option<String>, before: Option<String>,
+        first: Option<i32>, last: Option<i32>) -> Result<Connection<i32, NoteNode>>{
+    let session = ctx.data_unchecked::<Session>();
+    connection::query(after, before, first, last, |after: Option<i32>, before: Option<i32>, first: Option<usize>, last: Option<usize>| async move {
+        let from_last = first.is_none() && last.is_some();
+        let limit = first.or(last).unwrap_or(PAGE).clamp(1, MAX_PAGE);
+        let mut items = Note::page(&session.pool, session.workspace_id, after, before, limit as i64 + 1, from_last).await?;
+        let more = items.len() > limit;
+        if more {
+            match from_last {
+                true => { items.remove(0); },
+                false => items.truncate(limit),
+            }
+        }
+        let (previous, next) = match from_last {
+            true => (more, before.is_some()),
+            false => (after.is_some(), more),
+        };
+        let mut connection = Connection::new(previous, next);
+        connection.edges.extend(items.into_iter().map(|item| Edge::new(item.id, NoteNode(item))));
+        Ok::<_, async_graphql::Error>(connection)
+    }).await
+}
+
+pub struct Query;
+
+#[Object]
+impl Query{
+    /// The user of the token
+    async fn me(&self, ctx: &Context<'_>) -> Result<UserNode>{
+        let session = ctx.data_unchecked::<Session>();
+        Ok(UserNode(User::get(&session.pool, session.user_id).await?))
+    }
+
+    async fn note(&self, ctx: &Context<'_>, id: i32) -> Result<NoteNode>{
+        let session = ctx.data_unchecked::<Session>();
+        Note::get(session.pool.clone(), id, session.workspace_id)
+            .await
+            .map(NoteNode)
+            .map_err(not_found)
+    }
+
+    #[graphql(complexity = "page_complexity(first.or(last), child_complexity)")]
+    async fn notes(&self, ctx: &Context<'_>, after: Option<String>, before: Option<String>,
+            first: Option<i32>, last: Option<i32>) -> Result<Connection<i32, NoteNode>>{
+        notes(ctx, after, before, first, last).await
+    }
+
+    async fn label(&self, ctx: &Context<'_>, id: i32) -> Result<LabelNode>{
+        let session = ctx.data_unchecked::<Session>();
+        Label::get(session.pool.clone(), id, session.workspace_id)
+            .await
+            .map(LabelNode)
+            .map_err(not_found)
+    }
+
+    async fn labels(&self, ctx: &Context<'_>) -> Result<Vec<LabelNode>>{
+        let session = ctx.data_unchecked::<Session>();
+        Ok(Label::all(session.pool.clone(), session.workspace_id)
+            .await?
+            .into_iter()
+            .map(LabelNode)
+            .collect())
+    }
+
+    async fn category(&self, ctx: &Context<'_>, id: i32) -> Result<CategoryNode>{
+        let session = ctx.data_unchecked::<Session>();
+        Category::get(session.pool.clone(), id, session.workspace_id)
+            .await
+            .map(CategoryNode)
+            .map_err(not_found)
+    }
+
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<CategoryNode>>{
+        let session = ctx.data_unchecked::<Session>();
+        Ok(Category::all(session.pool.clone(), session.workspace_id)
+            .await?
+            .into_iter()
+            .map(CategoryNode)
+            .collect())
+    }
+}
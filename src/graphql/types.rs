@@ -0,0 +1,242 @@
+use async_graphql::{dataloader::DataLoader, connection::Connection, Context,
+    Enum, InputObject, Object, Result};
+use chrono::NaiveDateTime;
+
+use crate::graphql::{loaders::{CategoryNotes, LabelNotes, NoteCategories,
+    NoteLabels}, query::{notes, page_complexity, page_size}, Session};
+use crate::model::{category::Category, label::Label, note::{self, LinkedPage, Note}, user};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[graphql(name = "NoteKind")]
+pub enum Kind{
+    Text,
+    Checklist,
+}
+
+impl From<note::NoteKind> for Kind{
+    fn from(kind: note::NoteKind) -> Self{
+        match kind {
+            note::NoteKind::Text => Kind::Text,
+            note::NoteKind::Checklist => Kind::Checklist,
+        }
+    }
+}
+
+impl From<Kind> for note::NoteKind{
+    fn from(kind: Kind) -> Self{
+        match kind {
+            Kind::Text => note::NoteKind::Text,
+            Kind::Checklist => note::NoteKind::Checklist,
+        }
+    }
+}
+
+pub struct UserNode(pub user::User);
+
+#[Object(name = "User")]
+impl UserNode{
+    async fn id(&self) -> i32{
+        self.0.id
+    }
+
+    async fn email(&self) -> &str{
+        &self.0.email
+    }
+
+    /// The workspace the notes, labels and categories are read from
+    async fn workspace_id(&self, ctx: &Context<'_>) -> i32{
+        ctx.data_unchecked::<Session>().workspace_id
+    }
+
+    #[graphql(complexity = "page_complexity(first.or(last), child_complexity)")]
+    async fn notes(&self, ctx: &Context<'_>, after: Option<String>, before: Option<String>,
+            first: Option<i32>, last: Option<i32>) -> Result<Connection<i32, NoteNode>>{
+        notes(ctx, after, before, first, last).await
+    }
+
+    async fn labels(&self, ctx: &Context<'_>) -> Result<Vec<LabelNode>>{
+        let session = ctx.data_unchecked::<Session>();
+        Ok(Label::all(session.pool.clone(), session.workspace_id)
+            .await?
+            .into_iter()
+            .map(LabelNode)
+            .collect())
+    }
+
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<CategoryNode>>{
+        let session = ctx.data_unchecked::<Session>();
+        Ok(Category::all(session.pool.clone(), session.workspace_id)
+            .await?
+            .into_iter()
+            .map(CategoryNode)
+            .collect())
+    }
+}
+
+pub struct NoteNode(pub Note);
+
+#[Object(name = "Note")]
+impl NoteNode{
+    async fn id(&self) -> i32{
+        self.0.id
+    }
+
+    async fn title(&self) -> &str{
+        &self.0.title
+    }
+
+    async fn body(&self) -> &str{
+        &self.0.body
+    }
+
+    async fn kind(&self) -> Kind{
+        self.0.kind.into()
+    }
+
+    /// Checked items, only for checklists
+    async fn checked(&self) -> Option<i64>{
+        self.0.progress.map(|progress| progress.checked)
+    }
+
+    /// All the items, only for checklists
+    async fn total(&self) -> Option<i64>{
+        self.0.progress.map(|progress| progress.total)
+    }
+
+    async fn pinned(&self) -> bool{
+        self.0.pinned
+    }
+
+    async fn archived(&self) -> bool{
+        self.0.archived
+    }
+
+    async fn trashed(&self) -> bool{
+        self.0.trashed
+    }
+
+    async fn color(&self) -> Option<&str>{
+        self.0.color.as_deref()
+    }
+
+    async fn remind_at(&self) -> Option<NaiveDateTime>{
+        self.0.remind_at
+    }
+
+    async fn recurrence(&self) -> Option<&str>{
+        self.0.recurrence.as_deref()
+    }
+
+    async fn created_at(&self) -> NaiveDateTime{
+        self.0.created_at
+    }
+
+    async fn updated_at(&self) -> NaiveDateTime{
+        self.0.updated_at
+    }
+
+    async fn labels(&self, ctx: &Context<'_>) -> Result<Vec<LabelNode>>{
+        Ok(ctx.data_unchecked::<DataLoader<NoteLabels>>()
+            .load_one(self.0.id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(LabelNode)
+            .collect())
+    }
+
+    async fn categories(&self, ctx: &Context<'_>) -> Result<Vec<CategoryNode>>{
+        Ok(ctx.data_unchecked::<DataLoader<NoteCategories>>()
+            .load_one(self.0.id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(CategoryNode)
+            .collect())
+    }
+}
+
+pub struct LabelNode(pub Label);
+
+#[Object(name = "Label")]
+impl LabelNode{
+    async fn id(&self) -> i32{
+        self.0.id
+    }
+
+    async fn name(&self) -> &str{
+        &self.0.name
+    }
+
+    /// A page of its notes, in id order: `first` of them, 20 by default
+    /// and 100 at most, after the note with id `after`
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn notes(&self, ctx: &Context<'_>, first: Option<i32>, after: Option<i32>) -> Result<Vec<NoteNode>>{
+        let page = LinkedPage{
+            link_id: self.0.id,
+            after: after.unwrap_or(0),
+            size: page_size(first) as i64,
+        };
+        Ok(ctx.data_unchecked::<DataLoader<LabelNotes>>()
+            .load_one(page)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(NoteNode)
+            .collect())
+    }
+}
+
+pub struct CategoryNode(pub Category);
+
+#[Object(name = "Category")]
+impl CategoryNode{
+    async fn id(&self) -> i32{
+        self.0.id
+    }
+
+    async fn name(&self) -> &str{
+        &self.0.name
+    }
+
+    /// A page of its notes, in id order: `first` of them, 20 by default
+    /// and 100 at most, after the note with id `after`
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn notes(&self, ctx: &Context<'_>, first: Option<i32>, after: Option<i32>) -> Result<Vec<NoteNode>>{
+        let page = LinkedPage{
+            link_id: self.0.id,
+            after: after.unwrap_or(0),
+            size: page_size(first) as i64,
+        };
+        Ok(ctx.data_unchecked::<DataLoader<CategoryNotes>>()
+            .load_one(page)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(NoteNode)
+            .collect())
+    }
+}
+
+#[derive(Debug, InputObject)]
+pub struct NewNoteInput{
+    pub title: String,
+    pub body: Option<String>,
+    pub kind: Option<Kind>,
+    pub remind_at: Option<NaiveDateTime>,
+    /// Like `daily` or `FREQ=WEEKLY;BYDAY=MO,TH`
+    pub recurrence: Option<String>,
+}
+
+/// The fields to change, the missing ones are kept
+#[derive(Debug, InputObject)]
+pub struct UpdateNoteInput{
+    pub id: i32,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub kind: Option<Kind>,
+    pub pinned: Option<bool>,
+    pub archived: Option<bool>,
+    pub trashed: Option<bool>,
+    pub color: Option<String>,
+}
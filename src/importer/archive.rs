@@ -0,0 +1,250 @@
+//! Restore an archive written by [`crate::exporter::archive`] in a
+//! workspace, empty or not.
+use std::collections::{HashMap, HashSet};
+
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Postgres, Row, Transaction, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::exporter::archive::{Archive, ArchivedNote};
+use crate::importer::upsert_name;
+use crate::model::{attachment::Attachment, note_link::{self, NoteLink},
+    reminder::next_occurrence};
+
+/// The `remind_at`, `snoozed_until` and `reminded_at` of the restored note.
+/// Reminders already past at `now` are not sent again: recurrent ones move
+/// to their next occurrence and the rest are marked as fired.
+fn reminder_state(note: &ArchivedNote, now: NaiveDateTime) -> (Option<NaiveDateTime>, Option<NaiveDateTime>, Option<NaiveDateTime>){
+    let remind_at = match note.remind_at {
+        Some(remind_at) => remind_at,
+        None => return (None, None, None),
+    };
+    let fire_at = note.snoozed_until.unwrap_or(remind_at);
+    if fire_at > now {
+        return (Some(remind_at), note.snoozed_until, note.reminded_at);
+    }
+    match next_occurrence(&note.recurrence, remind_at, now) {
+        Some(next) => (Some(next), None, note.reminded_at),
+        None => (Some(remind_at), note.snoozed_until, Some(fire_at)),
+    }
+}
+
+/// What to do with a note of the archive that is already in the workspace,
+/// that is, a note imported from the same source or else with the same title
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy{
+    /// Keep the note in the workspace
+    #[default]
+    Skip,
+    /// Replace the note in the workspace with the one in the archive
+    Overwrite,
+    /// Restore the note besides the one in the workspace
+    Duplicate,
+}
+
+/// The result of restoring an archive
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Restored{
+    /// Notes created
+    pub created: usize,
+    /// Notes overwritten
+    pub updated: usize,
+    /// Notes already in the workspace
+    pub skipped: usize,
+}
+
+/// The note in the workspace the archived one conflicts with, if any, and
+/// whether it has the same source. Notes restored before are left out.
+async fn conflict(tx: &mut Transaction<'_, Postgres>, note: &ArchivedNote, restored: &[i32], workspace_id: i32) -> Result<Option<(i32, bool)>, Error>{
+    query(r#"SELECT id, COALESCE(source = $2, FALSE) AS same_source FROM notes
+        WHERE workspace_id = $1 AND (source = $2 OR title = $3) AND NOT id = ANY($4)
+        ORDER BY same_source DESC, id
+        LIMIT 1"#)
+        .bind(workspace_id)
+        .bind(&note.source)
+        .bind(&note.title)
+        .bind(restored)
+        .map(|row: PgRow| (row.get("id"), row.get("same_source")))
+        .fetch_optional(&mut *tx)
+        .await
+}
+
+/// Items, labels, categories and attachments of the restored note `id`
+async fn save_children(tx: &mut Transaction<'_, Postgres>, id: i32, note: &ArchivedNote, labels: &HashMap<i32, i32>, categories: &HashMap<i32, i32>) -> Result<(), Error>{
+    for (position, item) in note.items.iter().enumerate() {
+        query(r#"INSERT INTO note_items (note_id, text, checked, position, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6)"#)
+            .bind(id)
+            .bind(&item.text)
+            .bind(item.checked)
+            .bind(position as i32)
+            .bind(note.created_at)
+            .bind(note.updated_at)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for label_id in note.labels.iter().filter_map(|id| labels.get(id)) {
+        query(r#"INSERT INTO notes_labels (note_id, label_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"#)
+            .bind(id)
+            .bind(label_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for category_id in note.categories.iter().filter_map(|id| categories.get(id)) {
+        query(r#"INSERT INTO notes_categories (note_id, category_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"#)
+            .bind(id)
+            .bind(category_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    for attachment in note.attachments.iter() {
+        Attachment::new(tx, id, &attachment.filename, &attachment.mime_type, &attachment.data).await?;
+    }
+    Ok(())
+}
+
+async fn insert(tx: &mut Transaction<'_, Postgres>, note: &ArchivedNote, source: Option<&str>, workspace_id: i32, user_id: i32) -> Result<i32, Error>{
+    let (remind_at, snoozed_until, reminded_at) = reminder_state(note, Utc::now().naive_utc());
+    query(r#"INSERT INTO notes (title, body, kind, pinned, archived, trashed, color, remind_at, recurrence, snoozed_until, reminded_at, source, created_at, updated_at, user_id, workspace_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16) RETURNING id"#)
+        .bind(&note.title)
+        .bind(&note.body)
+        .bind(note.kind.as_str())
+        .bind(note.pinned)
+        .bind(note.archived)
+        .bind(note.trashed)
+        .bind(&note.color)
+        .bind(remind_at)
+        .bind(&note.recurrence)
+        .bind(snoozed_until)
+        .bind(reminded_at)
+        .bind(source)
+        .bind(note.created_at)
+        .bind(note.updated_at)
+        .bind(user_id)
+        .bind(workspace_id)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_one(&mut *tx)
+        .await
+}
+
+/// Replace the note `id` with the archived one, dropping what it had
+async fn overwrite(tx: &mut Transaction<'_, Postgres>, id: i32, note: &ArchivedNote) -> Result<(), Error>{
+    let (remind_at, snoozed_until, reminded_at) = reminder_state(note, Utc::now().naive_utc());
+    query(r#"UPDATE notes SET title = $1, body = $2, kind = $3, pinned = $4, archived = $5, trashed = $6, color = $7, remind_at = $8, recurrence = $9, snoozed_until = $10, reminded_at = $11, source = COALESCE($12, source), created_at = $13, updated_at = $14 WHERE id = $15"#)
+        .bind(&note.title)
+        .bind(&note.body)
+        .bind(note.kind.as_str())
+        .bind(note.pinned)
+        .bind(note.archived)
+        .bind(note.trashed)
+        .bind(&note.color)
+        .bind(remind_at)
+        .bind(&note.recurrence)
+        .bind(snoozed_until)
+        .bind(reminded_at)
+        .bind(&note.source)
+        .bind(note.created_at)
+        .bind(note.updated_at)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    for table in ["note_items", "notes_labels", "notes_categories", "attachments"] {
+        query(&format!("DELETE FROM {} WHERE note_id = $1", table))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Restore the archive in the workspace, all of it or nothing. Labels and
+/// categories are merged by name. Links to note ids in the bodies are
+/// pointed to the new ids.
+pub async fn restore(pool: &web::Data<PgPool>, archive: Archive, strategy: Strategy, workspace_id: i32, user_id: i32) -> Result<Restored, Error>{
+    let mut tx = pool.begin().await?;
+    let mut labels = HashMap::new();
+    for label in archive.labels.iter() {
+        labels.insert(label.id, upsert_name(&mut tx, "labels", &label.name, workspace_id, user_id).await?);
+    }
+    let mut categories = HashMap::new();
+    for category in archive.categories.iter() {
+        categories.insert(category.id, upsert_name(&mut tx, "categories", &category.name, workspace_id, user_id).await?);
+    }
+    let mut restored = Restored::default();
+    // Old id to new id of every note, skipped ones included, and the notes
+    // written, whose links have to be resolved again
+    let mut ids = HashMap::new();
+    let mut written = Vec::new();
+    for note in archive.notes.iter() {
+        let id = match (conflict(&mut tx, note, &written, workspace_id).await?, strategy) {
+            (Some((id, _)), Strategy::Skip) => {
+                restored.skipped += 1;
+                ids.insert(note.id, id);
+                continue;
+            },
+            (Some((id, _)), Strategy::Overwrite) => {
+                overwrite(&mut tx, id, note).await?;
+                restored.updated += 1;
+                id
+            },
+            // The source identifies a single note in the workspace
+            (Some((_, same_source)), Strategy::Duplicate) => {
+                let source = note.source.as_deref().filter(|_| !same_source);
+                restored.created += 1;
+                insert(&mut tx, note, source, workspace_id, user_id).await?
+            },
+            (None, _) => {
+                restored.created += 1;
+                insert(&mut tx, note, note.source.as_deref(), workspace_id, user_id).await?
+            },
+        };
+        save_children(&mut tx, id, note, &labels, &categories).await?;
+        ids.insert(note.id, id);
+        written.push(id);
+    }
+    let written: HashSet<i32> = written.into_iter().collect();
+    let mut bodies = Vec::new();
+    for note in archive.notes.iter() {
+        match ids.get(&note.id) {
+            Some(id) if written.contains(id) => {
+                let body = note_link::remap_links(&note.body, &ids);
+                if body != note.body {
+                    query(r#"UPDATE notes SET body = $1 WHERE id = $2"#)
+                        .bind(&body)
+                        .bind(id)
+                        .execute(&mut tx)
+                        .await?;
+                }
+                bodies.push((*id, body));
+            },
+            _ => {},
+        }
+    }
+    tx.commit().await?;
+    for (id, body) in bodies {
+        if let Err(e) = NoteLink::refresh(pool, id, &body, workspace_id).await {
+            log::warn!("Can not resolve the links of restored note {}: {}", id, e);
+        }
+    }
+    Ok(restored)
+}
+
+#[test]
+fn test_reminder_state() {
+    let note = |remind_at: &str, recurrence: Option<&str>| -> ArchivedNote {
+        serde_json::from_value(serde_json::json!({"id": 1, "title": "Pagar", "body": "", "kind": "text",
+            "color": null, "remind_at": remind_at, "recurrence": recurrence, "source": null,
+            "created_at": "2022-11-01T09:00:00", "updated_at": "2022-11-01T09:00:00"})).unwrap()
+    };
+    let at = |date: &str| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S").unwrap();
+    let now = at("2022-11-07T10:00:00");
+    // Still to come, as it was
+    assert_eq!(reminder_state(&note("2022-11-08T09:00:00", None), now), (Some(at("2022-11-08T09:00:00")), None, None));
+    // Past, already sent
+    assert_eq!(reminder_state(&note("2022-11-06T09:00:00", None), now),
+        (Some(at("2022-11-06T09:00:00")), None, Some(at("2022-11-06T09:00:00"))));
+    // Past and recurrent, at the next occurrence
+    assert_eq!(reminder_state(&note("2022-11-06T09:00:00", Some("FREQ=DAILY")), now), (Some(at("2022-11-08T09:00:00")), None, None));
+}
@@ -0,0 +1,102 @@
+//! Notes from email messages. The subject is the title and the HTML part,
+//! or else the text one, is the body. Attachments are kept and inline
+//! images are linked to them.
+use chrono::DateTime;
+use mail_parser::{MessageParser, MimeHeaders, PartType};
+
+use crate::importer::{title_from, ImportedAttachment, ImportedNote};
+
+/// The note of the raw message, or `None` if it is not a message
+pub fn to_note(raw: &[u8]) -> Option<ImportedNote>{
+    let message = MessageParser::default().parse(raw)?;
+    let html = message.html_part(0)
+        .and_then(|part| match &part.body {
+            PartType::Html(html) => Some(html2md::parse_html(html)),
+            _ => None,
+        });
+    let mut body = match html {
+        Some(body) => body,
+        None => message.body_text(0).map(|text| text.trim_end().to_string()).unwrap_or_default(),
+    };
+    let source = match message.message_id() {
+        Some(id) => format!("email:{}", id),
+        None => format!("email:{:x}", md5::compute(raw)),
+    };
+    let title = match message.subject().map(str::trim) {
+        Some(subject) if !subject.is_empty() => subject.to_string(),
+        _ => title_from(&body, "Email"),
+    };
+    let mut note = ImportedNote::new(source, title);
+    if let Some(date) = message.date().and_then(|date| DateTime::from_timestamp(date.to_timestamp(), 0)) {
+        note.created_at = date.naive_utc();
+        note.updated_at = date.naive_utc();
+    }
+    for (position, part) in message.attachments().enumerate() {
+        let mime_type = part.content_type()
+            .map(|content_type| match content_type.subtype() {
+                Some(subtype) => format!("{}/{}", content_type.ctype(), subtype),
+                None => content_type.ctype().to_string(),
+            })
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        let filename = match (part.attachment_name(), &part.body) {
+            (Some(name), _) if !name.trim().is_empty() => name.trim().to_string(),
+            (_, PartType::Message(_)) => format!("message-{}.eml", position + 1),
+            _ => format!("attachment-{}.{}", position + 1, mime_type.rsplit('/').next().unwrap_or("bin")),
+        };
+        // Inline images point to their part by its content id
+        if let Some(id) = part.content_id() {
+            body = body.replace(&format!("cid:{}", id), &filename.replace(' ', "%20"));
+        }
+        note.attachments.push(ImportedAttachment{
+            filename,
+            mime_type,
+            data: part.contents().to_vec(),
+        });
+    }
+    note.body = body;
+    Some(note)
+}
+
+#[test]
+fn test_to_note() {
+    let raw = concat!(
+        "From: Ana <ana@example.com>\r\n",
+        "To: inbox@notisbak.example.com\r\n",
+        "Subject: =?utf-8?q?Recetas_de_oto=C3=B1o?=\r\n",
+        "Date: Thu, 10 Nov 2022 18:00:00 +0100\r\n",
+        "Message-ID: <1234@example.com>\r\n",
+        "MIME-Version: 1.0\r\n",
+        "Content-Type: multipart/mixed; boundary=\"b1\"\r\n",
+        "\r\n",
+        "--b1\r\n",
+        "Content-Type: multipart/alternative; boundary=\"b2\"\r\n",
+        "\r\n",
+        "--b2\r\n",
+        "Content-Type: text/plain; charset=utf-8\r\n",
+        "\r\n",
+        "Sopa de calabaza\r\n",
+        "--b2\r\n",
+        "Content-Type: text/html; charset=utf-8\r\n",
+        "\r\n",
+        "<h1>Sopa</h1><p>De <b>calabaza</b></p><img src=\"cid:foto1\">\r\n",
+        "--b2--\r\n",
+        "--b1\r\n",
+        "Content-Type: image/png; name=\"la foto.png\"\r\n",
+        "Content-ID: <foto1>\r\n",
+        "Content-Disposition: inline; filename=\"la foto.png\"\r\n",
+        "Content-Transfer-Encoding: base64\r\n",
+        "\r\n",
+        "UE5H\r\n",
+        "--b1--\r\n");
+    let note = to_note(raw.as_bytes()).unwrap();
+    assert_eq!(note.title, "Recetas de otoño");
+    assert_eq!(note.source, "email:1234@example.com");
+    assert!(note.body.contains("Sopa"));
+    assert!(note.body.contains("**calabaza**"));
+    assert!(note.body.contains("la%20foto.png"));
+    assert_eq!(note.created_at.to_string(), "2022-11-10 17:00:00");
+    assert_eq!(note.attachments.len(), 1);
+    assert_eq!(note.attachments[0].filename, "la foto.png");
+    assert_eq!(note.attachments[0].mime_type, "image/png");
+    assert_eq!(note.attachments[0].data, b"PNG");
+}
@@ -0,0 +1,423 @@
+//! Evernote notes from an `.enex` export.
+//!
+//! The export is read as a stream, so files of any size can be imported.
+//! The ENML content of the notes is converted to markdown and the resources
+//! become attachments.
+use std::{collections::HashMap, io::BufRead};
+
+use chrono::NaiveDateTime;
+use quick_xml::{Reader, events::{BytesStart, Event}};
+
+use crate::importer::{Failure, ImportedAttachment, ImportedNote, Parsed,
+    title_from};
+
+/// Entities used by ENML besides the XML ones
+fn entity(name: &str) -> Option<&'static str>{
+    match name {
+        "nbsp" => Some(" "),
+        "quot" => Some("\""),
+        "apos" => Some("'"),
+        "amp" => Some("&"),
+        "lt" => Some("<"),
+        "gt" => Some(">"),
+        _ => None,
+    }
+}
+
+fn attribute(element: &BytesStart, key: &[u8]) -> Option<String>{
+    element.attributes()
+        .flatten()
+        .find(|attribute| attribute.key.as_ref() == key)
+        .and_then(|attribute| attribute.unescape_value().ok().map(|value| value.to_string()))
+}
+
+fn parse_date(value: &str) -> Option<NaiveDateTime>{
+    NaiveDateTime::parse_from_str(value.trim(), "%Y%m%dT%H%M%SZ").ok()
+}
+
+/// A resource of a note, as referenced by the `hash` of `<en-media>`
+struct Resource{
+    hash: String,
+    attachment: ImportedAttachment,
+}
+
+/// Writes markdown for the ENML elements as they are read
+#[derive(Default)]
+struct Markdown<'a>{
+    out: String,
+    /// Open lists, with the last number used by the ordered ones
+    lists: Vec<Option<usize>>,
+    links: Vec<String>,
+    pre: usize,
+    resources: HashMap<&'a str, &'a ImportedAttachment>,
+}
+
+impl<'a> Markdown<'a>{
+    fn at_break(&self) -> bool{
+        self.out.is_empty() || self.out.ends_with('\n') || self.out.ends_with(' ')
+    }
+
+    fn line_start(&mut self){
+        while self.out.ends_with(' ') {
+            self.out.pop();
+        }
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    fn blank_line(&mut self){
+        self.line_start();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn text(&mut self, text: &str){
+        if self.pre > 0 {
+            self.out.push_str(text);
+            return;
+        }
+        let words = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if text.starts_with(char::is_whitespace) && !self.at_break() {
+            self.out.push(' ');
+        }
+        if !words.is_empty() {
+            self.out.push_str(&words);
+            if text.ends_with(char::is_whitespace) {
+                self.out.push(' ');
+            }
+        }
+    }
+
+    fn start(&mut self, element: &BytesStart){
+        match element.local_name().as_ref() {
+            b"div" | b"p" | b"section" | b"center" => self.line_start(),
+            b"blockquote" => {
+                self.blank_line();
+                self.out.push_str("> ");
+            },
+            name @ (b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6") => {
+                self.blank_line();
+                self.out.push_str(&"#".repeat((name[1] - b'0') as usize));
+                self.out.push(' ');
+            },
+            b"br" => {
+                self.line_start();
+                self.out.push('\n');
+            },
+            b"b" | b"strong" => self.out.push_str("**"),
+            b"i" | b"em" => self.out.push('_'),
+            b"s" | b"strike" | b"del" => self.out.push_str("~~"),
+            b"code" if self.pre == 0 => self.out.push('`'),
+            b"a" => {
+                self.links.push(attribute(element, b"href").unwrap_or_default());
+                self.out.push('[');
+            },
+            b"ul" | b"ol" => {
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+                let ordered = element.local_name().as_ref() == b"ol";
+                self.lists.push(if ordered { Some(0) } else { None });
+            },
+            b"li" => {
+                self.line_start();
+                self.out.push_str(&"  ".repeat(self.lists.len().saturating_sub(1)));
+                match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        self.out.push_str(&format!("{}. ", number));
+                    },
+                    _ => self.out.push_str("- "),
+                }
+            },
+            b"en-todo" => {
+                if self.out.is_empty() || self.out.ends_with('\n') {
+                    self.out.push_str("- ");
+                }
+                match attribute(element, b"checked").as_deref() {
+                    Some("true") => self.out.push_str("[x] "),
+                    _ => self.out.push_str("[ ] "),
+                }
+            },
+            b"en-media" => {
+                let hash = attribute(element, b"hash").unwrap_or_default();
+                if let Some(attachment) = self.resources.get(hash.as_str()) {
+                    let image = if attachment.mime_type.starts_with("image/") { "!" } else { "" };
+                    self.out.push_str(&format!("{}[{}]({})", image, attachment.filename,
+                        attachment.filename.replace(' ', "%20")));
+                }
+            },
+            b"img" => {
+                if let Some(src) = attribute(element, b"src") {
+                    self.out.push_str(&format!("![]({})", src));
+                }
+            },
+            b"hr" => {
+                self.blank_line();
+                self.out.push_str("---");
+                self.blank_line();
+            },
+            b"pre" => {
+                self.blank_line();
+                self.out.push_str("```\n");
+                self.pre += 1;
+            },
+            b"tr" => self.line_start(),
+            _ => {},
+        }
+    }
+
+    fn end(&mut self, name: &[u8]){
+        match name {
+            b"div" | b"section" | b"center" | b"tr" => self.line_start(),
+            b"p" | b"blockquote" | b"h1" | b"h2" | b"h3" | b"h4" | b"h5" | b"h6" => self.blank_line(),
+            b"b" | b"strong" => self.out.push_str("**"),
+            b"i" | b"em" => self.out.push('_'),
+            b"s" | b"strike" | b"del" => self.out.push_str("~~"),
+            b"code" if self.pre == 0 => self.out.push('`'),
+            b"a" => {
+                let href = self.links.pop().unwrap_or_default();
+                self.out.push_str(&format!("]({})", href));
+            },
+            b"ul" | b"ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.blank_line();
+                }
+            },
+            b"li" => self.line_start(),
+            b"td" | b"th" => self.out.push_str(" | "),
+            b"pre" => {
+                self.line_start();
+                self.out.push_str("```");
+                self.blank_line();
+                self.pre = self.pre.saturating_sub(1);
+            },
+            _ => {},
+        }
+    }
+
+    fn finish(self) -> String{
+        let mut markdown = String::with_capacity(self.out.len());
+        let mut blank = 0;
+        for line in self.out.lines().map(str::trim_end) {
+            blank = if line.is_empty() { blank + 1 } else { 0 };
+            if blank < 2 {
+                markdown.push_str(line);
+                markdown.push('\n');
+            }
+        }
+        markdown.trim().to_string()
+    }
+}
+
+/// Convert the ENML content of a note to markdown. `<en-media>` becomes a
+/// link to the attachment with the same hash.
+pub fn enml_to_markdown(enml: &str, attachments: &HashMap<&str, &ImportedAttachment>) -> Result<String, quick_xml::Error>{
+    let mut reader = Reader::from_str(enml);
+    let mut markdown = Markdown{
+        resources: attachments.clone(),
+        ..Default::default()
+    };
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) => markdown.start(&element),
+            Event::Empty(element) => {
+                markdown.start(&element);
+                markdown.end(element.local_name().as_ref());
+            },
+            Event::End(element) => markdown.end(element.local_name().as_ref()),
+            Event::Text(text) => markdown.text(&text.unescape_with(entity)?),
+            Event::CData(text) => markdown.text(&String::from_utf8_lossy(&text)),
+            Event::Eof => break,
+            _ => {},
+        }
+    }
+    Ok(markdown.finish())
+}
+
+/// The note being read
+#[derive(Default)]
+struct EnexNote{
+    title: String,
+    content: String,
+    created: String,
+    updated: String,
+    tags: Vec<String>,
+    resources: Vec<Resource>,
+    data: String,
+    mime: String,
+    file_name: String,
+}
+
+impl EnexNote{
+    fn end_resource(&mut self){
+        let data: String = self.data.split_whitespace().collect();
+        let data = base64::decode(data).unwrap_or_default();
+        let hash = format!("{:x}", md5::compute(&data));
+        let file_name = match self.file_name.trim() {
+            "" => format!("{}.{}", hash, self.mime.rsplit('/').next().unwrap_or("bin")),
+            file_name => file_name.to_string(),
+        };
+        self.resources.push(Resource{
+            hash,
+            attachment: ImportedAttachment{
+                filename: file_name,
+                mime_type: self.mime.trim().to_string(),
+                data,
+            },
+        });
+        self.data.clear();
+        self.mime.clear();
+        self.file_name.clear();
+    }
+
+    fn into_note(self, notebook: Option<&str>) -> Result<ImportedNote, Failure>{
+        let title = self.title.trim().to_string();
+        let attachments = self.resources.iter()
+            .map(|resource| (resource.hash.as_str(), &resource.attachment))
+            .collect();
+        let body = enml_to_markdown(&self.content, &attachments)
+            .map_err(|e| Failure{
+                source: title.clone(),
+                error: e.to_string(),
+            })?;
+        let source = format!("enex:{:x}", md5::compute(format!("{}\n{}", title, self.created.trim())));
+        let title = match title.as_str() {
+            "" => title_from(&body, "Evernote"),
+            _ => title,
+        };
+        let mut note = ImportedNote::new(source, title);
+        note.body = body;
+        if let Some(created_at) = parse_date(&self.created) {
+            note.created_at = created_at;
+            note.updated_at = created_at;
+        }
+        if let Some(updated_at) = parse_date(&self.updated) {
+            note.updated_at = updated_at;
+        }
+        note.labels = self.tags.into_iter()
+            .map(|tag| tag.trim().to_string())
+            .filter(|tag| !tag.is_empty())
+            .collect();
+        note.categories = notebook.map(|notebook| vec![notebook.to_string()]).unwrap_or_default();
+        note.attachments = self.resources.into_iter()
+            .map(|resource| resource.attachment)
+            .collect();
+        Ok(note)
+    }
+}
+
+/// Read the notes of an export, sending each one as soon as it is complete.
+/// Every note is assigned to the category `notebook`, as exports do not
+/// name their notebook. Stops when `send` returns false.
+pub fn parse<R: BufRead>(input: R, notebook: Option<&str>, mut send: impl FnMut(Parsed) -> bool) -> Result<(), String>{
+    let mut reader = Reader::from_reader(input);
+    let mut buffer = Vec::new();
+    let mut path: Vec<Vec<u8>> = Vec::new();
+    let mut note = EnexNote::default();
+    loop {
+        buffer.clear();
+        let event = reader.read_event_into(&mut buffer)
+            .map_err(|e| format!("Invalid ENEX at {}: {}", reader.buffer_position(), e))?;
+        match event {
+            Event::Start(element) => {
+                if element.local_name().as_ref() == b"note" {
+                    note = EnexNote::default();
+                }
+                path.push(element.local_name().as_ref().to_vec());
+            },
+            Event::End(element) => {
+                path.pop();
+                let parsed = match element.local_name().as_ref() {
+                    b"resource" => {
+                        note.end_resource();
+                        None
+                    },
+                    b"note" => Some(match std::mem::take(&mut note).into_note(notebook) {
+                        Ok(note) => Parsed::Note(note),
+                        Err(failure) => Parsed::Failed(failure),
+                    }),
+                    _ => None,
+                };
+                if let Some(parsed) = parsed {
+                    if !send(parsed) || !send(Parsed::Read(reader.buffer_position() as u64)) {
+                        return Ok(());
+                    }
+                }
+            },
+            Event::Text(_) | Event::CData(_) => {
+                let text = match event {
+                    Event::Text(text) => text.unescape_with(entity)
+                        .map_err(|e| e.to_string())?
+                        .to_string(),
+                    Event::CData(text) => String::from_utf8_lossy(&text).to_string(),
+                    _ => unreachable!(),
+                };
+                let field = match path.iter().rev().take(2).map(Vec::as_slice).collect::<Vec<&[u8]>>()[..] {
+                    [b"title", b"note"] => &mut note.title,
+                    [b"content", b"note"] => &mut note.content,
+                    [b"created", b"note"] => &mut note.created,
+                    [b"updated", b"note"] => &mut note.updated,
+                    [b"tag", b"note"] => {
+                        note.tags.push(text);
+                        continue;
+                    },
+                    [b"data", b"resource"] => &mut note.data,
+                    [b"mime", b"resource"] => &mut note.mime,
+                    [b"file-name", b"resource-attributes"] => &mut note.file_name,
+                    _ => continue,
+                };
+                field.push_str(&text);
+            },
+            Event::Eof => break,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse() {
+    let data = base64::encode(b"PNG");
+    let hash = format!("{:x}", md5::compute(b"PNG"));
+    let enex = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-export SYSTEM "http://xml.evernote.com/pub/evernote-export3.dtd">
+<en-export export-date="20221105T120000Z" application="Evernote">
+  <note>
+    <title>Viaje &amp; vuelta</title>
+    <content><![CDATA[<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE en-note SYSTEM "http://xml.evernote.com/pub/enml2.dtd">
+<en-note><div>Hola <b>mundo</b>&nbsp;y <a href="https://notisbak.es">enlace</a></div>
+<div><en-todo checked="true"/>Maleta</div><div><en-todo/>Billetes</div>
+<ul><li>uno</li><li>dos</li></ul><h2>Fotos</h2><en-media hash="{}" type="image/png"/></en-note>]]></content>
+    <created>20221017T094640Z</created>
+    <updated>20221018T100000Z</updated>
+    <tag>viajes</tag><tag>familia</tag>
+    <resource>
+      <data encoding="base64">{}</data>
+      <mime>image/png</mime>
+      <resource-attributes><file-name>foto.png</file-name></resource-attributes>
+    </resource>
+  </note>
+</en-export>"#, hash, data);
+    let mut parsed = Vec::new();
+    parse(enex.as_bytes(), Some("Personal"), |item| {
+        parsed.push(item);
+        true
+    }).unwrap();
+    let note = match parsed.remove(0) {
+        Parsed::Note(note) => note,
+        _ => panic!("Not a note"),
+    };
+    assert_eq!(note.title, "Viaje & vuelta");
+    assert_eq!(note.body, "Hola **mundo** y [enlace](https://notisbak.es)\n- [x] Maleta\n- [ ] Billetes\n\n- uno\n- dos\n\n## Fotos\n\n![foto.png](foto.png)");
+    assert_eq!(note.labels, vec!["viajes", "familia"]);
+    assert_eq!(note.categories, vec!["Personal"]);
+    assert_eq!(note.created_at.to_string(), "2022-10-17 09:46:40");
+    assert_eq!(note.updated_at.to_string(), "2022-10-18 10:00:00");
+    assert_eq!(note.attachments[0].data, b"PNG");
+    assert!(matches!(parsed[0], Parsed::Read(_)));
+}
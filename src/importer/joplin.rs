@@ -0,0 +1,309 @@
+//! Joplin notes from a JEX export, or from a zip of a RAW export.
+//!
+//! Both have an item per `.md` file: its title in the first line, then its
+//! body and, after a blank line, its properties as `key: value` lines. The
+//! `type_` property tells notes, notebooks, tags, the tags of the notes and
+//! resources apart. The files of the resources are in `resources`.
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use actix_web::web;
+use chrono::NaiveDateTime;
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use zip::ZipArchive;
+
+use crate::importer::{ImportedAttachment, ImportedNote, Report};
+use crate::model::note_link::NoteLink;
+
+const NOTE: &str = "1";
+const FOLDER: &str = "2";
+const RESOURCE: &str = "4";
+const TAG: &str = "5";
+const NOTE_TAG: &str = "6";
+
+/// Notebooks may be nested at most this deep
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Default)]
+struct Item{
+    title: String,
+    body: String,
+    props: HashMap<String, String>,
+}
+
+impl Item{
+    fn prop(&self, key: &str) -> &str{
+        self.props.get(key).map(String::as_str).unwrap_or_default()
+    }
+
+    fn date(&self, keys: &[&str]) -> Option<NaiveDateTime>{
+        keys.iter()
+            .map(|key| self.prop(key))
+            .find_map(|value| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.fZ").ok())
+    }
+}
+
+/// Read an item the way Joplin writes it
+fn unserialize(text: &str) -> Item{
+    let mut item = Item::default();
+    let mut lines: Vec<&str> = text.lines().collect();
+    while let Some(line) = lines.pop() {
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            item.props.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    if item.prop("type_") != NOTE_TAG && !lines.is_empty() {
+        item.title = lines[0].to_string();
+        item.body = lines.get(2..).unwrap_or_default().join("\n");
+    }
+    item
+}
+
+/// The files of a JEX (a tar) or of a zip, by name
+fn files(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, String>{
+    let mut files = Vec::new();
+    if data.starts_with(b"PK\x03\x04") {
+        let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| e.to_string())?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(|e| e.to_string())?;
+            if file.is_file() {
+                let mut content = Vec::new();
+                file.read_to_end(&mut content).map_err(|e| e.to_string())?;
+                files.push((file.name().to_string(), content));
+            }
+        }
+    } else {
+        let mut archive = tar::Archive::new(data);
+        for entry in archive.entries().map_err(|e| e.to_string())? {
+            let mut entry = entry.map_err(|e| e.to_string())?;
+            if entry.header().entry_type().is_file() {
+                let name = entry.path().map_err(|e| e.to_string())?.to_string_lossy().to_string();
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).map_err(|e| e.to_string())?;
+                files.push((name, content));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The notebook and its parents as a category, like `Trabajo/Proyectos`
+fn folder_path(id: &str, folders: &HashMap<String, Item>) -> Option<String>{
+    let mut names = Vec::new();
+    let mut id = id;
+    while let Some(folder) = folders.get(id) {
+        if names.len() == MAX_DEPTH {
+            break;
+        }
+        names.push(folder.title.trim().replace('/', "-"));
+        id = folder.prop("parent_id");
+    }
+    names.reverse();
+    match names.is_empty() {
+        true => None,
+        false => Some(names.join("/")),
+    }
+}
+
+pub fn parse(data: &[u8], report: &mut Report) -> Result<Vec<ImportedNote>, String>{
+    let mut items: Vec<Item> = Vec::new();
+    let mut resource_files = HashMap::new();
+    for (name, content) in files(data)? {
+        let path = Path::new(&name);
+        let in_resources = path.parent()
+            .and_then(|parent| parent.file_name())
+            .is_some_and(|parent| parent == "resources");
+        let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().to_string();
+        if in_resources {
+            resource_files.insert(stem, content);
+        } else if name.ends_with(".md") {
+            match String::from_utf8(content) {
+                Ok(text) => items.push(unserialize(&text)),
+                Err(e) => report.fail(&name, e),
+            }
+        }
+    }
+    if items.is_empty() {
+        return Err("No Joplin items found in the export".to_string());
+    }
+    let mut notes = Vec::new();
+    let mut folders = HashMap::new();
+    let mut tags = HashMap::new();
+    let mut resources = HashMap::new();
+    let mut note_tags: Vec<(String, String)> = Vec::new();
+    for item in items {
+        let id = item.prop("id").to_string();
+        if item.prop("encryption_applied") == "1" {
+            report.fail(&format!("{}.md", id), "The item is encrypted");
+            continue;
+        }
+        match item.prop("type_") {
+            NOTE => notes.push(item),
+            FOLDER => { folders.insert(id, item); },
+            TAG => { tags.insert(id, item.title); },
+            RESOURCE => { resources.insert(id, item); },
+            NOTE_TAG => note_tags.push((item.prop("note_id").to_string(), item.prop("tag_id").to_string())),
+            _ => {},
+        }
+    }
+    let mut labels: HashMap<String, Vec<String>> = HashMap::new();
+    for (note_id, tag_id) in note_tags {
+        if let Some(tag) = tags.get(&tag_id) {
+            labels.entry(note_id).or_default().push(tag.clone());
+        }
+    }
+    let mut imported = Vec::new();
+    for item in notes {
+        let id = item.prop("id").to_string();
+        let mut note = ImportedNote::new(format!("joplin:{}", id), item.title.clone());
+        note.body = item.body.clone();
+        // Links to resources point to the attachments, the ones to notes
+        // are left for `link_notes` once they have ids
+        for (resource_id, resource) in resources.iter() {
+            let link = format!(":/{}", resource_id);
+            if !note.body.contains(&link) {
+                continue;
+            }
+            let extension = resource.prop("file_extension");
+            let filename = match (resource.title.trim(), extension) {
+                ("", "") => resource_id.clone(),
+                ("", extension) => format!("{}.{}", resource_id, extension),
+                (title, _) => title.to_string(),
+            };
+            match resource_files.get(resource_id) {
+                Some(data) => {
+                    note.body = note.body.replace(&link, &filename.replace(' ', "%20"));
+                    note.attachments.push(ImportedAttachment{
+                        filename,
+                        mime_type: resource.prop("mime").to_string(),
+                        data: data.clone(),
+                    });
+                },
+                None => report.fail(&format!("resources/{}", resource_id), "Resource not found in the export"),
+            }
+        }
+        note.categories = folder_path(item.prop("parent_id"), &folders).into_iter().collect();
+        note.labels = labels.remove(&id).unwrap_or_default();
+        if let Some(created_at) = item.date(&["user_created_time", "created_time"]) {
+            note.created_at = created_at;
+        }
+        if let Some(updated_at) = item.date(&["user_updated_time", "updated_time"]) {
+            note.updated_at = updated_at;
+        }
+        imported.push(note);
+    }
+    Ok(imported)
+}
+
+/// Turn `[text](:/id)` links to the notes with the given Joplin ids into
+/// wiki links to their ids here
+pub fn rewrite_links(body: &str, ids: &HashMap<String, i32>) -> String{
+    let mut rewritten = String::with_capacity(body.len());
+    let mut last = 0;
+    let mut from = 0;
+    while let Some(middle) = body[from..].find("](:/").map(|middle| from + middle) {
+        from = middle + 4;
+        let joplin_id = match body.get(from..from + 32) {
+            Some(joplin_id) if body[from + 32..].starts_with(')') => joplin_id,
+            _ => continue,
+        };
+        let start = match body[last..middle].rfind('[').map(|start| last + start) {
+            Some(start) if !body[..start].ends_with('!') => start,
+            _ => continue,
+        };
+        let id = match ids.get(joplin_id) {
+            Some(id) => id,
+            None => continue,
+        };
+        let text = &body[start + 1..middle];
+        rewritten.push_str(&body[last..start]);
+        match text.trim().is_empty() || text.contains(['[', ']', '|', '\n']) {
+            true => rewritten.push_str(&format!("[[{}]]", id)),
+            false => rewritten.push_str(&format!("[[{}|{}]]", id, text)),
+        }
+        last = from + 33;
+        from = last;
+    }
+    rewritten.push_str(&body[last..]);
+    rewritten
+}
+
+/// Rewrite the links between the notes imported from Joplin in the
+/// workspace, now that all of them have an id
+pub async fn link_notes(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<(), Error>{
+    let notes: Vec<(i32, String, String)> = query(r#"SELECT id, source, body FROM notes WHERE workspace_id = $1 AND source LIKE 'joplin:%'"#)
+        .bind(workspace_id)
+        .map(|row: PgRow| (row.get("id"), row.get("source"), row.get("body")))
+        .fetch_all(pool.get_ref())
+        .await?;
+    let ids: HashMap<String, i32> = notes.iter()
+        .map(|(id, source, _)| (source.trim_start_matches("joplin:").to_string(), *id))
+        .collect();
+    for (id, _, body) in notes.iter().filter(|(_, _, body)| body.contains("](:/")) {
+        let rewritten = rewrite_links(body, &ids);
+        if rewritten != *body {
+            query(r#"UPDATE notes SET body = $1 WHERE id = $2"#)
+                .bind(&rewritten)
+                .bind(id)
+                .execute(pool.get_ref())
+                .await?;
+            NoteLink::refresh(pool, *id, &rewritten, workspace_id).await?;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse() {
+    let folder = |id: &str, title: &str, parent: &str| format!("{}\n\nid: {}\nparent_id: {}\ntype_: 2", title, id, parent);
+    let note_a = format!("Compra\n\nPan y [leche](:/{}).\n![foto](:/{})\n\nid: {}\nparent_id: {}\ncreated_time: 2021-03-01T10:00:00.000Z\nuser_updated_time: 2021-03-02T11:30:00.500Z\ntype_: 1",
+        "b".repeat(32), "e".repeat(32), "a".repeat(32), "f2".repeat(16));
+    let note_b = format!("Leche\n\nid: {}\nparent_id: {}\ntype_: 1", "b".repeat(32), "f1".repeat(16));
+    let files = [
+        (format!("{}.md", "a".repeat(32)), note_a),
+        (format!("{}.md", "b".repeat(32)), note_b),
+        ("f1.md".to_string(), folder(&"f1".repeat(16), "Casa", "")),
+        ("f2.md".to_string(), folder(&"f2".repeat(16), "Recados", &"f1".repeat(16))),
+        ("t.md".to_string(), format!("super\n\nid: {}\ntype_: 5", "c".repeat(32))),
+        ("nt.md".to_string(), format!("id: {}\nnote_id: {}\ntag_id: {}\ntype_: 6", "d".repeat(32), "a".repeat(32), "c".repeat(32))),
+        ("r.md".to_string(), format!("foto 1.png\n\nid: {}\nmime: image/png\nfile_extension: png\ntype_: 4", "e".repeat(32))),
+    ];
+    let mut builder = tar::Builder::new(Vec::new());
+    let mut add = |name: &str, data: &[u8]| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, name, data).unwrap();
+    };
+    for (name, text) in files.iter() {
+        add(name, text.as_bytes());
+    }
+    add(&format!("resources/{}.png", "e".repeat(32)), b"PNG");
+    let data = builder.into_inner().unwrap();
+
+    let mut report = Report::default();
+    let notes = parse(&data, &mut report).unwrap();
+    assert!(report.failed.is_empty());
+    let note = notes.iter().find(|note| note.title == "Compra").unwrap();
+    assert_eq!(note.source, format!("joplin:{}", "a".repeat(32)));
+    assert_eq!(note.body, format!("Pan y [leche](:/{}).\n![foto](foto%201.png)", "b".repeat(32)));
+    assert_eq!(note.categories, vec!["Casa/Recados"]);
+    assert_eq!(note.labels, vec!["super"]);
+    assert_eq!(note.attachments[0].filename, "foto 1.png");
+    assert_eq!(note.attachments[0].data, b"PNG");
+    assert_eq!(note.created_at.to_string(), "2021-03-01 10:00:00");
+    assert_eq!(note.updated_at.to_string(), "2021-03-02 11:30:00.500");
+    let note = notes.iter().find(|note| note.title == "Leche").unwrap();
+    assert_eq!(note.body, "");
+    assert_eq!(note.categories, vec!["Casa"]);
+
+    let ids = HashMap::from([("b".repeat(32), 7)]);
+    let body = format!("[leche](:/{0}), [](:/{0}), ![x](:/{0}) y [otra](:/{1})", "b".repeat(32), "c".repeat(32));
+    assert_eq!(rewrite_links(&body, &ids), format!("[[7|leche]], [[7]], ![x](:/{0}) y [otra](:/{1})", "b".repeat(32), "c".repeat(32)));
+}
@@ -0,0 +1,208 @@
+//! Google Keep notes from a Takeout archive.
+//!
+//! Every note is a JSON file in the `Keep` directory of the archive, with its
+//! attachments next to it.
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use zip::{ZipArchive, result::ZipError};
+
+use crate::config;
+use crate::importer::{ImportedAttachment, ImportedNote, Report, title_from};
+use crate::model::note::NoteKind;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepNote{
+    #[serde(default)]
+    title: String,
+    text_content: Option<String>,
+    list_content: Option<Vec<KeepItem>>,
+    #[serde(default)]
+    labels: Vec<KeepLabel>,
+    #[serde(default)]
+    attachments: Vec<KeepAttachment>,
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    is_archived: bool,
+    #[serde(default)]
+    is_trashed: bool,
+    #[serde(default)]
+    created_timestamp_usec: Option<i64>,
+    #[serde(default)]
+    user_edited_timestamp_usec: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepItem{
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    is_checked: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeepLabel{
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeepAttachment{
+    file_path: String,
+    #[serde(default)]
+    mimetype: String,
+}
+
+fn from_usec(usec: i64) -> Option<NaiveDateTime>{
+    DateTime::from_timestamp_micros(usec).map(|date| date.naive_utc())
+}
+
+/// Keep sometimes names an attachment `.jpeg` in the note and stores it as
+/// `.jpg`, or the other way round
+fn candidates(path: &str) -> Vec<String>{
+    let mut paths = vec![path.to_string()];
+    if let Some(stem) = path.strip_suffix(".jpeg") {
+        paths.push(format!("{}.jpg", stem));
+    }
+    if let Some(stem) = path.strip_suffix(".jpg") {
+        paths.push(format!("{}.jpeg", stem));
+    }
+    paths
+}
+
+fn read_file(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>, ZipError>{
+    let file = archive.by_name(name)?;
+    // The sizes in the archive are not to be trusted, nor to be allocated
+    let limit = config::get().limits.upload_bytes() as u64;
+    let mut data = Vec::new();
+    file.take(limit + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        return Err(ZipError::Io(io::Error::other("File too large")));
+    }
+    Ok(data)
+}
+
+fn to_note(keep: KeepNote, name: &str, archive: &mut ZipArchive<Cursor<&[u8]>>, report: &mut Report) -> ImportedNote{
+    let path = Path::new(name);
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(name);
+    let dir = path.parent().and_then(|dir| dir.to_str()).unwrap_or_default();
+    let text = keep.text_content.unwrap_or_default();
+    let title = match keep.title.trim() {
+        "" => title_from(&text, stem),
+        title => title.to_string(),
+    };
+    let mut note = ImportedNote::new(format!("keep:{}", stem), title);
+    note.body = text;
+    let items = keep.list_content.unwrap_or_default();
+    if !items.is_empty() {
+        note.kind = NoteKind::Checklist;
+        note.items = items.into_iter()
+            .map(|item| (item.text, item.is_checked))
+            .collect();
+    }
+    note.labels = keep.labels.into_iter().map(|label| label.name).collect();
+    note.pinned = keep.is_pinned;
+    note.archived = keep.is_archived;
+    note.trashed = keep.is_trashed;
+    note.color = keep.color
+        .map(|color| color.to_lowercase())
+        .filter(|color| color != "default");
+    let updated_at = keep.user_edited_timestamp_usec.and_then(from_usec);
+    note.updated_at = updated_at.unwrap_or_else(|| Utc::now().naive_utc());
+    note.created_at = keep.created_timestamp_usec.and_then(from_usec).unwrap_or(note.updated_at);
+    for attachment in keep.attachments {
+        let path = Path::new(dir).join(&attachment.file_path);
+        let data = candidates(&path.to_string_lossy())
+            .iter()
+            .find_map(|candidate| read_file(archive, candidate).ok());
+        match data {
+            Some(data) => note.attachments.push(ImportedAttachment{
+                filename: attachment.file_path,
+                mime_type: attachment.mimetype,
+                data,
+            }),
+            None => report.fail(&path.to_string_lossy(), "Attachment not found in the archive"),
+        }
+    }
+    note
+}
+
+/// A JSON file right in a `Keep` directory, like `Takeout/Keep/Compra.json`
+fn in_keep(name: &str) -> bool{
+    let path = Path::new(name);
+    path.extension().is_some_and(|extension| extension == "json")
+        && path.parent().and_then(Path::file_name).is_some_and(|dir| dir == "Keep")
+}
+
+/// The notes of a Takeout archive, with Keep or more. The JSON files of
+/// Keep that are not notes are reported as failed.
+pub fn parse(data: &[u8], report: &mut Report) -> Result<Vec<ImportedNote>, ZipError>{
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let names: Vec<String> = archive.file_names()
+        .filter(|name| in_keep(name))
+        .map(|name| name.to_string())
+        .collect();
+    let mut notes = Vec::new();
+    for name in names.iter() {
+        let keep = read_file(&mut archive, name)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_slice::<KeepNote>(&content)
+                .map_err(|e| e.to_string()))
+            .and_then(|keep| match keep.text_content.is_some() || keep.list_content.is_some()
+                    || keep.user_edited_timestamp_usec.is_some() {
+                true => Ok(keep),
+                false => Err("Not a Keep note".to_string()),
+            });
+        match keep {
+            Ok(keep) => notes.push(to_note(keep, name, &mut archive, report)),
+            Err(e) => report.fail(name, e),
+        }
+    }
+    Ok(notes)
+}
+
+#[test]
+fn test_parse() {
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("Takeout/Keep/Compra.json", FileOptions::default()).unwrap();
+    zip.write_all(br#"{"color": "YELLOW", "isPinned": true, "title": "",
+        "listContent": [{"text": "Pan", "isChecked": true}, {"text": "Leche", "isChecked": false}],
+        "labels": [{"name": "casa"}], "createdTimestampUsec": 1666000000000000,
+        "userEditedTimestampUsec": 1666000500000000,
+        "attachments": [{"filePath": "foto.jpeg", "mimetype": "image/jpeg"}]}"#).unwrap();
+    zip.start_file("Takeout/Keep/foto.jpg", FileOptions::default()).unwrap();
+    zip.write_all(b"jpeg").unwrap();
+    zip.start_file("Takeout/Keep/roto.json", FileOptions::default()).unwrap();
+    zip.write_all(b"{").unwrap();
+    zip.start_file("Takeout/Keep/otro.json", FileOptions::default()).unwrap();
+    zip.write_all(br#"{"title": "Sin nada de Keep"}"#).unwrap();
+    // Other products of Takeout are not notes
+    zip.start_file("Takeout/Calendar/ajustes.json", FileOptions::default()).unwrap();
+    zip.write_all(br#"{"title": "Calendario"}"#).unwrap();
+    let data = zip.finish().unwrap().into_inner();
+
+    let mut report = Report::default();
+    let notes = parse(&data, &mut report).unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(report.failed.len(), 2);
+    let note = &notes[0];
+    assert_eq!(note.source, "keep:Compra");
+    assert_eq!(note.title, "Compra");
+    assert_eq!(note.kind, NoteKind::Checklist);
+    assert_eq!(note.items, vec![("Pan".to_string(), true), ("Leche".to_string(), false)]);
+    assert_eq!(note.labels, vec!["casa"]);
+    assert_eq!(note.color.as_deref(), Some("yellow"));
+    assert!(note.pinned && !note.archived);
+    assert_eq!(note.created_at.to_string(), "2022-10-17 09:46:40");
+    assert_eq!(note.attachments[0].data, b"jpeg");
+}
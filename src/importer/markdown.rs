@@ -0,0 +1,194 @@
+//! A zip of markdown files, like an Obsidian vault.
+//!
+//! The YAML front matter of a file gives the title, the labels in `tags`,
+//! the categories and the dates of the note. Without them, the folder is
+//! the category and the modification time of the file the dates.
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Serialize, Deserialize};
+use zip::{ZipArchive, result::ZipError};
+
+use crate::importer::{ImportedNote, Report};
+use crate::model::note::NoteKind;
+
+pub const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// A list that may also be written as a single string, like `tags: a, b`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum List{
+    Many(Vec<String>),
+    One(String),
+}
+
+impl List{
+    fn into_vec(self) -> Vec<String>{
+        let values = match self {
+            List::Many(values) => values,
+            List::One(value) => value.split([',', ' ']).map(str::to_string).collect(),
+        };
+        values.into_iter()
+            .map(|value| value.trim().trim_start_matches('#').to_string())
+            .filter(|value| !value.is_empty())
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FrontMatter{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<NoteKind>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<List>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub categories: Option<List>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+}
+
+/// The front matter and the rest of a markdown file
+pub fn split_front_matter(text: &str) -> (Option<&str>, &str){
+    let text = text.trim_start_matches('\u{feff}');
+    let rest = match text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) {
+        Some(rest) => rest,
+        None => return (None, text),
+    };
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+    (None, text)
+}
+
+fn parse_date(value: &str) -> Option<NaiveDateTime>{
+    let value = value.trim().trim_end_matches('Z');
+    [DATE_FORMAT, "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0)))
+}
+
+/// Split a checklist written as a task list into its text and its items
+fn task_list(text: &str) -> (String, Vec<(String, bool)>){
+    let mut body = Vec::new();
+    let mut items = Vec::new();
+    for line in text.lines() {
+        match line.trim_start().strip_prefix("- [") {
+            Some(rest) if rest.starts_with("] ") || rest.starts_with(" ] ") => {
+                items.push((rest.split_once("] ").unwrap().1.to_string(), false));
+            },
+            Some(rest) if rest.starts_with("x] ") || rest.starts_with("X] ") => {
+                items.push((rest[3..].to_string(), true));
+            },
+            _ => body.push(line),
+        }
+    }
+    (body.join("\n").trim().to_string(), items)
+}
+
+/// A folder of the archive as a category name
+fn category(path: &Path) -> Option<String>{
+    let parts: Vec<String> = path.parent()?
+        .components()
+        .filter_map(|part| part.as_os_str().to_str())
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .map(str::to_string)
+        .collect();
+    match parts.is_empty() {
+        true => None,
+        false => Some(parts.join("/")),
+    }
+}
+
+pub fn to_note(name: &str, text: &str, modified: Option<NaiveDateTime>) -> Result<ImportedNote, String>{
+    let path = Path::new(name);
+    let (front_matter, body) = split_front_matter(text);
+    let front_matter: FrontMatter = match front_matter {
+        Some(yaml) if !yaml.trim().is_empty() => serde_yaml::from_str(yaml)
+            .map_err(|e| format!("Invalid front matter: {}", e))?,
+        _ => FrontMatter::default(),
+    };
+    let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or(name);
+    let title = front_matter.title.unwrap_or_else(|| stem.to_string());
+    let mut note = ImportedNote::new(format!("markdown:{}", name), title);
+    note.body = body.trim().to_string();
+    if front_matter.kind == Some(NoteKind::Checklist) {
+        note.kind = NoteKind::Checklist;
+        (note.body, note.items) = task_list(&note.body);
+    }
+    note.labels = front_matter.tags.map(List::into_vec).unwrap_or_default();
+    note.categories = match front_matter.categories {
+        Some(categories) => categories.into_vec(),
+        None => category(path).into_iter().collect(),
+    };
+    if let Some(modified) = modified {
+        note.created_at = modified;
+        note.updated_at = modified;
+    }
+    if let Some(created_at) = front_matter.created.as_deref().and_then(parse_date) {
+        note.created_at = created_at;
+    }
+    if let Some(updated_at) = front_matter.updated.as_deref().and_then(parse_date) {
+        note.updated_at = updated_at;
+    }
+    Ok(note)
+}
+
+/// The notes of the `.md` files of a zip. Hidden folders, like `.obsidian`,
+/// are left out.
+pub fn parse(data: &[u8], report: &mut Report) -> Result<Vec<ImportedNote>, ZipError>{
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut notes = Vec::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let name = file.name().to_string();
+        let hidden = name.split('/').any(|part| part.starts_with('.') || part == "__MACOSX");
+        if !file.is_file() || hidden || !name.to_lowercase().ends_with(".md") {
+            continue;
+        }
+        let modified = file.last_modified();
+        let modified = NaiveDate::from_ymd_opt(modified.year().into(), modified.month().into(), modified.day().into())
+            .and_then(|date| date.and_hms_opt(modified.hour().into(), modified.minute().into(), modified.second().into()));
+        let mut text = String::new();
+        let note = file.read_to_string(&mut text)
+            .map_err(|e| e.to_string())
+            .and_then(|_| to_note(&name, &text, modified));
+        match note {
+            Ok(note) => notes.push(note),
+            Err(e) => report.fail(&name, e),
+        }
+    }
+    Ok(notes)
+}
+
+#[test]
+fn test_to_note() {
+    let text = "---\ntags: [casa, '#ideas']\ncreated: 2022-10-17\n---\n# Compra\n\nPan";
+    let modified = parse_date("2022-11-01T10:00:00");
+    let note = to_note("Personal/Recados/Compra.md", text, modified).unwrap();
+    assert_eq!(note.title, "Compra");
+    assert_eq!(note.body, "# Compra\n\nPan");
+    assert_eq!(note.labels, vec!["casa", "ideas"]);
+    assert_eq!(note.categories, vec!["Personal/Recados"]);
+    assert_eq!(note.created_at.to_string(), "2022-10-17 00:00:00");
+    assert_eq!(note.updated_at.to_string(), "2022-11-01 10:00:00");
+
+    let text = "---\ntitle: \"Lista: viaje\"\nkind: checklist\ncategories: []\n---\nMaleta\n\n- [x] Billetes\n- [ ] Toalla";
+    let note = to_note("Lista- viaje.md", text, None).unwrap();
+    assert_eq!(note.title, "Lista: viaje");
+    assert_eq!(note.body, "Maleta");
+    assert_eq!(note.items, vec![("Billetes".to_string(), true), ("Toalla".to_string(), false)]);
+    assert!(note.categories.is_empty());
+
+    assert_eq!(split_front_matter("Sin front matter\n---\n"), (None, "Sin front matter\n---\n"));
+}
@@ -0,0 +1,281 @@
+pub mod archive;
+pub mod email;
+pub mod enex;
+pub mod joplin;
+pub mod keep;
+pub mod markdown;
+
+use std::time::{Duration, Instant};
+
+use actix_web::{rt, web};
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Postgres, Row, Transaction, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use tokio::sync::mpsc::{self, Sender};
+use utoipa::ToSchema;
+
+use crate::model::{attachment::Attachment, import_job::ImportJob,
+    note::NoteKind, note_link::NoteLink};
+
+pub struct ImportedAttachment{
+    pub filename: String,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A note read from another application, ready to be saved
+pub struct ImportedNote{
+    /// Identifies the note in the original application, like `keep:Compra`
+    pub source: String,
+    pub title: String,
+    pub body: String,
+    pub kind: NoteKind,
+    /// Text and checked state of the items of a checklist
+    pub items: Vec<(String, bool)>,
+    pub labels: Vec<String>,
+    pub categories: Vec<String>,
+    pub pinned: bool,
+    pub archived: bool,
+    pub trashed: bool,
+    pub color: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+    pub attachments: Vec<ImportedAttachment>,
+}
+
+impl ImportedNote{
+    pub fn new(source: String, title: String) -> Self{
+        Self{
+            source,
+            title,
+            body: String::new(),
+            kind: NoteKind::Text,
+            items: Vec::new(),
+            labels: Vec::new(),
+            categories: Vec::new(),
+            pinned: false,
+            archived: false,
+            trashed: false,
+            color: None,
+            created_at: Utc::now().naive_utc(),
+            updated_at: Utc::now().naive_utc(),
+            attachments: Vec::new(),
+        }
+    }
+}
+
+/// Something that could not be imported
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Failure{
+    #[schema(example = "Keep/Compra.json")]
+    pub source: String,
+    pub error: String,
+}
+
+/// The result of an import
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Report{
+    /// Notes created
+    pub created: usize,
+    /// Notes already imported before
+    pub skipped: usize,
+    pub failed: Vec<Failure>,
+}
+
+impl Report{
+    pub fn fail(&mut self, source: &str, error: impl ToString){
+        self.failed.push(Failure{
+            source: source.to_string(),
+            error: error.to_string(),
+        });
+    }
+}
+
+/// Saves imported notes in a workspace, skipping the ones whose source was
+/// already imported, and keeps the report
+pub struct Importer{
+    pool: web::Data<PgPool>,
+    workspace_id: i32,
+    user_id: i32,
+    pub report: Report,
+    created: Vec<(i32, String)>,
+}
+
+impl Importer{
+    pub fn new(pool: web::Data<PgPool>, workspace_id: i32, user_id: i32, report: Report) -> Self{
+        Self{
+            pool,
+            workspace_id,
+            user_id,
+            report,
+            created: Vec::new(),
+        }
+    }
+
+    pub async fn add(&mut self, note: ImportedNote){
+        let source = note.source.clone();
+        let body = note.body.clone();
+        match save(&self.pool, note, self.workspace_id, self.user_id).await {
+            Ok(Some(id)) => {
+                self.report.created += 1;
+                self.created.push((id, body));
+            },
+            Ok(None) => self.report.skipped += 1,
+            Err(e) => self.report.fail(&source, e),
+        }
+    }
+
+    /// Resolve the links of the notes created, once every note is in as
+    /// they may point to any of them
+    pub async fn finish(self) -> Report{
+        for (id, body) in self.created.iter() {
+            if let Err(e) = NoteLink::refresh(&self.pool, *id, body, self.workspace_id).await {
+                log::warn!("Can not resolve the links of imported note {}: {}", id, e);
+            }
+        }
+        self.report
+    }
+}
+
+/// Save all the notes at once
+pub async fn import(pool: web::Data<PgPool>, notes: Vec<ImportedNote>, workspace_id: i32, user_id: i32, report: Report) -> Report{
+    let mut importer = Importer::new(pool, workspace_id, user_id, report);
+    for note in notes {
+        importer.add(note).await;
+    }
+    importer.finish().await
+}
+
+/// What a parser running in the background sends to be imported
+pub enum Parsed{
+    Note(ImportedNote),
+    Failed(Failure),
+    /// How many bytes of the file have been read
+    Read(u64),
+}
+
+/// How often the progress of a background import is saved
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Import in the background what `parse` sends, recording the progress in
+/// the job. The parser runs in its own thread and is stopped when sending
+/// fails.
+pub fn spawn<P>(pool: web::Data<PgPool>, job_id: i32, workspace_id: i32, user_id: i32, parse: P)
+where
+    P: FnOnce(&Sender<Parsed>) -> Result<(), String> + Send + 'static,
+{
+    let (sender, mut receiver) = mpsc::channel(16);
+    let parser = std::thread::spawn(move || parse(&sender));
+    rt::spawn(async move {
+        let mut importer = Importer::new(pool.clone(), workspace_id, user_id, Report::default());
+        let mut read = 0;
+        let mut saved_at = Instant::now();
+        while let Some(parsed) = receiver.recv().await {
+            match parsed {
+                Parsed::Note(note) => importer.add(note).await,
+                Parsed::Failed(failure) => importer.report.failed.push(failure),
+                Parsed::Read(bytes) => read = bytes,
+            }
+            if saved_at.elapsed() >= PROGRESS_INTERVAL {
+                saved_at = Instant::now();
+                if let Err(e) = ImportJob::progress(&pool, job_id, read, &importer.report).await {
+                    log::warn!("Can not save the progress of import job {}: {}", job_id, e);
+                }
+            }
+        }
+        let error = match parser.join() {
+            Ok(result) => result.err(),
+            Err(_) => Some("The parser crashed".to_string()),
+        };
+        let report = importer.finish().await;
+        if let Err(e) = ImportJob::finish(&pool, job_id, &report, error).await {
+            log::error!("Can not finish import job {}: {}", job_id, e);
+        }
+    });
+}
+
+/// Save one note with its items, labels, categories and attachments.
+/// Returns `None` when the source was already imported.
+pub async fn save(pool: &web::Data<PgPool>, note: ImportedNote, workspace_id: i32, user_id: i32) -> Result<Option<i32>, Error>{
+    let mut tx = pool.begin().await?;
+    let exists = query(r#"SELECT id FROM notes WHERE workspace_id = $1 AND source = $2"#)
+        .bind(workspace_id)
+        .bind(&note.source)
+        .fetch_optional(&mut tx)
+        .await?;
+    if exists.is_some() {
+        return Ok(None);
+    }
+    let id: i32 = query(r#"INSERT INTO notes (title, body, kind, pinned, archived, trashed, color, source, created_at, updated_at, user_id, workspace_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12) RETURNING id"#)
+        .bind(&note.title)
+        .bind(&note.body)
+        .bind(note.kind.as_str())
+        .bind(note.pinned)
+        .bind(note.archived)
+        .bind(note.trashed)
+        .bind(&note.color)
+        .bind(&note.source)
+        .bind(note.created_at)
+        .bind(note.updated_at)
+        .bind(user_id)
+        .bind(workspace_id)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_one(&mut tx)
+        .await?;
+    for (position, (text, checked)) in note.items.iter().enumerate() {
+        query(r#"INSERT INTO note_items (note_id, text, checked, position, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6)"#)
+            .bind(id)
+            .bind(text)
+            .bind(checked)
+            .bind(position as i32)
+            .bind(note.created_at)
+            .bind(note.updated_at)
+            .execute(&mut tx)
+            .await?;
+    }
+    for name in note.labels.iter() {
+        let label_id = upsert_name(&mut tx, "labels", name, workspace_id, user_id).await?;
+        query(r#"INSERT INTO notes_labels (note_id, label_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"#)
+            .bind(id)
+            .bind(label_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    for name in note.categories.iter() {
+        let category_id = upsert_name(&mut tx, "categories", name, workspace_id, user_id).await?;
+        query(r#"INSERT INTO notes_categories (note_id, category_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"#)
+            .bind(id)
+            .bind(category_id)
+            .execute(&mut tx)
+            .await?;
+    }
+    for attachment in note.attachments.iter() {
+        Attachment::new(&mut tx, id, &attachment.filename, &attachment.mime_type, &attachment.data).await?;
+    }
+    tx.commit().await?;
+    Ok(Some(id))
+}
+
+/// The id of the label or category called `name`, created when missing
+async fn upsert_name(tx: &mut Transaction<'_, Postgres>, table: &str, name: &str, workspace_id: i32, user_id: i32) -> Result<i32, Error>{
+    let sql = format!("INSERT INTO {} (name, user_id, workspace_id) VALUES ($1, $2, $3) ON CONFLICT (name, workspace_id) DO UPDATE SET name = EXCLUDED.name RETURNING id", table);
+    query(&sql)
+        .bind(name)
+        .bind(user_id)
+        .bind(workspace_id)
+        .map(|row: PgRow| row.get("id"))
+        .fetch_one(&mut *tx)
+        .await
+}
+
+/// A title for a note without one: the first line of its text, shortened
+pub fn title_from(text: &str, fallback: &str) -> String{
+    let line = text.lines()
+        .map(|line| line.trim().trim_start_matches('#').trim())
+        .find(|line| !line.is_empty())
+        .unwrap_or(fallback);
+    match line.char_indices().nth(80) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
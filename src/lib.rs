@@ -0,0 +1,15 @@
+//! The notisbak server as a library, so the binary and the tests build the
+//! same application.
+pub mod admin;
+pub mod config;
+pub mod events;
+pub mod exporter;
+pub mod graphql;
+pub mod importer;
+pub mod mailbox;
+pub mod metrics;
+pub mod model;
+pub mod notifier;
+pub mod openapi;
+pub mod routes;
+pub mod webhooks;
@@ -0,0 +1,105 @@
+//! Mail sent to the inbox of a user becomes a note in its active workspace.
+//! The address is `key@domain`, and every `+label` after the key puts that
+//! label on the note. Mail comes from the embedded SMTP listener or from an
+//! MTA that pipes it to `POST /mail/{recipient}`.
+pub mod smtp;
+
+use std::fmt;
+
+use actix_web::web;
+use sqlx::{Error, PgPool};
+
+use crate::importer::{self, email, Report};
+use crate::model::{inbox::Inbox, workspace::Member};
+
+/// Why a message is not taken
+#[derive(Debug)]
+pub enum Rejected{
+    /// There is no inbox at the address, or its user is disabled
+    Recipient,
+    /// The user of the inbox can not write in its workspace
+    ReadOnly,
+    /// It is not a message
+    Message,
+    /// It could not be saved, so it may be sent again
+    Failed(String),
+}
+
+impl fmt::Display for Rejected{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self {
+            Rejected::Recipient => write!(f, "No such inbox"),
+            Rejected::ReadOnly => write!(f, "The workspace of the inbox is read only"),
+            Rejected::Message => write!(f, "Not a valid message"),
+            Rejected::Failed(e) => write!(f, "Can not save the note: {}", e),
+        }
+    }
+}
+
+/// `rejected` when there is no row, or a failure to try again later
+fn temporary(error: Error, rejected: Rejected) -> Rejected{
+    match error {
+        Error::RowNotFound => rejected,
+        e => Rejected::Failed(e.to_string()),
+    }
+}
+
+/// The key of the inbox and the labels of an address of the `domain`
+pub fn parse_address(address: &str, domain: &str) -> Option<(String, Vec<String>)>{
+    let (local, at) = address.trim().trim_start_matches('<').trim_end_matches('>').rsplit_once('@')?;
+    if !at.eq_ignore_ascii_case(domain) {
+        return None;
+    }
+    let mut parts = local.split('+');
+    let key = parts.next()?.to_lowercase();
+    let labels = parts
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some((key, labels))
+}
+
+/// Where a message goes
+pub struct Recipient{
+    pub user_id: i32,
+    pub labels: Vec<String>,
+}
+
+impl Recipient{
+    /// The user of an address of the inboxes `domain`, when it is enabled
+    pub async fn find(pool: &web::Data<PgPool>, address: &str, domain: &str) -> Result<Recipient, Rejected>{
+        let (key, labels) = parse_address(address, domain).ok_or(Rejected::Recipient)?;
+        let user_id = Inbox::user(pool, &key)
+            .await
+            .map_err(|e| temporary(e, Rejected::Recipient))?;
+        Ok(Recipient{user_id, labels})
+    }
+
+    /// Make a note of the raw message. A message already taken, by its
+    /// Message-ID, is skipped.
+    pub async fn deliver(&self, pool: &web::Data<PgPool>, raw: &[u8]) -> Result<Report, Rejected>{
+        let member = Member::active(pool, self.user_id)
+            .await
+            .map_err(|e| temporary(e, Rejected::ReadOnly))?;
+        if !member.can_write() {
+            return Err(Rejected::ReadOnly);
+        }
+        let mut note = email::to_note(raw).ok_or(Rejected::Message)?;
+        note.labels.extend(self.labels.iter().cloned());
+        let report = importer::import(pool.clone(), vec![note], member.workspace_id,
+            self.user_id, Report::default()).await;
+        match report.failed.first() {
+            Some(failure) => Err(Rejected::Failed(failure.error.clone())),
+            None => Ok(report),
+        }
+    }
+}
+
+#[test]
+fn test_parse_address() {
+    assert_eq!(parse_address("<K3jd+ideas+Casa@Notes.example.com>", "notes.example.com"),
+        Some(("k3jd".to_string(), vec!["ideas".to_string(), "Casa".to_string()])));
+    assert_eq!(parse_address("k3jd@example.com", "notes.example.com"), None);
+    assert_eq!(parse_address("k3jd", "notes.example.com"), None);
+}
@@ -0,0 +1,179 @@
+//! An SMTP listener that only takes mail for the inboxes. It has no TLS nor
+//! authentication, so it is meant to be behind an MTA or to test locally.
+use std::io;
+use std::time::Duration;
+
+use actix_web::{rt::{self, net::{TcpListener, TcpStream}}, web};
+use sqlx::PgPool;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+use super::{Recipient, Rejected};
+
+/// How long a client may be silent
+const TIMEOUT: Duration = Duration::from_secs(300);
+/// Longest command line, as in RFC 5321
+const MAX_LINE: u64 = 512;
+/// Longest piece of a line of a message read at once. Longer lines are
+/// read in pieces, counted against the size limit.
+const MAX_DATA_LINE: u64 = 1000;
+/// Most recipients of a message
+const MAX_RECIPIENTS: usize = 100;
+
+/// Listen on `address`, taking messages for the inboxes of `domain` up to
+/// `size_limit` bytes
+pub fn start(pool: PgPool, address: String, domain: String, size_limit: usize){
+    let pool = web::Data::new(pool);
+    rt::spawn(async move {
+        let listener = match TcpListener::bind(&address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::error!("Can not listen for mail on {}: {}", address, e);
+                return;
+            },
+        };
+        log::info!("Listening for mail on {}", address);
+        loop {
+            match listener.accept().await {
+                Ok((stream, peer)) => {
+                    let pool = pool.clone();
+                    let domain = domain.clone();
+                    rt::spawn(async move {
+                        if let Err(e) = session(pool, stream, &domain, size_limit).await {
+                            log::warn!("Mail session with {} ended: {}", peer, e);
+                        }
+                    });
+                },
+                Err(e) => log::error!("Can not accept mail connection: {}", e),
+            }
+        }
+    });
+}
+
+/// The address of `MAIL FROM:<...>` or `RCPT TO:<...>`
+fn path(argument: &str) -> &str{
+    let argument = argument.split_once(':').map_or(argument, |(_, path)| path).trim();
+    match (argument.find('<'), argument.find('>')) {
+        (Some(start), Some(end)) if start < end => &argument[start + 1..end],
+        _ => argument.split_whitespace().next().unwrap_or_default(),
+    }
+}
+
+/// Read up to the end of the line, but no more than `max` bytes. Without
+/// a newline at the end, the line goes on or the connection ended.
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R, line: &mut Vec<u8>, max: u64) -> io::Result<usize>{
+    line.clear();
+    rt::time::timeout(TIMEOUT, reader.take(max).read_until(b'\n', line))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Client silent for too long"))?
+}
+
+async fn session(pool: web::Data<PgPool>, stream: TcpStream, domain: &str, size_limit: usize) -> io::Result<()>{
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    writer.write_all(format!("220 {} notisbak ESMTP\r\n", domain).as_bytes()).await?;
+    let mut sender = false;
+    let mut recipients: Vec<Recipient> = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        if read_line(&mut reader, &mut line, MAX_LINE).await? == 0 {
+            return Ok(());
+        }
+        if !line.ends_with(b"\n") {
+            writer.write_all(b"500 Line too long\r\n").await?;
+            return Ok(());
+        }
+        let command = String::from_utf8_lossy(&line).trim_end().to_string();
+        let (verb, argument) = command.split_once(' ').unwrap_or((&command, ""));
+        let reply = match verb.to_uppercase().as_str() {
+            "HELO" => format!("250 {}", domain),
+            "EHLO" => format!("250-{}\r\n250-SIZE {}\r\n250 8BITMIME", domain, size_limit),
+            "MAIL" => {
+                sender = true;
+                recipients.clear();
+                "250 OK".to_string()
+            },
+            "RCPT" if !sender => "503 MAIL first".to_string(),
+            "RCPT" if recipients.len() >= MAX_RECIPIENTS => "452 Too many recipients".to_string(),
+            "RCPT" => match Recipient::find(&pool, path(argument), domain).await {
+                Ok(recipient) => {
+                    recipients.push(recipient);
+                    "250 OK".to_string()
+                },
+                Err(e @ Rejected::Failed(_)) => format!("451 {}", e),
+                Err(e) => format!("550 {}", e),
+            },
+            "DATA" if recipients.is_empty() => "503 RCPT first".to_string(),
+            "DATA" => {
+                writer.write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n").await?;
+                let mut data = Vec::new();
+                let mut too_large = false;
+                // Whether the piece read starts a line, or goes on with one
+                let mut line_start = true;
+                loop {
+                    if read_line(&mut reader, &mut line, MAX_DATA_LINE).await? == 0 {
+                        return Ok(());
+                    }
+                    if line_start && (line == b".\r\n" || line == b".\n") {
+                        break;
+                    }
+                    let content = match line_start {
+                        true => line.strip_prefix(b".").unwrap_or(&line),
+                        false => &line,
+                    };
+                    line_start = line.ends_with(b"\n");
+                    too_large = too_large || data.len() + content.len() > size_limit;
+                    if !too_large {
+                        data.extend_from_slice(content);
+                    }
+                }
+                let mut reply = "250 OK".to_string();
+                if too_large {
+                    reply = "552 Message too large".to_string();
+                } else {
+                    for recipient in recipients.iter() {
+                        match recipient.deliver(&pool, &data).await {
+                            Ok(_) => {},
+                            Err(e @ Rejected::Failed(_)) => reply = format!("451 {}", e),
+                            Err(e) => reply = format!("554 {}", e),
+                        }
+                    }
+                }
+                sender = false;
+                recipients.clear();
+                reply
+            },
+            "RSET" => {
+                sender = false;
+                recipients.clear();
+                "250 OK".to_string()
+            },
+            "NOOP" => "250 OK".to_string(),
+            "QUIT" => {
+                writer.write_all(b"221 Bye\r\n").await?;
+                return Ok(());
+            },
+            _ => "502 Command not implemented".to_string(),
+        };
+        writer.write_all(format!("{}\r\n", reply).as_bytes()).await?;
+    }
+}
+
+#[test]
+fn test_path() {
+    assert_eq!(path("TO:<k3jd+ideas@localhost>"), "k3jd+ideas@localhost");
+    assert_eq!(path("FROM:<ana@example.com> SIZE=1024"), "ana@example.com");
+    assert_eq!(path("TO: k3jd@localhost"), "k3jd@localhost");
+}
+
+#[actix_web::test]
+async fn test_read_line() {
+    let mut reader = BufReader::new(&b"HELO localhost\r\nlonger than eight\r\n"[..]);
+    let mut line = Vec::new();
+    assert_eq!(read_line(&mut reader, &mut line, 100).await.unwrap(), 16);
+    assert_eq!(line, b"HELO localhost\r\n");
+    // A long line comes in pieces, none longer than the maximum
+    assert_eq!(read_line(&mut reader, &mut line, 8).await.unwrap(), 8);
+    assert_eq!(line, b"longer t");
+    assert_eq!(read_line(&mut reader, &mut line, 100).await.unwrap(), 11);
+    assert_eq!(line, b"han eight\r\n");
+}
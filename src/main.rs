@@ -1,189 +1,257 @@
-mod routes;
-mod model;
-
-use sqlx::{postgres::PgPoolOptions, migrate::{Migrator, MigrateDatabase}};
-use actix_web::{App, HttpServer, web::{self, Data}, dev::ServiceRequest,
-    middleware::Logger, Error};
+use sqlx::{postgres::{PgPool, PgPoolOptions}, migrate::MigrateDatabase};
+use actix_web::{App, HttpServer, web::{self, Data}, middleware::{from_fn, Logger}};
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use utoipa::{OpenApi, Modify, openapi};
-use utoipa_swagger_ui::{SwaggerUi, Url};
-use std::{env, path::Path};
+use rand::{distributions::Alphanumeric, Rng};
+use std::{io::{self, BufRead}, path::PathBuf, time::Duration};
 use env_logger::Env;
-use actix_web_httpauth::{extractors::bearer::BearerAuth,
-    middleware::HttpAuthentication};
-use jsonwebtoken::{decode, decode_header, Validation, DecodingKey};
-use openapi::security::{SecurityScheme, HttpBuilder, HttpAuthScheme};
+use notisbak::{admin::{self, Stats}, config::{self, Config}, graphql, mailbox,
+    metrics, model::user::{Credentials, User}, notifier,
+    routes, webhooks};
+
+/// Notes server. Without a command it serves.
+#[derive(Parser)]
+#[command(name = "notisbak", version)]
+struct Cli{
+    /// Configuration file, `notisbak.toml` by default
+    #[arg(long, global = true, env = "NOTISBAK_SERVER_CONFIG")]
+    config: Option<PathBuf>,
+    /// Overrides `database.url`
+    #[arg(long, global = true)]
+    database_url: Option<String>,
+    /// Overrides `server.port`
+    #[arg(long, global = true)]
+    port: Option<u16>,
+    /// Overrides `server.workers`
+    #[arg(long, global = true)]
+    workers: Option<usize>,
+    /// Migrate even when the migrations applied differ from the ones of
+    /// the binary
+    #[arg(long, global = true)]
+    allow_drift: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command{
+    /// Migrate the database and serve the API
+    Serve,
+    /// Manage the migrations of the database
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Manage the users
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Figures of the server
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand{
+    /// Apply the pending migrations
+    Up,
+    /// Revert the last migrations applied
+    Down{
+        #[arg(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// List the migrations and whether they are applied
+    Status,
+}
+
+#[derive(Subcommand)]
+enum UserCommand{
+    /// Create a user with its personal workspace
+    Create{
+        email: String,
+        /// Read the password from stdin instead of generating one
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    List,
+    /// Do not let the user log in anymore
+    Disable{
+        email: String,
+    },
+    /// Let a disabled user log in again
+    Enable{
+        email: String,
+    },
+    /// Give the user a new password
+    ResetPassword{
+        email: String,
+        /// Read the password from stdin instead of generating one
+        #[arg(long)]
+        password_stdin: bool,
+    },
+}
 
+/// The password from stdin, or a new one that is printed
+fn password(from_stdin: bool) -> anyhow::Result<String>{
+    if from_stdin {
+        let mut password = String::new();
+        io::stdin().lock().read_line(&mut password)?;
+        let password = password.trim_end_matches(['\r', '\n']).to_string();
+        if password.is_empty() {
+            bail!("Empty password");
+        }
+        return Ok(password);
+    }
+    let password: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(16)
+        .map(char::from)
+        .collect();
+    println!("Password: {}", password);
+    Ok(password)
+}
 
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
+async fn main() -> anyhow::Result<()> {
     dotenv().ok();
+    let cli = Cli::parse();
 
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL not set");
-    let port = env::var("PORT").expect("PORT not set");
-
-    if !sqlx::Postgres::database_exists(&db_url).await.unwrap(){
-        sqlx::Postgres::create_database(&db_url).await.unwrap()
+    let mut config = Config::load(cli.config.as_deref())?;
+    if let Some(url) = cli.database_url {
+        config.database.url = url;
     }
+    if let Some(port) = cli.port {
+        config.server.port = port;
+    }
+    if let Some(workers) = cli.workers {
+        config.server.workers = workers;
+    }
+    let command = cli.command.unwrap_or(Command::Serve);
+    // Only serving needs more than the database
+    if matches!(command, Command::Serve) {
+        config.validate()?;
+    }else if config.database.url.is_empty() {
+        bail!("database.url (DATABASE_URL) is required");
+    }
+    config::install(config.clone());
 
-    // Migrate the database
-    let migrations = if env::var("RUST_ENV") == Ok("production".to_string()) {
-        // Productions migrations dir
-        std::env::current_exe()?.parent().unwrap().join("migrations")
-    } else {
-        // Development migrations dir
-        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
-        Path::new(&crate_dir)
-            .join("./migrations")
-    };
-    println!("{}", &migrations.display());
-
-
-    //    #[openapi(modifiers(&SecurityAddon))]
-
-    #[derive(OpenApi)]
-    #[openapi(
-        paths(
-            routes::labels::create_label,
-            routes::labels::read_label,
-            routes::labels::read_labels,
-            routes::labels::update_label,
-            routes::labels::delete_label,
-            routes::categories::create_category,
-            routes::categories::read_category,
-            routes::categories::read_categories,
-            routes::categories::update_category,
-            routes::categories::delete_category,
-            routes::notes::create_note,
-            routes::notes::read_note,
-            routes::notes::read_notes,
-            routes::notes::read_labels_for_note,
-            routes::notes::read_categories_for_note,
-            routes::notes::update_note,
-            routes::notes::delete_note,
-            routes::notes::add_label_to_note,
-            routes::notes::add_category_to_note,
-            routes::notes::delete_label_from_note,
-            routes::notes::delete_category_from_note,
-        ),
-        components(
-            schemas(model::label::Label,
-                    model::label::NewLabel,
-                    model::category::Category,
-                    model::category::NewCategory,
-                    model::note::Note,
-                    model::note::NewNote,
-                    model::note::UpdateNote)
-        ),
-    )]
-    struct ApiDoc;
-
-    struct SecurityAddon;
-
-    //impl Modify for SecurityAddon{
-    //    fn modify(&self, openapi: & mut utoipa::openapi::OpenApi){
-    //        openapi.components = Some(
-    //            utoipa::openapi::ComponentsBuilder::new()
-    //                .security_scheme(
-    //                    "api_jwt_token",
-    //                    SecurityScheme::Http(
-    //                        HttpBuilder::new()
-    //                            .scheme(HttpAuthScheme::Bearer)
-    //                            .bearer_format("JWT")
-    //                            .build(),
-    //                    ),
-    //                )
-    //                .build(),
-    //        )
-    //    }
-    //}
+    let db_url = &config.database.url;
+    if !sqlx::Postgres::database_exists(db_url).await?{
+        sqlx::Postgres::create_database(db_url).await?
+    }
 
     let pool = PgPoolOptions::new()
-        .max_connections(4)
-        .connect(&db_url)
+        .max_connections(config.database.max_connections)
+        .connect(db_url)
         .await
-        .expect("pool failed");
+        .context("pool failed")?;
+
+    if matches!(command, Command::Serve | Command::Migrate(MigrateCommand::Up | MigrateCommand::Down{..})) {
+        for migration in admin::drift(&pool).await? {
+            eprintln!("Migration {} is {} in the database", migration.version, migration.state.as_str());
+        }
+    }
+
+    match command {
+        Command::Serve => {
+            admin::migrate_up(&pool, cli.allow_drift).await
+                .context("Can not migrate the database, see `notisbak migrate status`")?;
+            serve(pool, config).await?;
+        },
+        Command::Migrate(MigrateCommand::Up) => {
+            for version in admin::migrate_up(&pool, cli.allow_drift).await? {
+                println!("Applied {}", version);
+            }
+            println!("Database up to date");
+        },
+        Command::Migrate(MigrateCommand::Down{steps}) => {
+            for version in admin::migrate_down(&pool, steps, cli.allow_drift).await? {
+                println!("Reverted {}", version);
+            }
+        },
+        Command::Migrate(MigrateCommand::Status) => {
+            for status in admin::migration_status(&pool).await? {
+                println!("{} {:8} {}", status.version, status.state.as_str(), status.description);
+            }
+        },
+        Command::User(command) => user(Data::new(pool), command).await?,
+        Command::Stats => println!("{}", Stats::read(&Data::new(pool)).await?),
+    }
+    Ok(())
+}
 
-    // Do migration
-    Migrator::new(migrations)
-        .await.unwrap()
-        .run(&pool)
-        .await.unwrap();
+async fn user(pool: Data<PgPool>, command: UserCommand) -> anyhow::Result<()>{
+    let by_email = |email: String| {
+        let pool = pool.clone();
+        async move {
+            User::get_by_email(&pool, &email).await
+                .with_context(|| format!("There is no user {}", email))
+        }
+    };
+    match command {
+        UserCommand::Create{email, password_stdin} => {
+            let password = password(password_stdin)?;
+            let user = User::new(&pool, Credentials{email, password}).await
+                .context("Can not create the user")?;
+            println!("Created user {} {}", user.id, user.email);
+        },
+        UserCommand::List => {
+            for user in User::all(&pool).await? {
+                let disabled = if user.disabled { "disabled" } else { "" };
+                println!("{:>5} {:40} {} {}", user.id, user.email,
+                    user.created_at.format("%Y-%m-%d %H:%M"), disabled);
+            }
+        },
+        UserCommand::Disable{email} => {
+            let user = User::set_disabled(&pool, by_email(email).await?.id, true).await?;
+            println!("Disabled user {} {}", user.id, user.email);
+        },
+        UserCommand::Enable{email} => {
+            let user = User::set_disabled(&pool, by_email(email).await?.id, false).await?;
+            println!("Enabled user {} {}", user.id, user.email);
+        },
+        UserCommand::ResetPassword{email, password_stdin} => {
+            let user = by_email(email).await?;
+            let password = password(password_stdin)?;
+            User::set_password(&pool, user.id, &password).await?;
+            println!("New password for {}", user.email);
+        },
+    }
+    Ok(())
+}
 
+async fn serve(pool: PgPool, config: Config) -> anyhow::Result<()>{
     env_logger::init_from_env(Env::default().default_filter_or("info"));
 
+    notifier::scheduler::start(pool.clone(), notifier::channels(&config),
+        Duration::from_secs(config.reminders.interval));
+    webhooks::start(pool.clone(), Duration::from_secs(config.webhooks.interval));
+
+    // Largest file accepted by the importers and the inboxes
+    let upload_limit = config.limits.upload_bytes();
+
+    // Mail to the inboxes of the users, when there is where to listen
+    if !config.mail.listen.is_empty() {
+        mailbox::smtp::start(pool.clone(), config.mail.listen.clone(),
+            config.mail.domain.clone(), upload_limit);
+    }
+
+    let schema = graphql::schema();
+    let workers = config.server.workers;
+    let port = config.server.port;
+
     HttpServer::new(move ||{
-        let auth = HttpAuthentication::bearer(validator);
         App::new()
+            .wrap(from_fn(metrics::record))
             .wrap(Logger::default())
+            .wrap(config.cors.cors())
             .app_data(Data::new(pool.clone()))
-            .service(web::scope("test")
-                .wrap(auth.clone())
-                .service(routes::notes::root)
-            )
-            .service(web::scope("auth")
-                .service(routes::users::login)
-                .service(routes::users::register)
-                .service(web::scope("")
-                    .wrap(auth.clone())
-                    .service(routes::users::validate)
-                )
-            )
-            .service(
-                web::scope("api")
-                .wrap(auth.clone())
-                .service(routes::notes::root)
-                .service(routes::notes::create_note)
-                .service(routes::notes::read_note)
-                .service(routes::notes::read_notes)
-                .service(routes::notes::read_labels_for_note)
-                .service(routes::notes::read_categories_for_note)
-                .service(routes::notes::update_note)
-                .service(routes::notes::delete_note)
-                .service(routes::notes::add_label_to_note)
-                .service(routes::notes::add_category_to_note)
-                .service(routes::notes::delete_label_from_note)
-                .service(routes::notes::delete_category_from_note)
-                .service(routes::categories::create_category)
-                .service(routes::categories::read_category)
-                .service(routes::categories::read_categories)
-                .service(routes::categories::update_category)
-                .service(routes::categories::delete_category)
-                .service(routes::labels::create_label)
-                .service(routes::labels::read_label)
-                .service(routes::labels::read_labels)
-                .service(routes::labels::update_label)
-                .service(routes::labels::delete_label)
-                .service(routes::users::login)
-                .service(routes::users::register)
-            )
-            .service(
-                SwaggerUi::new("/swagger-ui/{_:.*}")
-                    .urls(vec![
-                        (Url::new("api1", "/api-doc/openapi1.json"),
-                        ApiDoc::openapi()),
-                    ])
-            )
+            .app_data(Data::new(schema.clone()))
+            .app_data(Data::new(config.clone()))
+            .app_data(web::PayloadConfig::new(upload_limit))
+            .app_data(web::JsonConfig::default().limit(config.limits.json_bytes()))
+            .configure(|cfg| routes::config_with(cfg, &config.features))
     })
-    .workers(2)
-    .bind(format!("0.0.0.0:{}", &port))
-    .unwrap()
+    .workers(workers)
+    .bind(("0.0.0.0", port))?
     .run()
-    .await
-}
-
-async fn validator(req: ServiceRequest, credentials: BearerAuth) -> Result<ServiceRequest, (Error, ServiceRequest)>{
-    eprint!("{}", req.path());
-    eprint!("{:?}", req);
-    println!("Estoy aqui");
-    eprintln!("{}", credentials.token());
-    let decoded = decode::<routes::users::Claims>(credentials.token(),
-        &DecodingKey::from_secret("SECRETO".as_ref()),
-        &Validation::default());
-    let header = decode_header(credentials.token()).unwrap();
-    let jwt = header.jwk;
-    eprintln!("BearerAuth {:?}", credentials);
-    eprintln!("BearerAuth {:?}", decoded);
-    eprintln!("decoded {:?}", jwt);
-    Ok(req)
+    .await?;
+    Ok(())
 }
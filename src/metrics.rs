@@ -0,0 +1,161 @@
+//! Figures for Prometheus, served at `/metrics` in its text format: the
+//! requests by route and status, the pool of connections and the totals of
+//! the server. Only counts, never what the users wrote, and the routes by
+//! their pattern, so there are no ids nor tokens in the labels. The totals
+//! are read once a minute at most.
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::{Duration, Instant}};
+
+use actix_web::{body::MessageBody, dev::{ServiceRequest, ServiceResponse},
+    middleware::Next, web, Error};
+use once_cell::sync::Lazy;
+use sqlx::postgres::PgPool;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::admin::Stats;
+
+/// Upper bounds, in seconds, of the buckets of the request durations
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// The totals are counted again at most this often, however often the
+/// scrapes come
+const STATS_TTL: Duration = Duration::from_secs(60);
+
+/// Route of the requests that match none, like scanners looking around
+const UNMATCHED: &str = "unmatched";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RequestKey{
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Debug, Default)]
+struct Histogram{
+    /// Requests in each bucket, not cumulative
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+static REQUESTS: Lazy<Mutex<BTreeMap<RequestKey, Histogram>>> = Lazy::new(Default::default);
+
+/// The last totals and when they were read. Concurrent scrapes wait for the
+/// one reading them instead of reading them too.
+static STATS: Lazy<AsyncMutex<Option<(Instant, Stats)>>> = Lazy::new(Default::default);
+
+async fn stats(pool: &web::Data<PgPool>) -> Result<Stats, sqlx::Error>{
+    let mut cached = STATS.lock().await;
+    match cached.as_ref() {
+        Some((read_at, stats)) if read_at.elapsed() < STATS_TTL => Ok(stats.clone()),
+        _ => {
+            let stats = Stats::read(pool).await?;
+            *cached = Some((Instant::now(), stats.clone()));
+            Ok(stats)
+        },
+    }
+}
+
+/// Count a request that took `seconds`
+pub fn observe(method: &str, route: &str, status: u16, seconds: f64){
+    let key = RequestKey{
+        method: method.to_string(),
+        route: route.to_string(),
+        status,
+    };
+    let mut requests = REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    let histogram = requests.entry(key).or_default();
+    if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+        histogram.buckets[bucket] += 1;
+    }
+    histogram.count += 1;
+    histogram.sum += seconds;
+}
+
+/// Middleware that times every request, for `.wrap(from_fn(metrics::record))`
+pub async fn record(req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error>{
+    let method = req.method().to_string();
+    let route = req.match_pattern().unwrap_or_else(|| UNMATCHED.to_string());
+    let start = Instant::now();
+    let res = next.call(req).await;
+    let status = match &res {
+        Ok(res) => res.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    observe(&method, &route, status.as_u16(), start.elapsed().as_secs_f64());
+    res
+}
+
+/// A label value, with the escapes of the text format
+fn escape(value: &str) -> String{
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn requests(out: &mut String){
+    let requests = REQUESTS.lock().unwrap_or_else(|e| e.into_inner());
+    out.push_str("# HELP notisbak_http_requests_total Requests answered.\n");
+    out.push_str("# TYPE notisbak_http_requests_total counter\n");
+    for (key, histogram) in requests.iter() {
+        let _ = writeln!(out, "notisbak_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+            key.method, escape(&key.route), key.status, histogram.count);
+    }
+    out.push_str("# HELP notisbak_http_request_duration_seconds Time to answer the requests.\n");
+    out.push_str("# TYPE notisbak_http_request_duration_seconds histogram\n");
+    for (key, histogram) in requests.iter() {
+        let labels = format!("method=\"{}\",route=\"{}\",status=\"{}\"",
+            key.method, escape(&key.route), key.status);
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+            cumulative += count;
+            let _ = writeln!(out, "notisbak_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, cumulative);
+        }
+        let _ = writeln!(out, "notisbak_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, histogram.count);
+        let _ = writeln!(out, "notisbak_http_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum);
+        let _ = writeln!(out, "notisbak_http_request_duration_seconds_count{{{}}} {}", labels, histogram.count);
+    }
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display){
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+}
+
+/// Every metric, in the text format of Prometheus
+pub async fn render(pool: &web::Data<PgPool>, max_connections: u32) -> Result<String, sqlx::Error>{
+    let stats = stats(pool).await?;
+    let mut out = String::new();
+    requests(&mut out);
+    gauge(&mut out, "notisbak_db_pool_connections", "Connections open in the pool.", pool.size());
+    gauge(&mut out, "notisbak_db_pool_idle_connections", "Connections of the pool waiting to be used.", pool.num_idle());
+    gauge(&mut out, "notisbak_db_pool_max_connections", "Connections the pool can open.", max_connections);
+    gauge(&mut out, "notisbak_users", "Users, disabled included.", stats.users);
+    gauge(&mut out, "notisbak_disabled_users", "Users that can not log in.", stats.disabled_users);
+    gauge(&mut out, "notisbak_workspaces", "Workspaces.", stats.workspaces);
+    gauge(&mut out, "notisbak_notes", "Notes, archived and in the trash included.", stats.notes);
+    gauge(&mut out, "notisbak_archived_notes", "Archived notes.", stats.archived_notes);
+    gauge(&mut out, "notisbak_trashed_notes", "Notes in the trash.", stats.trashed_notes);
+    gauge(&mut out, "notisbak_labels", "Labels.", stats.labels);
+    gauge(&mut out, "notisbak_categories", "Categories.", stats.categories);
+    gauge(&mut out, "notisbak_attachments", "Attachments.", stats.attachments);
+    gauge(&mut out, "notisbak_pending_reminders", "Reminders still to send.", stats.pending_reminders);
+    gauge(&mut out, "notisbak_pending_webhook_deliveries", "Webhook deliveries still to send.", stats.pending_deliveries);
+    gauge(&mut out, "notisbak_database_size_bytes", "Size of the database on disk.", stats.database_size);
+    Ok(out)
+}
+
+#[test]
+fn test_requests(){
+    observe("GET", "/api/notes/{note_id}", 200, 0.02);
+    observe("GET", "/api/notes/{note_id}", 200, 3.0);
+    observe("GET", "/api/notes/{note_id}", 200, 30.0);
+    let mut out = String::new();
+    requests(&mut out);
+    let labels = "method=\"GET\",route=\"/api/notes/{note_id}\",status=\"200\"";
+    assert!(out.contains(&format!("notisbak_http_requests_total{{{}}} 3\n", labels)));
+    assert!(out.contains(&format!("notisbak_http_request_duration_seconds_bucket{{{},le=\"0.01\"}} 0\n", labels)));
+    assert!(out.contains(&format!("notisbak_http_request_duration_seconds_bucket{{{},le=\"0.025\"}} 1\n", labels)));
+    assert!(out.contains(&format!("notisbak_http_request_duration_seconds_bucket{{{},le=\"5\"}} 2\n", labels)));
+    assert!(out.contains(&format!("notisbak_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 3\n", labels)));
+    assert_eq!(escape("a\"b\\"), "a\\\"b\\\\");
+}
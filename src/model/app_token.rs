@@ -0,0 +1,106 @@
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+/// Length of the tokens given to the user
+const LENGTH: usize = 32;
+
+/// A token for a client that authenticates with basic auth, using the email
+/// of the user and the token as password
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AppToken{
+    pub id: i32,
+    #[schema(example = "Móvil")]
+    pub name: String,
+    pub created_at: NaiveDateTime,
+    /// When a client last authenticated with it
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewAppToken{
+    #[schema(example = "Móvil")]
+    pub name: String,
+}
+
+/// A token just created. It can not be read again.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreatedAppToken{
+    pub id: i32,
+    #[schema(example = "Móvil")]
+    pub name: String,
+    #[schema(example = "hG3kP0qZ8sW1yT5uV7xA9bC2dE4fJ6mN")]
+    pub token: String,
+    pub created_at: NaiveDateTime,
+}
+
+fn hash(token: &str) -> String{
+    format!("{:x}", md5::compute(token))
+}
+
+impl AppToken{
+    fn from_row(row: PgRow) -> AppToken{
+        AppToken{
+            id: row.get("id"),
+            name: row.get("name"),
+            created_at: row.get("created_at"),
+            used_at: row.get("used_at"),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, user_id: i32) -> Result<Vec<AppToken>, Error>{
+        query(r#"SELECT id, name, created_at, used_at FROM app_tokens WHERE user_id = $1 ORDER BY id"#)
+            .bind(user_id)
+            .map(AppToken::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn create(pool: &web::Data<PgPool>, name: &str, user_id: i32) -> Result<CreatedAppToken, Error>{
+        let token: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(LENGTH)
+            .map(char::from)
+            .collect();
+        let created_at = Utc::now().naive_utc();
+        query(r#"INSERT INTO app_tokens (name, token, user_id, created_at) VALUES ($1, $2, $3, $4) RETURNING id, name, created_at"#)
+            .bind(name)
+            .bind(hash(&token))
+            .bind(user_id)
+            .bind(created_at)
+            .map(|row: PgRow| CreatedAppToken{
+                id: row.get("id"),
+                name: row.get("name"),
+                token: token.clone(),
+                created_at: row.get("created_at"),
+            })
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn delete(pool: &web::Data<PgPool>, id: i32, user_id: i32) -> Result<AppToken, Error>{
+        query(r#"DELETE FROM app_tokens WHERE id = $1 AND user_id = $2 RETURNING id, name, created_at, used_at"#)
+            .bind(id)
+            .bind(user_id)
+            .map(AppToken::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// The id of the user with the `email` and the `token`, recording that
+    /// the token was used
+    pub async fn authenticate(pool: &web::Data<PgPool>, email: &str, token: &str) -> Result<i32, Error>{
+        query(r#"UPDATE app_tokens t SET used_at = $1 FROM users u
+            WHERE u.id = t.user_id AND u.email = $2 AND t.token = $3 AND NOT u.disabled
+            RETURNING t.user_id"#)
+            .bind(Utc::now().naive_utc())
+            .bind(email)
+            .bind(hash(token))
+            .map(|row: PgRow| row.get("user_id"))
+            .fetch_one(pool.get_ref())
+            .await
+    }
+}
@@ -0,0 +1,72 @@
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Postgres, Row, Transaction, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+/// A file attached to a note. The content is downloaded apart.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Attachment{
+    pub id: i32,
+    pub note_id: i32,
+    #[schema(example = "foto.jpg")]
+    pub filename: String,
+    #[schema(example = "image/jpeg")]
+    pub mime_type: String,
+    pub size: i32,
+    pub created_at: NaiveDateTime,
+}
+
+const COLUMNS: &str = "a.id, a.note_id, a.filename, a.mime_type, a.size, a.created_at";
+
+impl Attachment{
+    fn from_row(row: PgRow) -> Attachment{
+        Attachment{
+            id: row.get("id"),
+            note_id: row.get("note_id"),
+            filename: row.get("filename"),
+            mime_type: row.get("mime_type"),
+            size: row.get("size"),
+            created_at: row.get("created_at"),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32) -> Result<Vec<Attachment>, Error>{
+        let sql = format!("SELECT {} FROM attachments a INNER JOIN notes n ON n.id = a.note_id WHERE a.note_id = $1 AND n.workspace_id = $2 ORDER BY a.id", COLUMNS);
+        query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .map(Attachment::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    /// The attachment with its content
+    pub async fn get(pool: &web::Data<PgPool>, note_id: i32, id: i32, workspace_id: i32) -> Result<(Attachment, Vec<u8>), Error>{
+        let sql = format!("SELECT {}, a.data FROM attachments a INNER JOIN notes n ON n.id = a.note_id WHERE a.id = $1 AND a.note_id = $2 AND n.workspace_id = $3", COLUMNS);
+        query(&sql)
+            .bind(id)
+            .bind(note_id)
+            .bind(workspace_id)
+            .map(|row: PgRow| {
+                let data = row.get("data");
+                (Attachment::from_row(row), data)
+            })
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn new(tx: &mut Transaction<'_, Postgres>, note_id: i32, filename: &str, mime_type: &str, data: &[u8]) -> Result<Attachment, Error>{
+        let sql = format!("INSERT INTO attachments AS a (note_id, filename, mime_type, size, data, created_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}", COLUMNS);
+        query(&sql)
+            .bind(note_id)
+            .bind(filename)
+            .bind(mime_type)
+            .bind(data.len() as i32)
+            .bind(data)
+            .bind(Utc::now().naive_utc())
+            .map(Attachment::from_row)
+            .fetch_one(&mut *tx)
+            .await
+    }
+}
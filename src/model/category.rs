@@ -3,7 +3,9 @@ use sqlx::{query, FromRow, Error, Row, postgres::{PgPool, PgRow}};
 use serde::{Serialize, Deserialize};
 use utoipa::ToSchema;
 
-#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
+use crate::events;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct Category{
     #[schema(example = 1)]
     pub id: i32,
@@ -11,12 +13,6 @@ pub struct Category{
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CategoryWU{
-    pub id: i32,
-    pub name: String,
-    pub user_id: i32,
-}
 #[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct NewCategory{
     #[schema(example = "categoría 1")]
@@ -24,13 +20,13 @@ pub struct NewCategory{
 }
 
 impl Category{
-    pub async fn all(pool: web::Data<PgPool>, user_id: i32) -> Result<Vec<Category>, Error>{
+    pub async fn all(pool: web::Data<PgPool>, workspace_id: i32) -> Result<Vec<Category>, Error>{
         let sql = r#"SELECT id, name
         FROM categories
-        WHERE user_id = $1
+        WHERE workspace_id = $1
         "#;
         query(sql)
-            .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Category{
                 id: row.get("id"),
                 name: row.get("name"),
@@ -39,14 +35,14 @@ impl Category{
             .await
     }
 
-    pub async fn get(pool: web::Data<PgPool>, id: i32, user_id: i32) -> Result<Category, Error>{
+    pub async fn get(pool: web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Category, Error>{
         let sql = r#"SELECT id, name
         FROM categories
-        WHERE id = $1 AND user_id = $2
+        WHERE id = $1 AND workspace_id = $2
         "#;
         query(sql)
             .bind(id)
-            .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Category{
                 id: row.get("id"),
                 name: row.get("name"),
@@ -55,45 +51,54 @@ impl Category{
             .await
     }
 
-    pub async fn new(pool: web::Data<PgPool>, name: &str, user_id: i32) -> Result<Category, Error>{
-        query(r#"INSERT INTO categories (name, user_id) VALUES ($1, $2) RETURNING id, name;"#)
+    pub async fn new(pool: web::Data<PgPool>, name: &str, workspace_id: i32, user_id: i32) -> Result<Category, Error>{
+        let category = query(r#"INSERT INTO categories (name, user_id, workspace_id) VALUES ($1, $2, $3) RETURNING id, name;"#)
             .bind(name)
             .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Category{
                 id: row.get("id"),
                 name: row.get("name"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "category.created", workspace_id, &category).await?;
+        Ok(category)
     }
 
-    pub async fn update(pool: web::Data<PgPool>, category: Category) -> Result<Category, Error>{
-        query(r#"UPDATE categories SET name=$2 WHERE id=$1 RETURNING id, name;"#)
+    pub async fn update(pool: web::Data<PgPool>, category: Category, workspace_id: i32) -> Result<Category, Error>{
+        let category = query(r#"UPDATE categories SET name=$2 WHERE id=$1 AND workspace_id=$3 RETURNING id, name;"#)
             .bind(category.id)
             .bind(category.name)
+            .bind(workspace_id)
             .map(|row: PgRow| Category{
                 id: row.get("id"),
                 name: row.get("name"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "category.updated", workspace_id, &category).await?;
+        Ok(category)
     }
 
-    pub async fn delete(pool: web::Data<PgPool>, id: i32) -> Result<Category, Error>{
-        query(r#"DELETE FROM categories WHERE id = $1 RETURNING id, name;"#)
+    pub async fn delete(pool: web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Category, Error>{
+        let category = query(r#"DELETE FROM categories WHERE id = $1 AND workspace_id = $2 RETURNING id, name;"#)
             .bind(id)
+            .bind(workspace_id)
             .map(|row: PgRow| Category{
                 id: row.get("id"),
                 name: row.get("name"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "category.deleted", workspace_id, &category).await?;
+        Ok(category)
     }
 
-    pub async fn get_categories_for_note(pool: web::Data<PgPool>, note_id: i32, user_id: i32) -> Result<Vec<Category>, Error>{
-        query(r#"SELECT c.id, c.name FROM categories c INNER JOIN notes_categories nc ON nc.category_id = c.id AND note_id = $1 AND c.user_id = $2"#)
+    pub async fn get_categories_for_note(pool: web::Data<PgPool>, note_id: i32, workspace_id: i32) -> Result<Vec<Category>, Error>{
+        query(r#"SELECT c.id, c.name FROM categories c INNER JOIN notes_categories nc ON nc.category_id = c.id AND note_id = $1 AND c.workspace_id = $2"#)
             .bind(note_id)
-            .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Category{
                 id: row.get("id"),
                 name: row.get("name"),
@@ -101,4 +106,17 @@ impl Category{
             .fetch_all(pool.get_ref())
             .await
     }
+
+    /// The categories of several notes at once, paired with the id of their note
+    pub async fn for_notes(pool: &web::Data<PgPool>, note_ids: &[i32], workspace_id: i32) -> Result<Vec<(i32, Category)>, Error>{
+        query(r#"SELECT nc.note_id, c.id, c.name FROM categories c INNER JOIN notes_categories nc ON nc.category_id = c.id WHERE nc.note_id = ANY($1) AND c.workspace_id = $2 ORDER BY c.name"#)
+            .bind(note_ids)
+            .bind(workspace_id)
+            .map(|row: PgRow| (row.get("note_id"), Category{
+                id: row.get("id"),
+                name: row.get("name"),
+            }))
+            .fetch_all(pool.get_ref())
+            .await
+    }
 }
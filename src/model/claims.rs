@@ -1,56 +1,41 @@
 use serde::{Serialize, Deserialize};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
-use jsonwebtoken::{encode, decode, decode_header, EncodingKey, DecodingKey,
+use jsonwebtoken::{encode, decode, EncodingKey, DecodingKey,
                    Header, Validation};
-use std::env;
+use std::io::{Error, ErrorKind};
+
+use crate::config;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
-    pub exp: usize,
+    pub exp: u64,
 }
 
 impl Claims{
     pub fn new(index: i32)-> Self{
-        let expiration: usize = env::var("EXPIRATION")
-            .expect("EXPIRATION not set")
-            .parse()
-            .unwrap();
-        let exp = usize::try_from(
-            jsonwebtoken::get_current_timestamp()).unwrap() + expiration;
+        let expiration = config::get().jwt.expiration;
         Self{
             sub: index.to_string(),
-            exp,
+            exp: jsonwebtoken::get_current_timestamp().saturating_add(expiration),
         }
     }
-    pub fn get_token(&self) -> Result<String, std::io::Error>{
-        let secret = env::var("SECRET").unwrap();
-        Ok(encode(
+    pub fn get_token(&self) -> Result<String, Error>{
+        let secret = &config::get().jwt.secret;
+        encode(
             &Header::default(),
             &self,
-            &EncodingKey::from_secret(secret.as_ref())).unwrap()
-        )
+            &EncodingKey::from_secret(secret.as_ref())
+        ).map_err(Error::other)
     }
-    pub fn get_index(credentials: BearerAuth) -> Result<i32, std::io::Error>{
-        let secret = env::var("SECRET").unwrap();
+    pub fn get_index(credentials: BearerAuth) -> Result<i32, Error>{
+        let secret = &config::get().jwt.secret;
         let decoded = decode::<Claims>(
             credentials.token(),
             &DecodingKey::from_secret(secret.as_bytes()),
             &Validation::default()
-        );
-        Ok(decoded.unwrap().claims.sub.parse().unwrap())
+        ).map_err(|e| Error::new(ErrorKind::PermissionDenied, e))?;
+        decoded.claims.sub.parse()
+            .map_err(|e| Error::new(ErrorKind::PermissionDenied, e))
     }
 }
-
-#[derive(Serialize, Deserialize)]
-struct Response{
-    code: String,
-    message: String,
-    token: Option<String>,
-}
-
-pub fn get_user_id_from_token(credentials: BearerAuth) -> Result<i32, std::io::Error>{
-    let secret = env::var("SECRET").unwrap();
-    let decoded = decode::<Claims>(credentials.token(), &DecodingKey::from_secret(secret.as_bytes()), &Validation::default());
-    Ok(decoded.unwrap().claims.sub.parse().unwrap())
-}
@@ -0,0 +1,212 @@
+//! The notes of a workspace as a tree of markdown files, as WebDAV shows
+//! them. Categories are folders, nested when their name has `/`, and a
+//! note is in the folder of its first category by name.
+use std::collections::{BTreeMap, HashMap};
+
+use actix_web::web;
+use chrono::NaiveDateTime;
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+
+use crate::exporter::markdown::file_name;
+use crate::model::category::Category;
+use crate::model::note::{self, Note, NoteKind};
+use crate::model::note_item::{self, NoteItem};
+
+pub struct DavNote{
+    pub id: i32,
+    pub title: String,
+    pub kind: NoteKind,
+    pub content: String,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl DavNote{
+    pub fn etag(&self) -> String{
+        format!("{}-{}", self.id, self.version)
+    }
+
+    /// Checklists can not be written as text
+    pub fn readonly(&self) -> bool{
+        self.kind == NoteKind::Checklist
+    }
+
+    /// The etag of the note as it is now
+    pub async fn etag_of(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<String, Error>{
+        query(r#"SELECT version FROM notes WHERE id = $1 AND workspace_id = $2"#)
+            .bind(id)
+            .bind(workspace_id)
+            .map(|row: PgRow| format!("{}-{}", id, row.get::<i32, _>("version")))
+            .fetch_one(pool.get_ref())
+            .await
+    }
+}
+
+/// What there is at a path of the tree
+pub enum Resource<'a>{
+    /// A folder, with the category it shows if it is not only the parent
+    /// of other folders
+    Folder(Option<&'a (i32, String)>),
+    File(&'a DavNote),
+}
+
+#[derive(Default)]
+pub struct Tree{
+    /// Folders by path, without slashes at the ends. The root is empty.
+    folders: BTreeMap<String, Option<(i32, String)>>,
+    files: BTreeMap<String, DavNote>,
+}
+
+/// The folder of a category
+pub fn folder_path(category: &str) -> String{
+    category.split('/')
+        .filter(|part| !part.trim().is_empty())
+        .map(file_name)
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+/// The folder of a path and its name
+pub fn split(path: &str) -> (&str, &str){
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+impl Tree{
+    /// Every folder, but only the files of the folders `paths`
+    pub async fn load(pool: &web::Data<PgPool>, workspace_id: i32, paths: &[&str]) -> Result<Tree, Error>{
+        let mut tree = Tree::default();
+        tree.folders.insert(String::new(), None);
+        let mut categories = Category::all(pool.clone(), workspace_id).await?;
+        categories.sort_by(|a, b| a.name.cmp(&b.name));
+        // The categories whose notes are in the folders
+        let mut names = Vec::new();
+        for category in categories {
+            let path = folder_path(&category.name);
+            if paths.contains(&path.as_str()) {
+                names.push(category.name.clone());
+            }
+            tree.add_folder(&path);
+            tree.folders.entry(path).or_default().get_or_insert((category.id, category.name));
+        }
+        let root = paths.contains(&"");
+        // A note is in the folder of its first category, so the filter of
+        // the outer SELECT is the one that counts
+        let sql = format!(r#"SELECT * FROM (SELECT {},
+                (SELECT c.name FROM notes_categories nc
+                    INNER JOIN categories c ON c.id = nc.category_id
+                    WHERE nc.note_id = notes.id ORDER BY c.name LIMIT 1) AS category
+                FROM notes WHERE workspace_id = $1 AND NOT trashed
+                AND (EXISTS (SELECT 1 FROM notes_categories nc
+                        INNER JOIN categories c ON c.id = nc.category_id
+                        WHERE nc.note_id = notes.id AND c.name = ANY($2))
+                    OR ($3 AND NOT EXISTS (SELECT 1 FROM notes_categories nc WHERE nc.note_id = notes.id)))
+            ) notes
+            WHERE category = ANY($2) OR ($3 AND category IS NULL)
+            ORDER BY id"#, note::COLUMNS);
+        let notes = query(&sql)
+            .bind(workspace_id)
+            .bind(&names)
+            .bind(root)
+            .map(|row: PgRow| {
+                let category: Option<String> = row.get("category");
+                let version: i32 = row.get("version");
+                (Note::from_row(row), category, version)
+            })
+            .fetch_all(pool.get_ref())
+            .await?;
+        let ids: Vec<i32> = notes.iter().map(|(note, _, _)| note.id).collect();
+        let mut items: HashMap<i32, Vec<NoteItem>> = HashMap::new();
+        for item in NoteItem::for_notes(pool, &ids, workspace_id).await? {
+            items.entry(item.note_id).or_default().push(item);
+        }
+        for (note, category, version) in notes {
+            let folder = category.as_deref().map(folder_path).unwrap_or_default();
+            let name = file_name(&note.title);
+            let mut path = join(&folder, &format!("{}.md", name));
+            // Notes with the same title, after the first one, have their id
+            if tree.files.contains_key(&path) {
+                path = join(&folder, &format!("{} ({}).md", name, note.id));
+            }
+            let content = note_item::to_markdown(&note, items.get(&note.id).map(Vec::as_slice).unwrap_or_default());
+            tree.files.insert(path, DavNote{
+                id: note.id,
+                title: note.title,
+                kind: note.kind,
+                content,
+                version,
+                created_at: note.created_at,
+                updated_at: note.updated_at,
+            });
+        }
+        Ok(tree)
+    }
+
+    /// Add the parents of the folder `path`
+    fn add_folder(&mut self, path: &str){
+        let mut parent = String::new();
+        for part in path.split('/') {
+            parent = join(&parent, part);
+            self.folders.entry(parent.clone()).or_default();
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<Resource<'_>>{
+        match self.folders.get(path) {
+            Some(category) => Some(Resource::Folder(category.as_ref())),
+            None => self.files.get(path).map(Resource::File),
+        }
+    }
+
+    /// What there is directly in the folder `path`
+    pub fn children(&self, path: &str) -> Vec<(&str, Resource<'_>)>{
+        let folders = self.folders.iter()
+            .filter(|(child, _)| !child.is_empty() && split(child).0 == path)
+            .map(|(child, category)| (child.as_str(), Resource::Folder(category.as_ref())));
+        let files = self.files.iter()
+            .filter(|(child, _)| split(child).0 == path)
+            .map(|(child, note)| (child.as_str(), Resource::File(note)));
+        folders.chain(files).collect()
+    }
+
+    /// The name of the category for the notes put in the folder `path`: the
+    /// one it shows or else one named after the folder
+    pub fn category(&self, path: &str) -> Option<String>{
+        match self.folders.get(path)? {
+            Some((_, name)) => Some(name.clone()),
+            None => Some(path.to_string()),
+        }
+    }
+}
+
+pub fn join(folder: &str, name: &str) -> String{
+    match folder.is_empty() {
+        true => name.to_string(),
+        false => format!("{}/{}", folder, name),
+    }
+}
+
+#[test]
+fn test_tree() {
+    let mut tree = Tree::default();
+    tree.folders.insert(String::new(), None);
+    tree.add_folder(&folder_path("Casa/Recados: pan"));
+    tree.folders.insert("Casa".to_string(), Some((1, "Casa".to_string())));
+    tree.files.insert("Casa/Compra.md".to_string(), DavNote{
+        id: 1,
+        title: "Compra".to_string(),
+        kind: NoteKind::Text,
+        content: String::new(),
+        version: 3,
+        created_at: NaiveDateTime::default(),
+        updated_at: NaiveDateTime::default(),
+    });
+    let children: Vec<&str> = tree.children("Casa").into_iter().map(|(path, _)| path).collect();
+    assert_eq!(children, vec!["Casa/Recados- pan", "Casa/Compra.md"]);
+    assert_eq!(tree.children("").len(), 1);
+    assert_eq!(tree.category("Casa"), Some("Casa".to_string()));
+    assert_eq!(tree.category("Casa/Recados- pan"), Some("Casa/Recados- pan".to_string()));
+    assert_eq!(tree.category("Otra"), None);
+    assert!(matches!(tree.get("Casa/Compra.md"), Some(Resource::File(note)) if note.etag() == "1-3"));
+    assert_eq!(split("Casa/Compra.md"), ("Casa", "Compra.md"));
+}
@@ -0,0 +1,196 @@
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::model::note::{self, Note};
+
+/// Length of the tokens of the feeds
+const LENGTH: usize = 24;
+
+/// What a feed publishes: the notes with a label or in a category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedSource{
+    Label,
+    Category,
+}
+
+impl FeedSource{
+    fn column(&self) -> &'static str{
+        match self {
+            FeedSource::Label => "label_id",
+            FeedSource::Category => "category_id",
+        }
+    }
+
+    fn table(&self) -> &'static str{
+        match self {
+            FeedSource::Label => "labels",
+            FeedSource::Category => "categories",
+        }
+    }
+
+    /// The source of the feeds under `/feeds/{path}`
+    pub fn from_path(path: &str) -> Option<FeedSource>{
+        match path {
+            "labels" => Some(FeedSource::Label),
+            "categories" => Some(FeedSource::Category),
+            _ => None,
+        }
+    }
+}
+
+/// A label or a category published as Atom and RSS
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Feed{
+    pub id: i32,
+    pub source: FeedSource,
+    /// The id of the label or the category
+    #[schema(example = 1)]
+    pub source_id: i32,
+    /// The name of the label or the category
+    #[schema(example = "Changelog")]
+    pub name: String,
+    /// Needed to read the feed when it is not public
+    pub token: String,
+    pub public: bool,
+    #[schema(example = "/feeds/labels/1.atom?token=k3jd81hs0c9xq2lmzp4w7r5t")]
+    pub atom: String,
+    #[schema(example = "/feeds/labels/1.rss?token=k3jd81hs0c9xq2lmzp4w7r5t")]
+    pub rss: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewFeed{
+    pub source: FeedSource,
+    #[schema(example = 1)]
+    pub source_id: i32,
+    /// Whether anybody can read it without the token, false by default
+    pub public: Option<bool>,
+}
+
+const SELECT: &str = r#"SELECT f.id, f.label_id, f.category_id, f.token, f.public,
+    f.workspace_id, f.created_at, COALESCE(l.name, c.name) AS name
+    FROM feeds f
+    LEFT JOIN labels l ON l.id = f.label_id
+    LEFT JOIN categories c ON c.id = f.category_id"#;
+
+fn token() -> String{
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+impl Feed{
+    fn from_row(row: PgRow) -> Feed{
+        let (source, source_id) = match row.get::<Option<i32>, _>("label_id") {
+            Some(id) => (FeedSource::Label, id),
+            None => (FeedSource::Category, row.get("category_id")),
+        };
+        let token: String = row.get("token");
+        let public: bool = row.get("public");
+        let path = format!("/feeds/{}/{}", source.table(), source_id);
+        let query = match public {
+            true => String::new(),
+            false => format!("?token={}", token),
+        };
+        Feed{
+            id: row.get("id"),
+            source,
+            source_id,
+            name: row.get("name"),
+            token,
+            public,
+            atom: format!("{}.atom{}", path, query),
+            rss: format!("{}.rss{}", path, query),
+            created_at: row.get("created_at"),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Vec<Feed>, Error>{
+        let sql = format!("{} WHERE f.workspace_id = $1 ORDER BY f.id", SELECT);
+        query(&sql)
+            .bind(workspace_id)
+            .map(Feed::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    /// The feed of a label or category, with its workspace
+    pub async fn find(pool: &web::Data<PgPool>, source: FeedSource, source_id: i32) -> Result<(Feed, i32), Error>{
+        let sql = format!("{} WHERE f.{} = $1", SELECT, source.column());
+        query(&sql)
+            .bind(source_id)
+            .map(|row: PgRow| {
+                let workspace_id = row.get("workspace_id");
+                (Feed::from_row(row), workspace_id)
+            })
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// Publish the label or category, or change whether its feed is public
+    pub async fn new(pool: &web::Data<PgPool>, feed: NewFeed, workspace_id: i32, user_id: i32) -> Result<Feed, Error>{
+        let sql = format!(r#"INSERT INTO feeds ({column}, token, public, workspace_id, user_id, created_at)
+            SELECT id, $1, $2, workspace_id, $3, $4 FROM {table} WHERE id = $5 AND workspace_id = $6
+            ON CONFLICT ({column}) DO UPDATE SET public = EXCLUDED.public
+            RETURNING id"#,
+            column = feed.source.column(), table = feed.source.table());
+        // Nothing is inserted when the source is not of the workspace
+        let id: i32 = query(&sql)
+            .bind(token())
+            .bind(feed.public.unwrap_or(false))
+            .bind(user_id)
+            .bind(Utc::now().naive_utc())
+            .bind(feed.source_id)
+            .bind(workspace_id)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_one(pool.get_ref())
+            .await?;
+        let sql = format!("{} WHERE f.id = $1 AND f.workspace_id = $2", SELECT);
+        query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(Feed::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn delete(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Feed, Error>{
+        let sql = format!("{} WHERE f.id = $1 AND f.workspace_id = $2", SELECT);
+        let feed = query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(Feed::from_row)
+            .fetch_one(pool.get_ref())
+            .await?;
+        query(r#"DELETE FROM feeds WHERE id = $1"#)
+            .bind(id)
+            .execute(pool.get_ref())
+            .await?;
+        Ok(feed)
+    }
+
+    /// The last changed notes of the feed, but the trashed ones
+    pub async fn notes(&self, pool: &web::Data<PgPool>, workspace_id: i32, limit: i64) -> Result<Vec<Note>, Error>{
+        let link = match self.source {
+            FeedSource::Label => "SELECT note_id FROM notes_labels WHERE label_id = $2",
+            FeedSource::Category => "SELECT note_id FROM notes_categories WHERE category_id = $2",
+        };
+        let sql = format!("SELECT {} FROM notes WHERE workspace_id = $1 AND NOT trashed AND id IN ({}) ORDER BY updated_at DESC LIMIT $3",
+            note::COLUMNS, link);
+        query(&sql)
+            .bind(workspace_id)
+            .bind(self.source_id)
+            .bind(limit)
+            .map(Note::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+}
@@ -0,0 +1,144 @@
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, types::Json, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::importer::{Failure, Report};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus{
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobStatus{
+    pub fn as_str(&self) -> &'static str{
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Done => "done",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+/// An import running in the background
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ImportJob{
+    pub id: i32,
+    #[schema(example = "enex")]
+    pub kind: String,
+    pub status: JobStatus,
+    /// Percentage of the file already read
+    #[schema(example = 42.5)]
+    pub progress: f64,
+    pub report: Report,
+    /// Why the job failed
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+const COLUMNS: &str = "id, kind, status, total_bytes, read_bytes, created, skipped, failed, error, created_at, updated_at";
+
+impl ImportJob{
+    fn from_row(row: PgRow) -> ImportJob{
+        let status = match row.get::<String, _>("status").as_str() {
+            "done" => JobStatus::Done,
+            "failed" => JobStatus::Failed,
+            _ => JobStatus::Running,
+        };
+        let total_bytes: i64 = row.get("total_bytes");
+        let read_bytes: i64 = row.get("read_bytes");
+        let progress = match total_bytes {
+            0 => 0.0,
+            total => (read_bytes as f64 * 1000.0 / total as f64).round() / 10.0,
+        };
+        ImportJob{
+            id: row.get("id"),
+            kind: row.get("kind"),
+            status,
+            progress,
+            report: Report{
+                created: row.get::<i32, _>("created") as usize,
+                skipped: row.get::<i32, _>("skipped") as usize,
+                failed: row.get::<Json<Vec<Failure>>, _>("failed").0,
+            },
+            error: row.get("error"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, user_id: i32) -> Result<Vec<ImportJob>, Error>{
+        let sql = format!("SELECT {} FROM import_jobs WHERE user_id = $1 ORDER BY id DESC", COLUMNS);
+        query(&sql)
+            .bind(user_id)
+            .map(ImportJob::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn get(pool: &web::Data<PgPool>, id: i32, user_id: i32) -> Result<ImportJob, Error>{
+        let sql = format!("SELECT {} FROM import_jobs WHERE id = $1 AND user_id = $2", COLUMNS);
+        query(&sql)
+            .bind(id)
+            .bind(user_id)
+            .map(ImportJob::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// A running job importing a file of `total_bytes`
+    pub async fn new(pool: &web::Data<PgPool>, kind: &str, total_bytes: u64, workspace_id: i32, user_id: i32) -> Result<ImportJob, Error>{
+        let created_at = Utc::now().naive_utc();
+        let updated_at = Utc::now().naive_utc();
+        let sql = format!("INSERT INTO import_jobs (kind, status, total_bytes, user_id, workspace_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}", COLUMNS);
+        query(&sql)
+            .bind(kind)
+            .bind(JobStatus::Running.as_str())
+            .bind(total_bytes as i64)
+            .bind(user_id)
+            .bind(workspace_id)
+            .bind(created_at)
+            .bind(updated_at)
+            .map(ImportJob::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// Record how far the job got
+    pub async fn progress(pool: &web::Data<PgPool>, id: i32, read_bytes: u64, report: &Report) -> Result<(), Error>{
+        query(r#"UPDATE import_jobs SET read_bytes = $1, created = $2, skipped = $3, failed = $4, updated_at = $5 WHERE id = $6"#)
+            .bind(read_bytes as i64)
+            .bind(report.created as i32)
+            .bind(report.skipped as i32)
+            .bind(Json(&report.failed))
+            .bind(Utc::now().naive_utc())
+            .bind(id)
+            .execute(pool.get_ref())
+            .await
+            .map(|_| ())
+    }
+
+    /// Mark the job as done, or as failed with `error`
+    pub async fn finish(pool: &web::Data<PgPool>, id: i32, report: &Report, error: Option<String>) -> Result<(), Error>{
+        let status = match error {
+            Some(_) => JobStatus::Failed,
+            None => JobStatus::Done,
+        };
+        query(r#"UPDATE import_jobs SET status = $1, read_bytes = CASE WHEN $2 IS NULL THEN total_bytes ELSE read_bytes END, error = $2, created = $3, skipped = $4, failed = $5, updated_at = $6 WHERE id = $7"#)
+            .bind(status.as_str())
+            .bind(error)
+            .bind(report.created as i32)
+            .bind(report.skipped as i32)
+            .bind(Json(&report.failed))
+            .bind(Utc::now().naive_utc())
+            .bind(id)
+            .execute(pool.get_ref())
+            .await
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,77 @@
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+/// Length of the keys of the addresses
+const LENGTH: usize = 16;
+
+/// The address where a user sends mail to make notes of it
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Inbox{
+    #[schema(example = "k3jd81hs0c9xq2lm@notisbak.example.com")]
+    pub address: String,
+    /// Address that also puts the label `ideas` to the notes
+    #[schema(example = "k3jd81hs0c9xq2lm+ideas@notisbak.example.com")]
+    pub example: String,
+    pub created_at: NaiveDateTime,
+}
+
+fn key() -> String{
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(LENGTH)
+        .map(char::from)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+impl Inbox{
+    fn from_row(row: PgRow, domain: &str) -> Inbox{
+        let key: String = row.get("key");
+        Inbox{
+            address: format!("{}@{}", key, domain),
+            example: format!("{}+ideas@{}", key, domain),
+            created_at: row.get("created_at"),
+        }
+    }
+
+    /// The inbox of the user, created the first time
+    pub async fn get(pool: &web::Data<PgPool>, user_id: i32, domain: &str) -> Result<Inbox, Error>{
+        query(r#"INSERT INTO inboxes (key, user_id, created_at) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE SET key = inboxes.key
+            RETURNING key, created_at"#)
+            .bind(key())
+            .bind(user_id)
+            .bind(Utc::now().naive_utc())
+            .map(|row: PgRow| Inbox::from_row(row, domain))
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// Give the user a new address, so the old one stops working
+    pub async fn renew(pool: &web::Data<PgPool>, user_id: i32, domain: &str) -> Result<Inbox, Error>{
+        query(r#"INSERT INTO inboxes (key, user_id, created_at) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id) DO UPDATE SET key = EXCLUDED.key, created_at = EXCLUDED.created_at
+            RETURNING key, created_at"#)
+            .bind(key())
+            .bind(user_id)
+            .bind(Utc::now().naive_utc())
+            .map(|row: PgRow| Inbox::from_row(row, domain))
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// The user of the inbox with the `key`
+    pub async fn user(pool: &web::Data<PgPool>, key: &str) -> Result<i32, Error>{
+        query(r#"SELECT i.user_id FROM inboxes i
+            INNER JOIN users u ON u.id = i.user_id
+            WHERE i.key = $1 AND NOT u.disabled"#)
+            .bind(key)
+            .map(|row: PgRow| row.get("user_id"))
+            .fetch_one(pool.get_ref())
+            .await
+    }
+}
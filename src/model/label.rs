@@ -3,7 +3,9 @@ use sqlx::{query, FromRow, Error, Row, postgres::{PgPool, PgRow}};
 use serde::{Serialize, Deserialize};
 use utoipa::ToSchema;
 
-#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
+use crate::events;
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct Label{
     #[schema(example = "1")]
     pub id: i32,
@@ -11,13 +13,6 @@ pub struct Label{
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct LabelWU{
-    pub id: i32,
-    pub name: String,
-    pub user_id: i32,
-}
-
 #[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct NewLabel{
     #[schema(example = "etiqueta 1")]
@@ -25,13 +20,13 @@ pub struct NewLabel{
 }
 
 impl Label{
-    pub async fn all(pool: web::Data<PgPool>, user_id: i32) -> Result<Vec<Label>, Error>{
+    pub async fn all(pool: web::Data<PgPool>, workspace_id: i32) -> Result<Vec<Label>, Error>{
         let sql = r#"SELECT id, name
         FROM labels l
-        WHERE user_id = $1
+        WHERE workspace_id = $1
         "#;
         query(sql)
-            .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Label{
                 id: row.get("id"),
                 name: row.get("name"),
@@ -40,14 +35,14 @@ impl Label{
             .await
     }
 
-    pub async fn get(pool: web::Data<PgPool>, id: i32, user_id: i32) -> Result<Label, Error>{
+    pub async fn get(pool: web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Label, Error>{
         let sql = r#"SELECT id, name
         FROM labels l
-        WHERE l.id = $1 AND user_id = $2
+        WHERE l.id = $1 AND workspace_id = $2
         "#;
         query(sql)
             .bind(id)
-            .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Label{
                 id: row.get("id"),
                 name: row.get("name"),
@@ -56,10 +51,10 @@ impl Label{
             .await
     }
 
-    pub async fn get_labels_for_note(pool: web::Data<PgPool>, note_id: i32, user_id: i32) -> Result<Vec<Label>, Error>{
-        query(r#"SELECT l.id, l.name FROM labels l INNER JOIN notes_labels nl ON nl.label_id = l.id AND note_id=$1 AND l.user_id = $2"#)
+    pub async fn get_labels_for_note(pool: web::Data<PgPool>, note_id: i32, workspace_id: i32) -> Result<Vec<Label>, Error>{
+        query(r#"SELECT l.id, l.name FROM labels l INNER JOIN notes_labels nl ON nl.label_id = l.id AND note_id=$1 AND l.workspace_id = $2"#)
             .bind(note_id)
-            .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Label{
                 id: row.get("id"),
                 name: row.get("name"),
@@ -68,39 +63,61 @@ impl Label{
             .await
     }
 
-    pub async fn new(pool: &web::Data<PgPool>, name: &str, user_id: i32) -> Result<Label, Error>{
-        query(r#"INSERT INTO labels (name, user_id) VALUES ($1, $2) RETURNING id, name;"#)
+    /// The labels of several notes at once, paired with the id of their note
+    pub async fn for_notes(pool: &web::Data<PgPool>, note_ids: &[i32], workspace_id: i32) -> Result<Vec<(i32, Label)>, Error>{
+        query(r#"SELECT nl.note_id, l.id, l.name FROM labels l INNER JOIN notes_labels nl ON nl.label_id = l.id WHERE nl.note_id = ANY($1) AND l.workspace_id = $2 ORDER BY l.name"#)
+            .bind(note_ids)
+            .bind(workspace_id)
+            .map(|row: PgRow| (row.get("note_id"), Label{
+                id: row.get("id"),
+                name: row.get("name"),
+            }))
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn new(pool: &web::Data<PgPool>, name: &str, workspace_id: i32, user_id: i32) -> Result<Label, Error>{
+        let label = query(r#"INSERT INTO labels (name, user_id, workspace_id) VALUES ($1, $2, $3) RETURNING id, name;"#)
             .bind(name)
             .bind(user_id)
+            .bind(workspace_id)
             .map(|row: PgRow| Label{
                 id: row.get("id"),
                 name: row.get("name"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "label.created", workspace_id, &label).await?;
+        Ok(label)
     }
 
-    pub async fn update(pool: web::Data<PgPool>, label: Label) -> Result<Label, Error>{
-        query(r#"UPDATE labels SET name = $2 WHERE id = $1 RETURNING id, name;"#)
+    pub async fn update(pool: web::Data<PgPool>, label: Label, workspace_id: i32) -> Result<Label, Error>{
+        let label = query(r#"UPDATE labels SET name = $2 WHERE id = $1 AND workspace_id = $3 RETURNING id, name;"#)
             .bind(label.id)
             .bind(label.name)
+            .bind(workspace_id)
             .map(|row: PgRow| Label{
                 id: row.get("id"),
                 name: row.get("name"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "label.updated", workspace_id, &label).await?;
+        Ok(label)
     }
 
-    pub async fn delete(pool: web::Data<PgPool>, id: i32) -> Result<Label, Error>{
-        query(r#"DELETE FROM labels WHERE id = $1 RETURNING id, name;"#)
+    pub async fn delete(pool: web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Label, Error>{
+        let label = query(r#"DELETE FROM labels WHERE id = $1 AND workspace_id = $2 RETURNING id, name;"#)
             .bind(id)
+            .bind(workspace_id)
             .map(|row: PgRow| Label{
                 id: row.get("id"),
                 name: row.get("name"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "label.deleted", workspace_id, &label).await?;
+        Ok(label)
     }
 }
 
@@ -1,8 +1,21 @@
+pub mod app_token;
+pub mod attachment;
 pub mod category;
 pub mod claims;
+pub mod dav;
+pub mod feed;
+pub mod import_job;
+pub mod inbox;
 pub mod label;
+pub mod nextcloud;
 pub mod note_category;
 pub mod note_label;
 pub mod note;
-pub mod user_label;
+pub mod note_item;
+pub mod note_link;
+pub mod recurrence;
+pub mod reminder;
+pub mod template;
 pub mod user;
+pub mod webhook;
+pub mod workspace;
@@ -0,0 +1,159 @@
+//! Notes as the Nextcloud Notes API v1 shows them. Favorites are the pinned
+//! notes and the category of a note is the first of its categories by name.
+use std::collections::HashMap;
+
+use actix_web::web;
+use chrono::DateTime;
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+
+use crate::importer::title_from;
+use crate::model::note::{self, Note, NewNote, NoteKind};
+use crate::model::note_category::NoteCategory;
+use crate::model::note_item::{self, NoteItem};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NextcloudNote{
+    pub id: i32,
+    pub etag: String,
+    /// Checklists can not be changed as markdown
+    pub readonly: bool,
+    pub content: String,
+    pub title: String,
+    pub category: String,
+    pub favorite: bool,
+    /// Unix time of the last change
+    pub modified: i64,
+    /// Of the note, to change it only if nobody did meanwhile
+    #[serde(skip)]
+    pub version: i32,
+}
+
+/// What a client sends to create or change a note. Missing fields are not
+/// changed.
+#[derive(Debug, Default, Deserialize)]
+pub struct NoteChanges{
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub category: Option<String>,
+    pub favorite: Option<bool>,
+    pub modified: Option<i64>,
+}
+
+/// The category of a note for Nextcloud, empty when it has none
+const CATEGORY: &str = r#"COALESCE((SELECT c.name FROM notes_categories nc
+    INNER JOIN categories c ON c.id = nc.category_id
+    WHERE nc.note_id = notes.id ORDER BY c.name LIMIT 1), '') AS category"#;
+
+impl NextcloudNote{
+    fn new(note: Note, category: String, version: i32, items: &[NoteItem]) -> Self{
+        let content = note_item::to_markdown(&note, items);
+        let modified = note.updated_at.and_utc().timestamp();
+        let etag = format!("{:x}", md5::compute(format!("{}\n{}\n{}\n{}\n{}\n{}",
+            note.id, note.title, content, category, note.pinned, modified)));
+        Self{
+            id: note.id,
+            etag,
+            readonly: note.kind == NoteKind::Checklist,
+            content,
+            title: note.title,
+            category,
+            favorite: note.pinned,
+            modified,
+            version,
+        }
+    }
+
+    fn from_row(row: PgRow, items: &[NoteItem]) -> Self{
+        let category = row.get("category");
+        let version = row.get("version");
+        NextcloudNote::new(Note::from_row(row), category, version, items)
+    }
+
+    /// The note without the `exclude`d fields. The id is always there.
+    pub fn to_value(&self, exclude: &[&str]) -> Value{
+        let mut value = json!(self);
+        if let Some(fields) = value.as_object_mut() {
+            fields.retain(|field, _| field == "id" || !exclude.contains(&field.as_str()));
+        }
+        value
+    }
+
+    /// The notes of the workspace but the trashed ones
+    pub async fn all(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Vec<NextcloudNote>, Error>{
+        let mut items: HashMap<i32, Vec<NoteItem>> = HashMap::new();
+        for item in NoteItem::all_in_workspace(pool, workspace_id).await? {
+            items.entry(item.note_id).or_default().push(item);
+        }
+        let sql = format!("SELECT {}, {} FROM notes WHERE workspace_id = $1 AND NOT trashed ORDER BY id", note::COLUMNS, CATEGORY);
+        query(&sql)
+            .bind(workspace_id)
+            .map(|row: PgRow| {
+                let id: i32 = row.get("id");
+                NextcloudNote::from_row(row, items.get(&id).map(Vec::as_slice).unwrap_or_default())
+            })
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn get(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<NextcloudNote, Error>{
+        let items = NoteItem::all(pool, id, workspace_id).await?;
+        let sql = format!("SELECT {}, {} FROM notes WHERE id = $1 AND workspace_id = $2 AND NOT trashed", note::COLUMNS, CATEGORY);
+        query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(|row: PgRow| NextcloudNote::from_row(row, &items))
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn create(pool: &web::Data<PgPool>, mut changes: NoteChanges, workspace_id: i32, user_id: i32) -> Result<NextcloudNote, Error>{
+        let content = changes.content.take().unwrap_or_default();
+        let title = match changes.title.take() {
+            Some(title) if !title.trim().is_empty() => title,
+            _ => title_from(&content, "New note"),
+        };
+        let note = Note::new(pool.clone(), NewNote{
+            title,
+            body: Some(content),
+            kind: None,
+            remind_at: None,
+            recurrence: None,
+        }, workspace_id, user_id).await?;
+        NextcloudNote::update(pool, note.id, changes, workspace_id, user_id, None).await
+    }
+
+    /// Change the note, only if it is still at `version` when there is one.
+    /// If it is not, it fails as if there were no note.
+    pub async fn update(pool: &web::Data<PgPool>, id: i32, changes: NoteChanges, workspace_id: i32, user_id: i32, version: Option<i32>) -> Result<NextcloudNote, Error>{
+        Note::update_if(pool.clone(), json!({
+            "id": id,
+            "title": changes.title,
+            "body": changes.content,
+            "pinned": changes.favorite,
+        }), workspace_id, false, version).await?;
+        if let Some(category) = changes.category {
+            NoteCategory::set_only(pool, id, category.trim(), workspace_id, user_id).await?;
+        }
+        if let Some(modified) = changes.modified.and_then(|modified| DateTime::from_timestamp(modified, 0)) {
+            query(r#"UPDATE notes SET updated_at = $1 WHERE id = $2 AND workspace_id = $3"#)
+                .bind(modified.naive_utc())
+                .bind(id)
+                .bind(workspace_id)
+                .execute(pool.get_ref())
+                .await?;
+        }
+        NextcloudNote::get(pool, id, workspace_id).await
+    }
+
+    /// Move the note to the trash, as clients expect it to be gone
+    pub async fn delete(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<(), Error>{
+        query(r#"UPDATE notes SET trashed = TRUE WHERE id = $1 AND workspace_id = $2 AND NOT trashed RETURNING id"#)
+            .bind(id)
+            .bind(workspace_id)
+            .fetch_one(pool.get_ref())
+            .await
+            .map(|_| ())
+    }
+}
@@ -1,42 +1,92 @@
-use std::convert::TryInto;
+use std::{fmt, str::FromStr};
 
 use actix_web::web;
 use chrono::{NaiveDateTime, Utc};
-use sqlx::{query, FromRow, Error, Row, postgres::{PgPool, PgRow, PgQueryResult}};
+use sqlx::{query, types::Json, FromRow, Error, Row, postgres::{PgConnection, PgPool, PgRow}};
 use serde::{Serialize, Deserialize};
-use crate::model::{label::Label, category::Category};
 use serde_json::Value;
 use utoipa::ToSchema;
 
+use crate::events;
+use crate::model::{category::Category, label::Label, note_link::NoteLink};
+
 //https://github.com/juhaku/utoipa
 
-#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
-pub struct Note{
-    pub id: i32,
-    #[schema(example = "Titulo")]
-    pub title: String,
-    #[schema(example = "Contenido")]
-    pub body: String,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteKind{
+    #[default]
+    Text,
+    Checklist,
 }
 
-#[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
-pub struct NoteWU{
+impl NoteKind{
+    pub fn as_str(&self) -> &'static str{
+        match self {
+            NoteKind::Text => "text",
+            NoteKind::Checklist => "checklist",
+        }
+    }
+}
+
+impl fmt::Display for NoteKind{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for NoteKind{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>{
+        match s {
+            "text" => Ok(NoteKind::Text),
+            "checklist" => Ok(NoteKind::Checklist),
+            _ => Err(format!("Unknown note kind {}", s)),
+        }
+    }
+}
+
+/// How many items of a checklist are checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Progress{
+    pub checked: i64,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Note{
     pub id: i32,
     #[schema(example = "Titulo")]
     pub title: String,
     #[schema(example = "Contenido")]
     pub body: String,
+    pub kind: NoteKind,
+    /// Only for checklists
+    pub progress: Option<Progress>,
+    pub pinned: bool,
+    pub archived: bool,
+    pub trashed: bool,
+    #[schema(example = "yellow")]
+    pub color: Option<String>,
+    pub remind_at: Option<NaiveDateTime>,
+    #[schema(example = "FREQ=WEEKLY;BYDAY=MO,TH")]
+    pub recurrence: Option<String>,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
-    pub user_id: i32,
+    /// Goes up with every change of the note
+    #[serde(default)]
+    pub version: i32,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
 pub struct NewNote{
     pub title: String,
     pub body: Option<String>,
+    pub kind: Option<NoteKind>,
+    pub remind_at: Option<NaiveDateTime>,
+    #[schema(example = "daily")]
+    pub recurrence: Option<String>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
@@ -44,179 +94,382 @@ pub struct UpdateNote{
     pub id: i32,
     pub title: String,
     pub body: Option<String>,
+    pub kind: Option<NoteKind>,
+    pub pinned: Option<bool>,
+    pub archived: Option<bool>,
+    pub trashed: Option<bool>,
+    pub color: Option<String>,
+    /// Only update the note if it is still at this version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
 }
 
-impl Note{
-    pub async fn all(pool: web::Data<PgPool>, user_id: i32) -> Result<Vec<Note>, Error>{
-        query(r#"SELECT id, title, body, created_at, updated_at FROM notes WHERE user_id = $1"#)
-            .bind(user_id)
-            .map(|row: PgRow| Note{
-                id: row.get("id"),
-                title: row.get("title"),
-                body: row.get("body"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            })
+/// Relations to embed in the notes, from `?include=labels,categories`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Include{
+    pub labels: bool,
+    pub categories: bool,
+}
+
+impl Include{
+    pub fn is_empty(&self) -> bool{
+        !self.labels && !self.categories
+    }
+
+    /// The relation as a JSON array aggregated in the same query, or NULL
+    fn column(wanted: bool, table: &str, link: &str, column: &str, name: &str) -> String{
+        match wanted {
+            true => format!(r#"COALESCE((SELECT json_agg(json_build_object('id', t.id, 'name', t.name) ORDER BY t.name)
+                FROM {table} t INNER JOIN {link} k ON k.{column} = t.id WHERE k.note_id = notes.id), '[]'::json) AS {name}"#,
+                table = table, link = link, column = column, name = name),
+            false => format!("NULL::json AS {}", name),
+        }
+    }
+
+    fn columns(&self) -> String{
+        format!("{}, {}",
+            Include::column(self.labels, "labels", "notes_labels", "label_id", "labels"),
+            Include::column(self.categories, "categories", "notes_categories", "category_id", "categories"))
+    }
+}
+
+impl fmt::Display for Include{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        let relations: Vec<&str> = [(self.labels, "labels"), (self.categories, "categories")]
+            .iter()
+            .filter(|(wanted, _)| *wanted)
+            .map(|(_, relation)| *relation)
+            .collect();
+        f.write_str(&relations.join(","))
+    }
+}
+
+impl FromStr for Include{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>{
+        let mut include = Include::default();
+        for relation in s.split(',').map(str::trim).filter(|relation| !relation.is_empty()) {
+            match relation {
+                "labels" => include.labels = true,
+                "categories" => include.categories = true,
+                _ => return Err(format!("Can not include {}", relation)),
+            }
+        }
+        Ok(include)
+    }
+}
+
+/// A note with the relations asked for embedded
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NoteDetail{
+    #[serde(flatten)]
+    pub note: Note,
+    /// Only with `include=labels`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<Label>>,
+    /// Only with `include=categories`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub categories: Option<Vec<Category>>,
+}
+
+impl NoteDetail{
+    fn from_row(row: PgRow) -> NoteDetail{
+        let labels: Option<Json<Vec<Label>>> = row.get("labels");
+        let categories: Option<Json<Vec<Category>>> = row.get("categories");
+        NoteDetail{
+            note: Note::from_row(row),
+            labels: labels.map(|labels| labels.0),
+            categories: categories.map(|categories| categories.0),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, workspace_id: i32, include: Include) -> Result<Vec<NoteDetail>, Error>{
+        let sql = format!("SELECT {}, {} FROM notes WHERE workspace_id = $1", COLUMNS, include.columns());
+        query(&sql)
+            .bind(workspace_id)
+            .map(NoteDetail::from_row)
             .fetch_all(pool.get_ref())
             .await
     }
 
-    pub async fn get(pool: web::Data<PgPool>, id: i32, user_id: i32) -> Result<Note, Error>{
-        query(r#"SELECT id, title, body, created_at, updated_at FROM notes WHERE id = $1 AND user_id = $2"#)
-            .bind(id)
-            .bind(user_id)
-            .map(|row: PgRow| Note{
-                id: row.get("id"),
-                title: row.get("title"),
-                body: row.get("body"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            })
-            .fetch_one(pool.get_ref())
+    /// Up to `limit` notes by id, those after the note `after`
+    pub async fn page(pool: &web::Data<PgPool>, workspace_id: i32, include: Include, after: Option<i32>, limit: i64) -> Result<Vec<NoteDetail>, Error>{
+        let sql = format!("SELECT {}, {} FROM notes WHERE workspace_id = $1 AND ($2::INT IS NULL OR id > $2) ORDER BY id LIMIT $3",
+            COLUMNS, include.columns());
+        query(&sql)
+            .bind(workspace_id)
+            .bind(after)
+            .bind(limit)
+            .map(NoteDetail::from_row)
+            .fetch_all(pool.get_ref())
             .await
     }
 
-    pub async fn new(pool: web::Data<PgPool>, note: NewNote, user_id: i32) -> Result<Note, Error>{
-        let title = note.title;
-        let body = note.body.unwrap_or("".to_string());
-        let created_at = Utc::now().naive_utc();
-        let updated_at = Utc::now().naive_utc();
-        query(r#"INSERT INTO notes (title, body, created_at, updated_at, user_id) VALUES ($1, $2, $3, $4, $5) RETURNING id, title, body, created_at, updated_at;"#,)
-            .bind(title)
-            .bind(body)
-            .bind(created_at)
-            .bind(updated_at)
-            .bind(user_id)
-            .map(|row: PgRow| Note{
-                id: row.get("id"),
-                title: row.get("title"),
-                body: row.get("body"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            })
+    pub async fn get(pool: &web::Data<PgPool>, id: i32, workspace_id: i32, include: Include) -> Result<NoteDetail, Error>{
+        let sql = format!("SELECT {}, {} FROM notes WHERE id = $1 AND workspace_id = $2", COLUMNS, include.columns());
+        query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(NoteDetail::from_row)
             .fetch_one(pool.get_ref())
             .await
     }
+}
 
-    pub async fn update(pool: web::Data<PgPool>, content: Value, user_id: i32) -> Result<Note, Error>{
-        let updated_at = Utc::now().naive_utc();
-        let id: i32 = content.get("id").as_ref().unwrap().as_i64().unwrap().try_into().unwrap();
-        let title_option = content.get("title");
-        let body_option = content.get("body");
-        let mut sql = query("");
-        if title_option != None && body_option != None{
-            sql = query(r#"UPDATE notes SET title = $1, body = $2, updated_at = $3 WHERE id = $4 AND user_id = $5 RETURNING id, title, body, created_at, updated_at;"#)
-                .bind(title_option.unwrap().as_str().unwrap())
-                .bind(body_option.unwrap().as_str().unwrap());
-        }else if title_option != None{ 
-            sql = query(r#"UPDATE notes SET title = $1, updated_at = $2 WHERE id = $3 AND user_id = $4 RETURNING id, title, body, created_at, updated_at;"#)
-                .bind(title_option.unwrap().as_str().unwrap())
-        }else if body_option != None{
-            sql = query(r#"UPDATE notes SET body = $1, updated_at = $2 WHERE id = $3 AND user_id = $4 RETURNING id, title, body, created_at, updated_at;"#)
-                .bind(body_option.unwrap().as_str().unwrap())
+/// Columns of a note, usable both in SELECT and RETURNING clauses on `notes`
+pub const COLUMNS: &str = r#"notes.id, notes.title, notes.body, notes.kind,
+    notes.pinned, notes.archived, notes.trashed, notes.color,
+    notes.remind_at, notes.recurrence, notes.created_at, notes.updated_at, notes.version,
+    (SELECT COUNT(*) FROM note_items i WHERE i.note_id = notes.id AND i.checked) AS checked,
+    (SELECT COUNT(*) FROM note_items i WHERE i.note_id = notes.id) AS total"#;
+
+/// A page of the notes of a label or a category, by id: at most `size` of
+/// them after the note `after`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LinkedPage{
+    /// The id of the label or the category
+    pub link_id: i32,
+    pub after: i32,
+    pub size: i64,
+}
+
+/// The id of the note in the fields of an update, when it is a valid one
+pub fn note_id(content: &Value) -> Option<i32>{
+    content.get("id")
+        .and_then(Value::as_i64)
+        .and_then(|id| i32::try_from(id).ok())
+}
+
+impl Note{
+    pub fn from_row(row: PgRow) -> Note{
+        let kind = row.get::<String, _>("kind").parse().unwrap_or_default();
+        let progress = match kind {
+            NoteKind::Checklist => Some(Progress{
+                checked: row.get("checked"),
+                total: row.get("total"),
+            }),
+            NoteKind::Text => None,
+        };
+        Note{
+            id: row.get("id"),
+            title: row.get("title"),
+            body: row.get("body"),
+            kind,
+            progress,
+            pinned: row.get("pinned"),
+            archived: row.get("archived"),
+            trashed: row.get("trashed"),
+            color: row.get("color"),
+            remind_at: row.get("remind_at"),
+            recurrence: row.get("recurrence"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+            version: row.get("version"),
         }
-        sql.bind(updated_at)
-            .bind(id)
-            .bind(user_id)
-            .map(|row: PgRow| Note{
-                id: row.get("id"),
-                title: row.get("title"),
-                body: row.get("body"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            })
-            .fetch_one(pool.get_ref())
+    }
+
+    pub async fn all(pool: web::Data<PgPool>, workspace_id: i32) -> Result<Vec<Note>, Error>{
+        let sql = format!("SELECT {} FROM notes WHERE workspace_id = $1", COLUMNS);
+        query(&sql)
+            .bind(workspace_id)
+            .map(Note::from_row)
+            .fetch_all(pool.get_ref())
             .await
     }
 
-    pub async fn delete(pool: web::Data<PgPool>, id: i32, user_id: i32) -> Result<Note, Error>{
-        query(r#"DELETE FROM notes WHERE id = $1 AND user_id = $2 RETURNING id, title, body, created_at, updated_at;"#)
+    pub async fn get(pool: web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Note, Error>{
+        let sql = format!("SELECT {} FROM notes WHERE id = $1 AND workspace_id = $2", COLUMNS);
+        query(&sql)
             .bind(id)
-            .bind(user_id)
-            .map(|row: PgRow| Note{
-                id: row.get("id"),
-                title: row.get("title"),
-                body: row.get("body"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-            })
+            .bind(workspace_id)
+            .map(Note::from_row)
             .fetch_one(pool.get_ref())
             .await
     }
 
-    pub async fn set_label(self, pool: web::Data<PgPool>, label_id: i32) -> Result<PgQueryResult, Error>{
-        query("INSERT INTO notes_labels (note_id, label_id) VALUES (?, ?);")
-            .bind(self.id)
-            .bind(label_id)
-            .execute(pool.get_ref())
-            .await
+    /// A page of notes by id, after `after` and before `before`. With
+    /// `from_last` the page is taken from the end, still in id order.
+    pub async fn page(pool: &web::Data<PgPool>, workspace_id: i32, after: Option<i32>, before: Option<i32>, limit: i64, from_last: bool) -> Result<Vec<Note>, Error>{
+        let sql = format!("SELECT {} FROM notes WHERE workspace_id = $1 AND ($2::INT IS NULL OR id > $2) AND ($3::INT IS NULL OR id < $3) ORDER BY id {} LIMIT $4",
+            COLUMNS, if from_last { "DESC" } else { "ASC" });
+        let mut notes = query(&sql)
+            .bind(workspace_id)
+            .bind(after)
+            .bind(before)
+            .bind(limit)
+            .map(Note::from_row)
+            .fetch_all(pool.get_ref())
+            .await?;
+        if from_last {
+            notes.reverse();
+        }
+        Ok(notes)
     }
 
-    pub async fn unset_label(self, pool: web::Data<PgPool>, label_id: i32) -> Result<PgQueryResult, Error>{
-        query("DELETE FROM notes_labels WHERE node_id = ?, label_id = ?")
-            .bind(self.id)
-            .bind(label_id)
-            .execute(pool.get_ref())
-            .await
+    /// Pages of the notes with labels, paired with the page they are in
+    pub async fn for_labels(pool: &web::Data<PgPool>, pages: &[LinkedPage], workspace_id: i32) -> Result<Vec<(LinkedPage, Note)>, Error>{
+        Note::linked(pool, "notes_labels", "label_id", pages, workspace_id).await
     }
 
-    pub async fn get_labels(self, pool: web::Data<PgPool>) -> Result<Vec<Label>, Error>{
-        query(r#"SELECT l.id, l.name FROM labels l INNER JOIN notes_labels nl ON l.id = nl.label_id AND nl.note_id = $1"#)
-            .bind(self.id)
-            .map(|row: PgRow| Label{
-                id: row.get("id"),
-                name: row.get("name"),
-            })
+    /// Pages of the notes in categories, paired with the page they are in
+    pub async fn for_categories(pool: &web::Data<PgPool>, pages: &[LinkedPage], workspace_id: i32) -> Result<Vec<(LinkedPage, Note)>, Error>{
+        Note::linked(pool, "notes_categories", "category_id", pages, workspace_id).await
+    }
+
+    async fn linked(pool: &web::Data<PgPool>, table: &str, column: &str, pages: &[LinkedPage], workspace_id: i32) -> Result<Vec<(LinkedPage, Note)>, Error>{
+        let sql = format!(r#"SELECT page.link_id, page.after AS page_after, page.size AS page_size, notes.*
+            FROM unnest($1::INTEGER[], $2::INTEGER[], $3::BIGINT[]) AS page(link_id, after, size)
+            CROSS JOIN LATERAL (
+                SELECT {} FROM notes INNER JOIN {table} link ON link.note_id = notes.id
+                WHERE link.{column} = page.link_id AND notes.workspace_id = $4 AND notes.id > page.after
+                ORDER BY notes.id LIMIT page.size) notes
+            ORDER BY notes.id"#,
+            COLUMNS, column = column, table = table);
+        query(&sql)
+            .bind(pages.iter().map(|page| page.link_id).collect::<Vec<i32>>())
+            .bind(pages.iter().map(|page| page.after).collect::<Vec<i32>>())
+            .bind(pages.iter().map(|page| page.size).collect::<Vec<i64>>())
+            .bind(workspace_id)
+            .map(|row: PgRow| (LinkedPage{
+                link_id: row.get("link_id"),
+                after: row.get("page_after"),
+                size: row.get("page_size"),
+            }, Note::from_row(row)))
             .fetch_all(pool.get_ref())
             .await
     }
 
-    pub async fn get_label(self, pool: web::Data<PgPool>, label_id: i32) -> Result<Label, Error>{
-        query(r#"SELECT id, name FROM labels WHERE id = ?"#)
-            .bind(label_id)
-            .map(|row: PgRow| Label{
-                id: row.get("id"),
-                name: row.get("name"),
-            })
-            .fetch_one(pool.get_ref())
-            .await
+    pub async fn new(pool: web::Data<PgPool>, note: NewNote, workspace_id: i32, user_id: i32) -> Result<Note, Error>{
+        let note = Note::insert(&mut *pool.acquire().await?, note, workspace_id, user_id).await?;
+        Note::created(&pool, &note, workspace_id).await?;
+        Ok(note)
     }
 
-    pub async fn set_category(self, pool: web::Data<PgPool>, category_id: i32) -> Result<PgQueryResult, Error>{
-        query("INSERT INTO notes_categories (note_id, category_id) VALUES (?, ?);")
-            .bind(self.id)
-            .bind(category_id)
-            .execute(pool.get_ref())
+    /// Only insert the note, so it can be part of a transaction. Once it is
+    /// committed, [`Note::created`] completes it.
+    pub(crate) async fn insert(conn: &mut PgConnection, note: NewNote, workspace_id: i32, user_id: i32) -> Result<Note, Error>{
+        let title = note.title;
+        let body = note.body.unwrap_or_default();
+        let created_at = Utc::now().naive_utc();
+        let updated_at = Utc::now().naive_utc();
+        let sql = format!("INSERT INTO notes (title, body, kind, remind_at, recurrence, created_at, updated_at, user_id, workspace_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING {};", COLUMNS);
+        query(&sql)
+            .bind(title)
+            .bind(body)
+            .bind(note.kind.unwrap_or_default().as_str())
+            .bind(note.remind_at)
+            .bind(note.recurrence)
+            .bind(created_at)
+            .bind(updated_at)
+            .bind(user_id)
+            .bind(workspace_id)
+            .map(Note::from_row)
+            .fetch_one(conn)
             .await
     }
 
-    pub async fn unset_category(self, pool: web::Data<PgPool>, category_id: i32) -> Result<PgQueryResult, Error>{
-        query("DELETE FROM notes_categories WHERE node_id = ?, category_id = ?")
-            .bind(self.id)
-            .bind(category_id)
-            .execute(pool.get_ref())
-            .await
+    /// Link the inserted note and publish it
+    pub(crate) async fn created(pool: &web::Data<PgPool>, note: &Note, workspace_id: i32) -> Result<(), Error>{
+        NoteLink::refresh(pool, note.id, &note.body, workspace_id).await?;
+        NoteLink::relink(pool, note.id, &note.title, workspace_id).await?;
+        events::notify(pool.get_ref(), "note.created", workspace_id, note).await
     }
 
-    pub async fn get_categories(self, pool: web::Data<PgPool>) -> Result<Vec<Category>, Error>{
-        query(r#"SELECT c.id, c.name FROM categories l INNER JOIN notes_categories nc ON c.id = nc.category_id AND nc.category_id = ?"#)
-            .bind(self.id)
-            .map(|row: PgRow| Category{
-                id: row.get("id"),
-                name: row.get("name"),
-            })
-            .fetch_all(pool.get_ref())
-            .await
+    /// Update the fields present in `content`.
+    ///
+    /// When the title changes and `rewrite_links` is set, the `[[title]]`
+    /// links of the notes pointing to this one are rewritten with the new
+    /// title. Without a valid `id` there is nothing to update, and it fails.
+    pub async fn update(pool: web::Data<PgPool>, content: Value, workspace_id: i32, rewrite_links: bool) -> Result<Note, Error>{
+        Note::update_if(pool, content, workspace_id, rewrite_links, None).await
     }
 
-    pub async fn get_category(self, pool: web::Data<PgPool>, category_id: i32) -> Result<Category, Error>{
-        query(r#"SELECT id, name FROM categories WHERE id = ?"#)
-            .bind(category_id)
-            .map(|row: PgRow| Category{
-                id: row.get("id"),
-                name: row.get("name"),
-            })
+    /// Like [`Note::update`], but only if the note is still at `version`,
+    /// when there is one, so the changes made meanwhile are not lost. If it
+    /// is not, it fails as if there were no note.
+    pub async fn update_if(pool: web::Data<PgPool>, content: Value, workspace_id: i32, rewrite_links: bool, version: Option<i32>) -> Result<Note, Error>{
+        let updated_at = Utc::now().naive_utc();
+        let id = note_id(&content)
+            .ok_or_else(|| Error::Decode("The id of the note is missing or invalid".into()))?;
+        let title = content.get("title").and_then(|title| title.as_str());
+        let body = content.get("body").and_then(|body| body.as_str());
+        let kind = content.get("kind").and_then(|kind| kind.as_str());
+        let pinned = content.get("pinned").and_then(|pinned| pinned.as_bool());
+        let archived = content.get("archived").and_then(|archived| archived.as_bool());
+        let trashed = content.get("trashed").and_then(|trashed| trashed.as_bool());
+        let color = content.get("color").and_then(|color| color.as_str());
+        let previous = Note::get(pool.clone(), id, workspace_id).await?;
+        let sql = format!("UPDATE notes SET title = COALESCE($1, title), body = COALESCE($2, body), kind = COALESCE($3, kind), pinned = COALESCE($4, pinned), archived = COALESCE($5, archived), trashed = COALESCE($6, trashed), color = COALESCE($7, color), updated_at = $8 WHERE id = $9 AND workspace_id = $10 AND ($11::INTEGER IS NULL OR version = $11) RETURNING {};", COLUMNS);
+        let note = query(&sql)
+            .bind(title)
+            .bind(body)
+            .bind(kind)
+            .bind(pinned)
+            .bind(archived)
+            .bind(trashed)
+            .bind(color)
+            .bind(updated_at)
+            .bind(id)
+            .bind(workspace_id)
+            .bind(version)
+            .map(Note::from_row)
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        if body.is_some() {
+            NoteLink::refresh(&pool, note.id, &note.body, workspace_id).await?;
+        }
+        if note.title != previous.title {
+            NoteLink::renamed(&pool, note.id, &previous.title, &note.title, workspace_id, rewrite_links).await?;
+        }
+        events::notify(pool.get_ref(), "note.updated", workspace_id, &note).await?;
+        Ok(note)
+    }
+
+    /// Replace the body of the note `id`, like [`Note::update`] does
+    pub(crate) async fn set_body(pool: &web::Data<PgPool>, id: i32, body: &str, workspace_id: i32) -> Result<Note, Error>{
+        let sql = format!("UPDATE notes SET body = $1, updated_at = $2 WHERE id = $3 AND workspace_id = $4 RETURNING {};", COLUMNS);
+        let note = query(&sql)
+            .bind(body)
+            .bind(Utc::now().naive_utc())
+            .bind(id)
+            .bind(workspace_id)
+            .map(Note::from_row)
+            .fetch_one(pool.get_ref())
+            .await?;
+        NoteLink::refresh(pool, note.id, &note.body, workspace_id).await?;
+        events::notify(pool.get_ref(), "note.updated", workspace_id, &note).await?;
+        Ok(note)
+    }
+
+    pub async fn delete(pool: web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Note, Error>{
+        let sql = format!("DELETE FROM notes WHERE id = $1 AND workspace_id = $2 RETURNING {};", COLUMNS);
+        let note = query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(Note::from_row)
+            .fetch_one(pool.get_ref())
+            .await?;
+        events::notify(pool.get_ref(), "note.deleted", workspace_id, &note).await?;
+        Ok(note)
     }
 }
+
+#[test]
+fn test_include() {
+    assert!("".parse::<Include>().unwrap().is_empty());
+    assert_eq!("labels, categories".parse::<Include>(), Ok(Include{labels: true, categories: true}));
+    assert_eq!("categories".parse::<Include>(), Ok(Include{labels: false, categories: true}));
+    assert!("labels,items".parse::<Include>().is_err());
+    assert_eq!(Include{labels: true, categories: true}.to_string(), "labels,categories");
+}
+
+#[test]
+fn test_note_id() {
+    use serde_json::json;
+    assert_eq!(note_id(&json!({"id": 7, "title": "Nota"})), Some(7));
+    assert_eq!(note_id(&json!({"title": "Nota"})), None);
+    assert_eq!(note_id(&json!({"id": "7"})), None);
+    assert_eq!(note_id(&json!({"id": 4_294_967_296_i64})), None);
+    assert_eq!(note_id(&json!([7])), None);
+}
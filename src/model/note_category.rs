@@ -1,8 +1,10 @@
 use actix_web::web;
-use sqlx::{query, FromRow, Error, Row, postgres::{PgPool, PgRow}};
+use sqlx::{query, FromRow, Error, Row, postgres::{PgConnection, PgPool, PgRow}};
 use serde::{Serialize, Deserialize};
 use utoipa::ToSchema;
 
+use crate::events;
+
 #[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NoteCategory{
@@ -12,63 +14,81 @@ pub struct NoteCategory{
 }
 
 impl NoteCategory{
-    pub async fn all(pool: web::Data<PgPool>) -> Result<Vec<NoteCategory>, Error>{
-        query(r#"SELECT id, note_id, category_id FROM notes_categories"#)
-            .map(|row: PgRow| NoteCategory{
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                category_id: row.get("category_id"),
-            })
-            .fetch_all(pool.get_ref())
-            .await
-    }
-
-    pub async fn get(pool: web::Data<PgPool>, id: i32) -> Result<NoteCategory, Error>{
-        query(r#"SELECT id, note_id, category_id FROM notes_categories WHERE id = ?"#)
-            .bind(id)
-            .map(|row: PgRow| NoteCategory{
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                category_id: row.get("category_id"),
-            })
-            .fetch_one(pool.get_ref())
-            .await
+    pub async fn new(pool: web::Data<PgPool>, note_id: i32, category_id: i32, workspace_id: i32) -> Result<NoteCategory, Error>{
+        let note_category = NoteCategory::insert(&mut *pool.acquire().await?, note_id, category_id, workspace_id).await?;
+        events::notify(pool.get_ref(), "category.assigned", workspace_id, &note_category).await?;
+        Ok(note_category)
     }
 
-    pub async fn get_last_inserted(pool: web::Data<PgPool>) -> Result<NoteCategory, Error>{
-        query(r#"SELECT id, note_id, category_id FROM notes_categories WHERE id=(SELECT CURRVAL(PG_GET_SERIAL_SEQUENCE('notes_categories', 'id')))"#)
-            .map(|row: PgRow| NoteCategory{
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                category_id: row.get("category_id"),
-            })
-            .fetch_one(pool.get_ref())
-            .await
-    }
-
-    pub async fn new(pool: web::Data<PgPool>, note_id: i32, category_id: i32) -> Result<NoteCategory, Error>{
-        query(r#"INSERT INTO notes_categories (note_id, category_id) VALUES ($1, $2) RETURNING id, note_id, category_id;"#)
+    /// Only insert the assignment, so it can be part of a transaction
+    pub(crate) async fn insert(conn: &mut PgConnection, note_id: i32, category_id: i32, workspace_id: i32) -> Result<NoteCategory, Error>{
+        query(r#"INSERT INTO notes_categories (note_id, category_id) SELECT n.id, c.id FROM notes n, categories c WHERE n.id = $1 AND c.id = $2 AND n.workspace_id = $3 AND c.workspace_id = $3 RETURNING id, note_id, category_id;"#)
             .bind(note_id)
             .bind(category_id)
+            .bind(workspace_id)
             .map(|row: PgRow| NoteCategory{
                 id: row.get("id"),
                 note_id: row.get("note_id"),
                 category_id: row.get("category_id"),
             })
-            .fetch_one(pool.get_ref())
+            .fetch_one(conn)
             .await
     }
-    pub async fn delete(pool: web::Data<PgPool>, note_id: i32, category_id: i32) -> Result<NoteCategory, Error>{
-        query(r#"DELETE FROM notes_categories WHERE note_id = $1 AND category_id = $2 RETURNING id, note_id, category_id;"#)
+
+    pub async fn delete(pool: web::Data<PgPool>, note_id: i32, category_id: i32, workspace_id: i32) -> Result<NoteCategory, Error>{
+        let note_category = query(r#"DELETE FROM notes_categories WHERE note_id = $1 AND category_id = $2 AND note_id IN (SELECT id FROM notes WHERE workspace_id = $3) RETURNING id, note_id, category_id;"#)
             .bind(note_id)
             .bind(category_id)
+            .bind(workspace_id)
             .map(|row: PgRow| NoteCategory{
                 id: row.get("id"),
                 note_id: row.get("note_id"),
                 category_id: row.get("category_id"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "category.unassigned", workspace_id, &note_category).await?;
+        Ok(note_category)
     }
-}
 
+    /// Leave the note only in the category `name`, created when missing, or in
+    /// none if it is empty
+    pub async fn set_only(pool: &web::Data<PgPool>, id: i32, name: &str, workspace_id: i32, user_id: i32) -> Result<(), Error>{
+        let from_row = |row: PgRow| NoteCategory{
+            id: row.get("id"),
+            note_id: row.get("note_id"),
+            category_id: row.get("category_id"),
+        };
+        let mut tx = pool.begin().await?;
+        let removed = query(r#"DELETE FROM notes_categories WHERE note_id = $1 RETURNING id, note_id, category_id"#)
+            .bind(id)
+            .map(from_row)
+            .fetch_all(&mut tx)
+            .await?;
+        let mut assigned = None;
+        if !name.is_empty() {
+            query(r#"INSERT INTO categories (name, user_id, workspace_id) VALUES ($1, $2, $3) ON CONFLICT (name, workspace_id) DO NOTHING"#)
+                .bind(name)
+                .bind(user_id)
+                .bind(workspace_id)
+                .execute(&mut tx)
+                .await?;
+            assigned = query(r#"INSERT INTO notes_categories (note_id, category_id) SELECT $1, id FROM categories WHERE name = $2 AND workspace_id = $3 RETURNING id, note_id, category_id"#)
+                .bind(id)
+                .bind(name)
+                .bind(workspace_id)
+                .map(from_row)
+                .fetch_optional(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        let kept = assigned.as_ref().map(|note_category| note_category.category_id);
+        for note_category in removed.iter().filter(|note_category| Some(note_category.category_id) != kept) {
+            events::notify(pool.get_ref(), "category.unassigned", workspace_id, note_category).await?;
+        }
+        if let Some(note_category) = assigned.filter(|assigned| !removed.iter().any(|removed| removed.category_id == assigned.category_id)) {
+            events::notify(pool.get_ref(), "category.assigned", workspace_id, &note_category).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::model::note::{Note, NoteKind};
+
+/// An item of a checklist note
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NoteItem{
+    pub id: i32,
+    pub note_id: i32,
+    #[schema(example = "Comprar pan")]
+    pub text: String,
+    pub checked: bool,
+    pub position: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewNoteItem{
+    #[schema(example = "Comprar pan")]
+    pub text: String,
+    pub checked: Option<bool>,
+    /// Where to insert the item, at the end by default
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateNoteItem{
+    pub id: i32,
+    #[schema(example = "Comprar pan")]
+    pub text: String,
+}
+
+/// The ids of the items of a checklist in their new order
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ItemsOrder{
+    #[schema(example = json!([3, 1, 2]))]
+    pub ids: Vec<i32>,
+}
+
+const COLUMNS: &str = "id, note_id, text, checked, position, created_at, updated_at";
+
+/// Only items of checklists in the workspace can be read or changed
+const IN_WORKSPACE: &str = "note_id IN (SELECT id FROM notes WHERE id = $1 AND workspace_id = $2 AND kind = 'checklist')";
+
+impl NoteItem{
+    fn from_row(row: PgRow) -> NoteItem{
+        NoteItem{
+            id: row.get("id"),
+            note_id: row.get("note_id"),
+            text: row.get("text"),
+            checked: row.get("checked"),
+            position: row.get("position"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32) -> Result<Vec<NoteItem>, Error>{
+        let sql = format!("SELECT {} FROM note_items WHERE {} ORDER BY position, id", COLUMNS, IN_WORKSPACE);
+        query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .map(NoteItem::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    /// Items of every checklist in the workspace, in order
+    pub async fn all_in_workspace(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Vec<NoteItem>, Error>{
+        let sql = format!("SELECT {} FROM note_items WHERE note_id IN (SELECT id FROM notes WHERE workspace_id = $1 AND kind = 'checklist') ORDER BY note_id, position, id", COLUMNS);
+        query(&sql)
+            .bind(workspace_id)
+            .map(NoteItem::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    /// The items of the notes, in their order
+    pub async fn for_notes(pool: &web::Data<PgPool>, note_ids: &[i32], workspace_id: i32) -> Result<Vec<NoteItem>, Error>{
+        let sql = format!("SELECT {} FROM note_items WHERE note_id IN (SELECT id FROM notes WHERE id = ANY($1) AND workspace_id = $2 AND kind = 'checklist') ORDER BY note_id, position, id", COLUMNS);
+        query(&sql)
+            .bind(note_ids)
+            .bind(workspace_id)
+            .map(NoteItem::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn new(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32, item: NewNoteItem) -> Result<NoteItem, Error>{
+        let created_at = Utc::now().naive_utc();
+        let updated_at = Utc::now().naive_utc();
+        let mut tx = pool.begin().await?;
+        let last: i32 = query(&format!("SELECT COALESCE(MAX(position) + 1, 0) AS last FROM note_items WHERE {}", IN_WORKSPACE))
+            .bind(note_id)
+            .bind(workspace_id)
+            .map(|row: PgRow| row.get("last"))
+            .fetch_one(&mut tx)
+            .await?;
+        let position = item.position.unwrap_or(last).clamp(0, last);
+        query(r#"UPDATE note_items SET position = position + 1 WHERE note_id = $1 AND position >= $2"#)
+            .bind(note_id)
+            .bind(position)
+            .execute(&mut tx)
+            .await?;
+        let sql = format!("INSERT INTO note_items (note_id, text, checked, position, created_at, updated_at) SELECT id, $3, $4, $5, $6, $7 FROM notes WHERE id = $1 AND workspace_id = $2 AND kind = 'checklist' RETURNING {}", COLUMNS);
+        let item = query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .bind(item.text)
+            .bind(item.checked.unwrap_or(false))
+            .bind(position)
+            .bind(created_at)
+            .bind(updated_at)
+            .map(NoteItem::from_row)
+            .fetch_one(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(item)
+    }
+
+    pub async fn update(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32, item: UpdateNoteItem) -> Result<NoteItem, Error>{
+        let sql = format!("UPDATE note_items SET text = $3, updated_at = $4 WHERE {} AND id = $5 RETURNING {}", IN_WORKSPACE, COLUMNS);
+        query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .bind(item.text)
+            .bind(Utc::now().naive_utc())
+            .bind(item.id)
+            .map(NoteItem::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn set_checked(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32, id: i32, checked: bool) -> Result<NoteItem, Error>{
+        let sql = format!("UPDATE note_items SET checked = $3, updated_at = $4 WHERE {} AND id = $5 RETURNING {}", IN_WORKSPACE, COLUMNS);
+        query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .bind(checked)
+            .bind(Utc::now().naive_utc())
+            .bind(id)
+            .map(NoteItem::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// Put the items in the order of `ids`. Items not listed keep their
+    /// relative order after the listed ones.
+    pub async fn reorder(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32, ids: &[i32]) -> Result<Vec<NoteItem>, Error>{
+        let sql = format!(r#"UPDATE note_items i SET position = o.position - 1
+            FROM (SELECT id, ROW_NUMBER() OVER (ORDER BY COALESCE(array_position($3, id), 2147483647), position, id) AS position
+                FROM note_items WHERE {}) o
+            WHERE i.id = o.id"#, IN_WORKSPACE);
+        query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .bind(ids)
+            .execute(pool.get_ref())
+            .await?;
+        NoteItem::all(pool, note_id, workspace_id).await
+    }
+
+    pub async fn delete(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32, id: i32) -> Result<NoteItem, Error>{
+        let sql = format!("DELETE FROM note_items WHERE {} AND id = $3 RETURNING {}", IN_WORKSPACE, COLUMNS);
+        let item = query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .bind(id)
+            .map(NoteItem::from_row)
+            .fetch_one(pool.get_ref())
+            .await?;
+        query(r#"UPDATE note_items SET position = position - 1 WHERE note_id = $1 AND position > $2"#)
+            .bind(note_id)
+            .bind(item.position)
+            .execute(pool.get_ref())
+            .await?;
+        Ok(item)
+    }
+}
+
+/// GFM task list with the items of a checklist
+pub fn to_task_list(items: &[NoteItem]) -> String{
+    items.iter()
+        .map(|item| format!("- [{}] {}", if item.checked { "x" } else { " " },
+            item.text.replace('\n', " ")))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The note as markdown text. Checklists append their items as a task list.
+pub fn to_markdown(note: &Note, items: &[NoteItem]) -> String{
+    match note.kind {
+        NoteKind::Checklist if note.body.trim().is_empty() => to_task_list(items),
+        NoteKind::Checklist => format!("{}\n\n{}", note.body.trim_end(), to_task_list(items)),
+        NoteKind::Text => note.body.clone(),
+    }
+}
+
+impl Note{
+    /// Render the note as markdown, fetching the items of checklists
+    pub async fn to_markdown(&self, pool: &web::Data<PgPool>, workspace_id: i32) -> Result<String, Error>{
+        let items = match self.kind {
+            NoteKind::Checklist => NoteItem::all(pool, self.id, workspace_id).await?,
+            NoteKind::Text => Vec::new(),
+        };
+        Ok(to_markdown(self, &items))
+    }
+}
+
+#[test]
+fn test_to_task_list() {
+    let item = |text: &str, checked: bool| NoteItem{
+        id: 1,
+        note_id: 1,
+        text: text.to_string(),
+        checked,
+        position: 0,
+        created_at: Utc::now().naive_utc(),
+        updated_at: Utc::now().naive_utc(),
+    };
+    let items = vec![item("pan", true), item("leche\nentera", false)];
+    assert_eq!(to_task_list(&items), "- [x] pan\n- [ ] leche entera");
+}
@@ -1,8 +1,10 @@
 use actix_web::web;
-use sqlx::{query, FromRow, Error, Row, postgres::{PgPool, PgRow}};
+use sqlx::{query, FromRow, Error, Row, postgres::{PgConnection, PgPool, PgRow}};
 use serde::{Serialize, Deserialize};
 use utoipa::ToSchema;
 
+use crate::events;
+
 #[derive(Debug, FromRow, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct NoteLabel{
@@ -12,78 +14,40 @@ pub struct NoteLabel{
 }
 
 impl NoteLabel{
-    pub async fn all(pool: web::Data<PgPool>) -> Result<Vec<NoteLabel>, Error>{
-        query(r#"SELECT id, note_id, label_id FROM notes_labels"#)
-            .map(|row: PgRow| NoteLabel{
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                label_id: row.get("label_id"),
-            })
-            .fetch_all(pool.get_ref())
-            .await
+    pub async fn new(pool: web::Data<PgPool>, note_id: i32, label_id: i32, workspace_id: i32) -> Result<NoteLabel, Error>{
+        let note_label = NoteLabel::insert(&mut *pool.acquire().await?, note_id, label_id, workspace_id).await?;
+        events::notify(pool.get_ref(), "label.assigned", workspace_id, &note_label).await?;
+        Ok(note_label)
     }
 
-    pub async fn get(pool: web::Data<PgPool>, id: i32) -> Result<NoteLabel, Error>{
-        query(r#"SELECT id, note_id, label_id FROM notes_labels WHERE id = ?"#)
-            .bind(id)
-            .map(|row: PgRow| NoteLabel{
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                label_id: row.get("label_id"),
-            })
-            .fetch_one(pool.get_ref())
-            .await
-    }
-
-    pub async fn get_last_inserted(pool: web::Data<PgPool>) -> Result<NoteLabel, Error>{
-        query(r#"SELECT id, note_id, label_id FROM notes_labels WHERE id=(SELECT CURRVAL(PG_GET_SERIAL_SEQUENCE('notes_labels', 'id')))"#)
-            .map(|row: PgRow| NoteLabel{
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                label_id: row.get("label_id"),
-            })
-            .fetch_one(pool.get_ref())
-            .await
-    }
-
-    pub async fn new(pool: web::Data<PgPool>, note_id: i32, label_id: i32) -> Result<NoteLabel, Error>{
-        query(r#"INSERT INTO notes_labels (note_id, label_id) VALUES ($1, $2) RETURNING id, note_id, label_id;"#)
+    /// Only insert the assignment, so it can be part of a transaction
+    pub(crate) async fn insert(conn: &mut PgConnection, note_id: i32, label_id: i32, workspace_id: i32) -> Result<NoteLabel, Error>{
+        query(r#"INSERT INTO notes_labels (note_id, label_id) SELECT n.id, l.id FROM notes n, labels l WHERE n.id = $1 AND l.id = $2 AND n.workspace_id = $3 AND l.workspace_id = $3 RETURNING id, note_id, label_id;"#)
             .bind(note_id)
             .bind(label_id)
+            .bind(workspace_id)
             .map(|row: PgRow| NoteLabel{
                 id: row.get("id"),
                 note_id: row.get("note_id"),
                 label_id: row.get("label_id"),
             })
-            .fetch_one(pool.get_ref())
-            .await
-    }
-
-    pub async fn update(pool: web::Data<PgPool>, note_label: NoteLabel) -> Result<NoteLabel, Error>{
-        query(r#"UPDATE notes_labels SET note_id = ?, label_id = ? WHERE id = ?  RETURNING id, note_id, label_id;"#)
-            .bind(note_label.note_id)
-            .bind(note_label.label_id)
-            .bind(note_label.id)
-            .map(|row: PgRow| NoteLabel{
-                id: row.get("id"),
-                note_id: row.get("note_id"),
-                label_id: row.get("label_id"),
-            })
-            .fetch_one(pool.get_ref())
+            .fetch_one(conn)
             .await
     }
 
-    pub async fn delete(pool: web::Data<PgPool>, note_id: i32, label_id: i32) -> Result<NoteLabel, Error>{
-        query(r#"DELETE FROM notes_labels WHERE note_id = $1 AND label_id = $2 RETURNING id, note_id, label_id;"#)
+    pub async fn delete(pool: web::Data<PgPool>, note_id: i32, label_id: i32, workspace_id: i32) -> Result<NoteLabel, Error>{
+        let note_label = query(r#"DELETE FROM notes_labels WHERE note_id = $1 AND label_id = $2 AND note_id IN (SELECT id FROM notes WHERE workspace_id = $3) RETURNING id, note_id, label_id;"#)
             .bind(note_id)
             .bind(label_id)
+            .bind(workspace_id)
             .map(|row: PgRow| NoteLabel{
                 id: row.get("id"),
                 note_id: row.get("note_id"),
                 label_id: row.get("label_id"),
             })
             .fetch_one(pool.get_ref())
-            .await
+            .await?;
+        events::notify(pool.get_ref(), "label.unassigned", workspace_id, &note_label).await?;
+        Ok(note_label)
     }
 }
-
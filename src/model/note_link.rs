@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::model::note::Note;
+
+/// A note at the other end of a link
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct LinkedNote{
+    pub id: i32,
+    #[schema(example = "Titulo")]
+    pub title: String,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Edge{
+    pub source: i32,
+    pub target: i32,
+}
+
+/// The notes of the workspace and the links between them
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Graph{
+    pub nodes: Vec<LinkedNote>,
+    pub edges: Vec<Edge>,
+}
+
+/// What a `[[...]]` link in the body of a note points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target{
+    Id(i32),
+    Title(String),
+}
+
+/// A `[[target]]` or `[[target|text]]` link found in a body
+struct Link<'a>{
+    range: Range<usize>,
+    target: &'a str,
+    text: Option<&'a str>,
+}
+
+fn scan(body: &str) -> Vec<Link<'_>>{
+    let mut links = Vec::new();
+    let mut from = 0;
+    while let Some(start) = body[from..].find("[[").map(|start| from + start) {
+        let inner_start = start + 2;
+        let end = match body[inner_start..].find("]]") {
+            Some(end) => inner_start + end,
+            None => break,
+        };
+        let inner = &body[inner_start..end];
+        if inner.contains(['[', '\n']) {
+            from = inner_start;
+            continue;
+        }
+        let (target, text) = match inner.split_once('|') {
+            Some((target, text)) => (target.trim(), Some(text)),
+            None => (inner.trim(), None),
+        };
+        if !target.is_empty() {
+            links.push(Link{range: start..end + 2, target, text});
+        }
+        from = end + 2;
+    }
+    links
+}
+
+/// The targets of the links in `body`. Numbers are note ids, anything else
+/// a note title.
+pub fn parse_links(body: &str) -> Vec<Target>{
+    scan(body).into_iter()
+        .map(|link| match link.target.parse() {
+            Ok(id) => Target::Id(id),
+            Err(_) => Target::Title(link.target.to_string()),
+        })
+        .collect()
+}
+
+/// Point the links to the title `from` in `body` to the title `to`
+pub fn rewrite_links(body: &str, from: &str, to: &str) -> String{
+    let from = from.trim().to_lowercase();
+    let mut rewritten = String::with_capacity(body.len());
+    let mut last = 0;
+    for link in scan(body).into_iter().filter(|link| link.target.to_lowercase() == from) {
+        rewritten.push_str(&body[last..link.range.start]);
+        match link.text {
+            Some(text) => rewritten.push_str(&format!("[[{}|{}]]", to, text)),
+            None => rewritten.push_str(&format!("[[{}]]", to)),
+        }
+        last = link.range.end;
+    }
+    rewritten.push_str(&body[last..]);
+    rewritten
+}
+
+/// Point the links to note ids in `body` to the ids they were given in
+/// `ids`, as when notes are restored in another workspace
+pub fn remap_links(body: &str, ids: &HashMap<i32, i32>) -> String{
+    let mut rewritten = String::with_capacity(body.len());
+    let mut last = 0;
+    for link in scan(body) {
+        let id = match link.target.parse::<i32>().ok().and_then(|id| ids.get(&id)) {
+            Some(id) => id,
+            None => continue,
+        };
+        rewritten.push_str(&body[last..link.range.start]);
+        match link.text {
+            Some(text) => rewritten.push_str(&format!("[[{}|{}]]", id, text)),
+            None => rewritten.push_str(&format!("[[{}]]", id)),
+        }
+        last = link.range.end;
+    }
+    rewritten.push_str(&body[last..]);
+    rewritten
+}
+
+fn linked_note_from_row(row: PgRow) -> LinkedNote{
+    LinkedNote{
+        id: row.get("id"),
+        title: row.get("title"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+pub struct NoteLink;
+
+impl NoteLink{
+    /// Notes linked from the note `id`
+    pub async fn links(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Vec<LinkedNote>, Error>{
+        query(r#"SELECT n.id, n.title, n.updated_at FROM note_links l
+            INNER JOIN notes n ON n.id = l.target_id
+            WHERE l.source_id = $1 AND n.workspace_id = $2
+            ORDER BY n.title, n.id"#)
+            .bind(id)
+            .bind(workspace_id)
+            .map(linked_note_from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    /// Notes that link to the note `id`
+    pub async fn backlinks(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Vec<LinkedNote>, Error>{
+        query(r#"SELECT n.id, n.title, n.updated_at FROM note_links l
+            INNER JOIN notes n ON n.id = l.source_id
+            WHERE l.target_id = $1 AND n.workspace_id = $2
+            ORDER BY n.title, n.id"#)
+            .bind(id)
+            .bind(workspace_id)
+            .map(linked_note_from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn graph(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Graph, Error>{
+        let nodes = query(r#"SELECT id, title, updated_at FROM notes WHERE workspace_id = $1 ORDER BY id"#)
+            .bind(workspace_id)
+            .map(linked_note_from_row)
+            .fetch_all(pool.get_ref())
+            .await?;
+        let edges = query(r#"SELECT l.source_id, l.target_id FROM note_links l
+            INNER JOIN notes n ON n.id = l.source_id
+            WHERE n.workspace_id = $1
+            ORDER BY l.source_id, l.target_id"#)
+            .bind(workspace_id)
+            .map(|row: PgRow| Edge{
+                source: row.get("source_id"),
+                target: row.get("target_id"),
+            })
+            .fetch_all(pool.get_ref())
+            .await?;
+        Ok(Graph{nodes, edges})
+    }
+
+    /// Store the links found in `body` as the links of the note `id`.
+    ///
+    /// Links to notes of other workspaces, to missing notes and to the note
+    /// itself are ignored. A title shared by several notes resolves to the
+    /// oldest one.
+    pub async fn refresh(pool: &web::Data<PgPool>, id: i32, body: &str, workspace_id: i32) -> Result<(), Error>{
+        let mut ids = Vec::new();
+        let mut titles = Vec::new();
+        for target in parse_links(body) {
+            match target {
+                Target::Id(id) => ids.push(id),
+                Target::Title(title) => titles.push(title.to_lowercase()),
+            }
+        }
+        let mut tx = pool.begin().await?;
+        let targets: Vec<i32> = query(r#"SELECT id FROM notes WHERE workspace_id = $1 AND id = ANY($2)
+            UNION
+            SELECT id FROM (SELECT DISTINCT ON (LOWER(title)) id FROM notes
+                WHERE workspace_id = $1 AND LOWER(title) = ANY($3)
+                ORDER BY LOWER(title), id) t"#)
+            .bind(workspace_id)
+            .bind(&ids)
+            .bind(&titles)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_all(&mut tx)
+            .await?
+            .into_iter()
+            .filter(|target| *target != id)
+            .collect();
+        query(r#"DELETE FROM note_links WHERE source_id = $1 AND NOT target_id = ANY($2)"#)
+            .bind(id)
+            .bind(&targets)
+            .execute(&mut tx)
+            .await?;
+        query(r#"INSERT INTO note_links (source_id, target_id, created_at)
+            SELECT $1, UNNEST($2::INTEGER[]), $3
+            ON CONFLICT DO NOTHING"#)
+            .bind(id)
+            .bind(&targets)
+            .bind(Utc::now().naive_utc())
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Refresh the links of the notes that mention the title `title` of the
+    /// note `id` and do not link to it yet
+    pub async fn relink(pool: &web::Data<PgPool>, id: i32, title: &str, workspace_id: i32) -> Result<(), Error>{
+        let sources: Vec<(i32, String)> = query(r#"SELECT id, body FROM notes
+            WHERE workspace_id = $1 AND id <> $2
+            AND STRPOS(LOWER(body), LOWER('[[' || $3)) > 0
+            AND id NOT IN (SELECT source_id FROM note_links WHERE target_id = $2)"#)
+            .bind(workspace_id)
+            .bind(id)
+            .bind(title.trim())
+            .map(|row: PgRow| (row.get("id"), row.get("body")))
+            .fetch_all(pool.get_ref())
+            .await?;
+        for (source_id, body) in sources.iter() {
+            NoteLink::refresh(pool, *source_id, body, workspace_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Follow the rename of the note `id` from `from` to `to` in the notes
+    /// linking to it. With `rewrite` their `[[from]]` links become `[[to]]`,
+    /// as updates of those notes; without it the links by the old title are
+    /// dropped. Then the notes mentioning the new title link to it.
+    pub async fn renamed(pool: &web::Data<PgPool>, id: i32, from: &str, to: &str, workspace_id: i32, rewrite: bool) -> Result<(), Error>{
+        let sources: Vec<(i32, String)> = query(r#"SELECT n.id, n.body FROM note_links l
+            INNER JOIN notes n ON n.id = l.source_id
+            WHERE l.target_id = $1 AND n.workspace_id = $2"#)
+            .bind(id)
+            .bind(workspace_id)
+            .map(|row: PgRow| (row.get("id"), row.get("body")))
+            .fetch_all(pool.get_ref())
+            .await?;
+        for (source_id, body) in sources.iter() {
+            let rewritten = match rewrite {
+                true => rewrite_links(body, from, to),
+                false => body.clone(),
+            };
+            if rewritten != *body {
+                Note::set_body(pool, *source_id, &rewritten, workspace_id).await?;
+            } else {
+                NoteLink::refresh(pool, *source_id, body, workspace_id).await?;
+            }
+        }
+        NoteLink::relink(pool, id, to, workspace_id).await
+    }
+}
+
+#[test]
+fn test_parse_links() {
+    let body = "Ver [[Compra]] y [[ 42 ]], [[Viaje|el viaje]] o [[]] [[roto\n]] [[a [[Otra]]";
+    assert_eq!(parse_links(body), vec![
+        Target::Title("Compra".to_string()),
+        Target::Id(42),
+        Target::Title("Viaje".to_string()),
+        Target::Title("Otra".to_string()),
+    ]);
+    assert_eq!(
+        rewrite_links("[[compra]], [[Compra|lista]] y [[Compras]]", "Compra", "Mercado"),
+        "[[Mercado]], [[Mercado|lista]] y [[Compras]]"
+    );
+    let ids = HashMap::from([(4, 40), (7, 70)]);
+    assert_eq!(
+        remap_links("[[4]], [[ 7 |siete]], [[5]] y [[Compra]]", &ids),
+        "[[40]], [[70|siete]], [[5]] y [[Compra]]"
+    );
+}
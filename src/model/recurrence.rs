@@ -0,0 +1,207 @@
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, Months, NaiveDate, NaiveDateTime, Weekday};
+
+/// Largest INTERVAL accepted, a thousand years at most
+pub const MAX_INTERVAL: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency{
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Recurrence rule of a reminder.
+///
+/// Accepts the shortcuts `daily`, `weekly`, `monthly` and `yearly`, and the
+/// RRULE subset made of `FREQ`, `INTERVAL`, `BYDAY` (weekly only) and `UNTIL`,
+/// like `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence{
+    pub frequency: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub until: Option<NaiveDateTime>,
+}
+
+impl Recurrence{
+    fn new(frequency: Frequency) -> Self{
+        Self{
+            frequency,
+            interval: 1,
+            by_day: Vec::new(),
+            until: None,
+        }
+    }
+
+    /// The first occurrence of the series started at `start` that comes
+    /// strictly after `after`. None when there is no other, also when it
+    /// is beyond the dates that can be represented.
+    pub fn next_after(&self, start: NaiveDateTime, after: NaiveDateTime) -> Option<NaiveDateTime>{
+        let interval = i64::from(self.interval);
+        let next = match self.frequency {
+            Frequency::Daily => next_by_step(start, after, Duration::try_days(interval)?),
+            Frequency::Weekly if self.by_day.is_empty() => next_by_step(start, after, Duration::try_weeks(interval)?),
+            Frequency::Weekly => self.next_by_day(start, after),
+            Frequency::Monthly => next_by_months(start, after, self.interval),
+            Frequency::Yearly => next_by_months(start, after, self.interval.saturating_mul(12)),
+        };
+        next.filter(|next| self.until.map_or(true, |until| *next <= until))
+    }
+
+    fn next_by_day(&self, start: NaiveDateTime, after: NaiveDateTime) -> Option<NaiveDateTime>{
+        let interval = i64::from(self.interval);
+        let mut days: Vec<i64> = self.by_day.iter()
+            .map(|day| i64::from(day.num_days_from_monday()))
+            .collect();
+        days.sort_unstable();
+        let first_monday = start.date().checked_sub_signed(Duration::days(i64::from(start.weekday().num_days_from_monday())))?;
+        let first_period = if after > start {
+            (after.date() - first_monday).num_days() / 7 / interval
+        }else{
+            0
+        };
+        // Every period has at least one candidate, so two periods are enough
+        (first_period..first_period + 2)
+            .filter_map(|period| first_monday.checked_add_signed(Duration::try_weeks(period.checked_mul(interval)?)?))
+            .flat_map(|monday| days.iter().filter_map(move |day| monday.checked_add_signed(Duration::days(*day))))
+            .map(|date| date.and_time(start.time()))
+            .find(|candidate| *candidate >= start && *candidate > after)
+    }
+}
+
+fn next_by_step(start: NaiveDateTime, after: NaiveDateTime, step: Duration) -> Option<NaiveDateTime>{
+    if after < start {
+        return Some(start);
+    }
+    let steps = (after - start).num_seconds() / step.num_seconds() + 1;
+    start.checked_add_signed(step.checked_mul(i32::try_from(steps).ok()?)?)
+}
+
+fn next_by_months(start: NaiveDateTime, after: NaiveDateTime, months: u32) -> Option<NaiveDateTime>{
+    if after < start {
+        return Some(start);
+    }
+    let elapsed = (after.year() - start.year()) * 12 + after.month() as i32 - start.month() as i32;
+    let mut step = (elapsed.max(0) as u32) / months.max(1);
+    loop {
+        let candidate = start.checked_add_months(Months::new(step.checked_mul(months)?))?;
+        if candidate > after {
+            return Some(candidate);
+        }
+        step += 1;
+    }
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, String>{
+    match value {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(format!("Invalid BYDAY value {}", value)),
+    }
+}
+
+fn parse_until(value: &str) -> Result<NaiveDateTime, String>{
+    let value = value.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y%m%d")
+            .map(|date| date.and_hms_opt(23, 59, 59).unwrap()))
+        .map_err(|_| format!("Invalid UNTIL value {}", value))
+}
+
+impl FromStr for Recurrence{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>{
+        let s = s.trim();
+        match s.to_lowercase().as_str() {
+            "daily" => return Ok(Recurrence::new(Frequency::Daily)),
+            "weekly" => return Ok(Recurrence::new(Frequency::Weekly)),
+            "monthly" => return Ok(Recurrence::new(Frequency::Monthly)),
+            "yearly" => return Ok(Recurrence::new(Frequency::Yearly)),
+            _ => {},
+        }
+        let rule = s.strip_prefix("RRULE:").unwrap_or(s);
+        let mut recurrence: Option<Recurrence> = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut until = None;
+        for part in rule.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part.split_once('=')
+                .ok_or_else(|| format!("Invalid rule part {}", part))?;
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    let frequency = match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(format!("Unsupported FREQ {}", value)),
+                    };
+                    recurrence = Some(Recurrence::new(frequency));
+                },
+                "INTERVAL" => {
+                    interval = value.parse()
+                        .ok()
+                        .filter(|interval| (1..=MAX_INTERVAL).contains(interval))
+                        .ok_or_else(|| format!("Invalid INTERVAL {}", value))?;
+                },
+                "BYDAY" => {
+                    by_day = value.split(',')
+                        .map(|day| parse_weekday(&day.to_uppercase()))
+                        .collect::<Result<Vec<Weekday>, String>>()?;
+                },
+                "UNTIL" => until = Some(parse_until(value)?),
+                _ => return Err(format!("Unsupported rule part {}", key)),
+            }
+        }
+        let mut recurrence = recurrence.ok_or_else(|| "FREQ is required".to_string())?;
+        if !by_day.is_empty() && recurrence.frequency != Frequency::Weekly {
+            return Err("BYDAY is only supported with FREQ=WEEKLY".to_string());
+        }
+        recurrence.interval = interval;
+        recurrence.by_day = by_day;
+        recurrence.until = until;
+        Ok(recurrence)
+    }
+}
+
+#[test]
+fn test_next_after() {
+    let at = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+    let start = at("2022-10-03 09:00"); // Monday
+
+    let daily: Recurrence = "daily".parse().unwrap();
+    assert_eq!(daily.next_after(start, at("2022-10-01 00:00")), Some(start));
+    assert_eq!(daily.next_after(start, start), Some(at("2022-10-04 09:00")));
+    assert_eq!(daily.next_after(start, at("2022-10-10 10:00")), Some(at("2022-10-11 09:00")));
+
+    let weekdays: Recurrence = "FREQ=WEEKLY;INTERVAL=2;BYDAY=TH,MO".parse().unwrap();
+    assert_eq!(weekdays.next_after(start, start), Some(at("2022-10-06 09:00")));
+    assert_eq!(weekdays.next_after(start, at("2022-10-06 09:00")), Some(at("2022-10-17 09:00")));
+
+    let monthly: Recurrence = "RRULE:FREQ=MONTHLY;UNTIL=20221231".parse().unwrap();
+    assert_eq!(monthly.next_after(start, at("2022-11-05 00:00")), Some(at("2022-12-03 09:00")));
+    assert_eq!(monthly.next_after(start, at("2022-12-03 09:00")), None);
+
+    assert!("FREQ=HOURLY".parse::<Recurrence>().is_err());
+    assert!("FREQ=DAILY;BYDAY=MO".parse::<Recurrence>().is_err());
+    assert!("INTERVAL=2".parse::<Recurrence>().is_err());
+    assert!("FREQ=DAILY;INTERVAL=1000000000".parse::<Recurrence>().is_err());
+
+    // Far in the future there is no next one, instead of an overflow
+    let far = NaiveDate::MAX.and_hms_opt(0, 0, 0).unwrap();
+    for rule in ["FREQ=DAILY;INTERVAL=1000", "FREQ=WEEKLY;INTERVAL=1000", "FREQ=WEEKLY;INTERVAL=1000;BYDAY=MO", "FREQ=YEARLY;INTERVAL=1000"] {
+        let recurrence: Recurrence = rule.parse().unwrap();
+        assert_eq!(recurrence.next_after(start, far), None, "{}", rule);
+    }
+    let huge = Recurrence{interval: u32::MAX, ..daily};
+    assert_eq!(huge.next_after(start, start), None);
+}
@@ -0,0 +1,213 @@
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::model::recurrence::Recurrence;
+
+/// A note with a reminder, as listed in the upcoming reminders
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Reminder{
+    pub note_id: i32,
+    #[schema(example = "Titulo")]
+    pub title: String,
+    pub remind_at: NaiveDateTime,
+    #[schema(example = "FREQ=WEEKLY;BYDAY=MO,TH")]
+    pub recurrence: Option<String>,
+    pub snoozed_until: Option<NaiveDateTime>,
+    /// Already sent and waiting to be dismissed or snoozed
+    pub fired: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewReminder{
+    pub remind_at: NaiveDateTime,
+    #[schema(example = "daily")]
+    pub recurrence: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Snooze{
+    /// Between 1 and 525600, a year
+    #[schema(example = 10)]
+    pub minutes: i64,
+}
+
+/// A reminder to be sent, with everything the channels need
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DueReminder{
+    pub note_id: i32,
+    pub workspace_id: i32,
+    pub user_id: i32,
+    pub email: String,
+    pub title: String,
+    pub body: String,
+    pub remind_at: NaiveDateTime,
+    pub recurrence: Option<String>,
+}
+
+/// Check that `recurrence` is a rule the scheduler understands
+pub fn validate_recurrence(recurrence: &Option<String>) -> Result<(), String>{
+    match recurrence {
+        Some(recurrence) => recurrence.parse::<Recurrence>().map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn next_occurrence(recurrence: &Option<String>, remind_at: NaiveDateTime, after: NaiveDateTime) -> Option<NaiveDateTime>{
+    recurrence.as_ref()
+        .and_then(|recurrence| recurrence.parse::<Recurrence>().ok())
+        .and_then(|recurrence| recurrence.next_after(remind_at, after))
+}
+
+const SELECT: &str = r#"SELECT id AS note_id, title, remind_at, recurrence, snoozed_until,
+    COALESCE(reminded_at >= COALESCE(snoozed_until, remind_at), FALSE) AS fired
+    FROM notes
+    "#;
+
+impl Reminder{
+    fn from_row(row: PgRow) -> Reminder{
+        Reminder{
+            note_id: row.get("note_id"),
+            title: row.get("title"),
+            remind_at: row.get("remind_at"),
+            recurrence: row.get("recurrence"),
+            snoozed_until: row.get("snoozed_until"),
+            fired: row.get("fired"),
+        }
+    }
+
+    /// Reminders of the workspace that are due before `until`, fired ones included
+    pub async fn upcoming(pool: &web::Data<PgPool>, workspace_id: i32, until: NaiveDateTime) -> Result<Vec<Reminder>, Error>{
+        let sql = format!("{} WHERE workspace_id = $1 AND remind_at IS NOT NULL AND COALESCE(snoozed_until, remind_at) <= $2 ORDER BY COALESCE(snoozed_until, remind_at)", SELECT);
+        query(&sql)
+            .bind(workspace_id)
+            .bind(until)
+            .map(Reminder::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn get(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32) -> Result<Reminder, Error>{
+        let sql = format!("{} WHERE id = $1 AND workspace_id = $2 AND remind_at IS NOT NULL", SELECT);
+        query(&sql)
+            .bind(note_id)
+            .bind(workspace_id)
+            .map(Reminder::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn set(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32, reminder: NewReminder) -> Result<Reminder, Error>{
+        query(r#"UPDATE notes SET remind_at = $1, recurrence = $2, snoozed_until = NULL, reminded_at = NULL WHERE id = $3 AND workspace_id = $4 RETURNING id"#)
+            .bind(reminder.remind_at)
+            .bind(reminder.recurrence)
+            .bind(note_id)
+            .bind(workspace_id)
+            .fetch_one(pool.get_ref())
+            .await?;
+        Reminder::get(pool, note_id, workspace_id).await
+    }
+
+    pub async fn unset(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32) -> Result<Reminder, Error>{
+        let reminder = Reminder::get(pool, note_id, workspace_id).await?;
+        query(r#"UPDATE notes SET remind_at = NULL, recurrence = NULL, snoozed_until = NULL, reminded_at = NULL WHERE id = $1 AND workspace_id = $2"#)
+            .bind(note_id)
+            .bind(workspace_id)
+            .execute(pool.get_ref())
+            .await?;
+        Ok(reminder)
+    }
+
+    /// Send the reminder again at `snoozed_until`
+    pub async fn snooze(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32, snoozed_until: NaiveDateTime) -> Result<Reminder, Error>{
+        query(r#"UPDATE notes SET snoozed_until = $1 WHERE id = $2 AND workspace_id = $3 AND remind_at IS NOT NULL RETURNING id"#)
+            .bind(snoozed_until)
+            .bind(note_id)
+            .bind(workspace_id)
+            .fetch_one(pool.get_ref())
+            .await?;
+        Reminder::get(pool, note_id, workspace_id).await
+    }
+
+    /// Forget the current occurrence. Recurrent reminders move to the next
+    /// one and the rest are removed.
+    pub async fn dismiss(pool: &web::Data<PgPool>, note_id: i32, workspace_id: i32) -> Result<Reminder, Error>{
+        let reminder = Reminder::get(pool, note_id, workspace_id).await?;
+        let now = Utc::now().naive_utc();
+        let next = next_occurrence(&reminder.recurrence, reminder.remind_at, now.max(reminder.remind_at));
+        let (remind_at, recurrence) = match next {
+            Some(next) => (Some(next), reminder.recurrence.clone()),
+            None => (None, None),
+        };
+        query(r#"UPDATE notes SET remind_at = $1, recurrence = $2, snoozed_until = NULL, reminded_at = NULL WHERE id = $3 AND workspace_id = $4"#)
+            .bind(remind_at)
+            .bind(recurrence)
+            .bind(note_id)
+            .bind(workspace_id)
+            .execute(pool.get_ref())
+            .await?;
+        Ok(reminder)
+    }
+
+    /// The notes with a reminder due at `now`, to take them one by one
+    pub async fn due(pool: &PgPool, now: NaiveDateTime) -> Result<Vec<i32>, Error>{
+        query(r#"SELECT id FROM notes
+            WHERE COALESCE(snoozed_until, remind_at) <= $1
+            AND (reminded_at IS NULL OR reminded_at < COALESCE(snoozed_until, remind_at))
+            ORDER BY COALESCE(snoozed_until, remind_at)"#)
+            .bind(now)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Claim the reminder of the note, if it is still due at `now`, so no
+    /// other scheduler sends it. None when it is not due anymore or another
+    /// scheduler has it.
+    ///
+    /// Recurrent reminders move to their next occurrence; the rest stay
+    /// marked as fired until they are dismissed or snoozed.
+    pub async fn take(pool: &PgPool, note_id: i32, now: NaiveDateTime) -> Result<Option<DueReminder>, Error>{
+        let mut tx = pool.begin().await?;
+        let due = query(r#"SELECT n.id, n.workspace_id, n.user_id, u.email, n.title, n.body, n.remind_at, n.recurrence,
+            COALESCE(n.snoozed_until, n.remind_at) AS fire_at
+            FROM notes n
+            INNER JOIN users u ON u.id = n.user_id
+            WHERE n.id = $1
+            AND COALESCE(n.snoozed_until, n.remind_at) <= $2
+            AND (n.reminded_at IS NULL OR n.reminded_at < COALESCE(n.snoozed_until, n.remind_at))
+            FOR UPDATE OF n SKIP LOCKED"#)
+            .bind(note_id)
+            .bind(now)
+            .map(|row: PgRow| (DueReminder{
+                note_id: row.get("id"),
+                workspace_id: row.get("workspace_id"),
+                user_id: row.get("user_id"),
+                email: row.get("email"),
+                title: row.get("title"),
+                body: row.get("body"),
+                remind_at: row.get("remind_at"),
+                recurrence: row.get("recurrence"),
+            }, row.get::<NaiveDateTime, _>("fire_at")))
+            .fetch_optional(&mut tx)
+            .await?;
+        let (reminder, fire_at) = match due {
+            Some(due) => due,
+            None => return Ok(None),
+        };
+        let sql = match next_occurrence(&reminder.recurrence, reminder.remind_at, now) {
+            Some(next) => query(r#"UPDATE notes SET remind_at = $1, snoozed_until = NULL, reminded_at = $2 WHERE id = $3"#)
+                .bind(next)
+                .bind(fire_at),
+            None => query(r#"UPDATE notes SET reminded_at = $1 WHERE id = $2"#)
+                .bind(fire_at),
+        };
+        sql.bind(reminder.note_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(Some(reminder))
+    }
+}
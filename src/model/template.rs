@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Postgres, Row, Transaction, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::events;
+use crate::model::{note::{Note, NewNote, NoteKind}, note_label::NoteLabel,
+    note_category::NoteCategory};
+
+/// Placeholders always available in a template
+pub const BUILTINS: [&str; 4] = ["date", "time", "datetime", "user.email"];
+
+/// A note to start from. `{{date}}`, `{{time}}`, `{{datetime}}` and
+/// `{{user.email}}` in the title and body are filled in when the note is
+/// created; any other `{{placeholder}}` is a prompt whose value is asked
+/// for.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Template{
+    pub id: i32,
+    #[schema(example = "Acta de reunión")]
+    pub name: String,
+    #[schema(example = "Reunión {{date}}")]
+    pub title: String,
+    #[schema(example = "Asistentes: {{asistentes}}")]
+    pub body: String,
+    pub kind: NoteKind,
+    /// Labels assigned to the notes created from the template
+    pub labels: Vec<i32>,
+    /// Categories assigned to the notes created from the template
+    pub categories: Vec<i32>,
+    /// The placeholders that need a value
+    #[schema(example = json!(["asistentes"]))]
+    pub prompts: Vec<String>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewTemplate{
+    #[schema(example = "Acta de reunión")]
+    pub name: String,
+    #[schema(example = "Reunión {{date}}")]
+    pub title: String,
+    pub body: Option<String>,
+    pub kind: Option<NoteKind>,
+    pub labels: Option<Vec<i32>>,
+    pub categories: Option<Vec<i32>>,
+}
+
+/// The fields left out keep their value
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateTemplate{
+    pub id: i32,
+    pub name: Option<String>,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub kind: Option<NoteKind>,
+    pub labels: Option<Vec<i32>>,
+    pub categories: Option<Vec<i32>>,
+}
+
+/// Values for the prompts of a template
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct TemplateValues{
+    #[schema(example = json!({"asistentes": "Ana, Luis"}))]
+    pub values: HashMap<String, String>,
+}
+
+/// The names of the `{{placeholders}}` in `text`, in order and without
+/// repetitions
+pub fn placeholders(text: &str) -> Vec<String>{
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                if !name.is_empty() && !name.contains(['{', '\n']) && !names.iter().any(|known| known == name) {
+                    names.push(name.to_string());
+                }
+                rest = &after[end + 2..];
+            },
+            None => break,
+        }
+    }
+    names
+}
+
+/// Replace the `{{placeholders}}` of `text` with their value in `values`.
+/// Placeholders without value are left untouched.
+pub fn render(text: &str, values: &HashMap<String, String>) -> String{
+    let mut rendered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(end) => end,
+            None => break,
+        };
+        rendered.push_str(&rest[..start]);
+        match values.get(after[..end].trim()) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// The `values` given for the prompts with the builtin placeholders for
+/// `email` at `now`, which can not be overridden
+pub fn variables(mut values: HashMap<String, String>, email: &str, now: NaiveDateTime) -> HashMap<String, String>{
+    values.extend([
+        ("date".to_string(), now.format("%Y-%m-%d").to_string()),
+        ("time".to_string(), now.format("%H:%M").to_string()),
+        ("datetime".to_string(), now.format("%Y-%m-%d %H:%M").to_string()),
+        ("user.email".to_string(), email.to_string()),
+    ]);
+    values
+}
+
+const COLUMNS: &str = r#"t.id, t.name, t.title, t.body, t.kind, t.created_at, t.updated_at,
+    ARRAY(SELECT label_id FROM templates_labels WHERE template_id = t.id ORDER BY label_id) AS labels,
+    ARRAY(SELECT category_id FROM templates_categories WHERE template_id = t.id ORDER BY category_id) AS categories"#;
+
+impl Template{
+    fn from_row(row: PgRow) -> Template{
+        let title: String = row.get("title");
+        let body: String = row.get("body");
+        let prompts = placeholders(&format!("{}\n{}", title, body))
+            .into_iter()
+            .filter(|name| !BUILTINS.contains(&name.as_str()))
+            .collect();
+        Template{
+            id: row.get("id"),
+            name: row.get("name"),
+            title,
+            body,
+            kind: row.get::<String, _>("kind").parse().unwrap_or_default(),
+            labels: row.get("labels"),
+            categories: row.get("categories"),
+            prompts,
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Vec<Template>, Error>{
+        let sql = format!("SELECT {} FROM templates t WHERE t.workspace_id = $1 ORDER BY t.name", COLUMNS);
+        query(&sql)
+            .bind(workspace_id)
+            .map(Template::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn get(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Template, Error>{
+        let sql = format!("SELECT {} FROM templates t WHERE t.id = $1 AND t.workspace_id = $2", COLUMNS);
+        query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(Template::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn new(pool: &web::Data<PgPool>, template: NewTemplate, workspace_id: i32, user_id: i32) -> Result<Template, Error>{
+        let created_at = Utc::now().naive_utc();
+        let updated_at = Utc::now().naive_utc();
+        let mut tx = pool.begin().await?;
+        let id: i32 = query(r#"INSERT INTO templates (name, title, body, kind, user_id, workspace_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id;"#)
+            .bind(template.name)
+            .bind(template.title)
+            .bind(template.body.unwrap_or_default())
+            .bind(template.kind.unwrap_or_default().as_str())
+            .bind(user_id)
+            .bind(workspace_id)
+            .bind(created_at)
+            .bind(updated_at)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_one(&mut tx)
+            .await?;
+        set_defaults(&mut tx, id, workspace_id, template.labels, template.categories).await?;
+        tx.commit().await?;
+        Template::get(pool, id, workspace_id).await
+    }
+
+    pub async fn update(pool: &web::Data<PgPool>, template: UpdateTemplate, workspace_id: i32) -> Result<Template, Error>{
+        let updated_at = Utc::now().naive_utc();
+        let mut tx = pool.begin().await?;
+        query(r#"UPDATE templates SET name = COALESCE($1, name), title = COALESCE($2, title), body = COALESCE($3, body), kind = COALESCE($4, kind), updated_at = $5 WHERE id = $6 AND workspace_id = $7 RETURNING id;"#)
+            .bind(template.name)
+            .bind(template.title)
+            .bind(template.body)
+            .bind(template.kind.map(|kind| kind.as_str()))
+            .bind(updated_at)
+            .bind(template.id)
+            .bind(workspace_id)
+            .fetch_one(&mut tx)
+            .await?;
+        set_defaults(&mut tx, template.id, workspace_id, template.labels, template.categories).await?;
+        tx.commit().await?;
+        Template::get(pool, template.id, workspace_id).await
+    }
+
+    pub async fn delete(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Template, Error>{
+        let template = Template::get(pool, id, workspace_id).await?;
+        query(r#"DELETE FROM templates WHERE id = $1 AND workspace_id = $2"#)
+            .bind(id)
+            .bind(workspace_id)
+            .execute(pool.get_ref())
+            .await?;
+        Ok(template)
+    }
+
+    /// Fill in the placeholders of the title and body. Fails with the
+    /// prompts left without value.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<(String, String), Vec<String>>{
+        let missing: Vec<String> = self.prompts.iter()
+            .filter(|prompt| !values.contains_key(*prompt))
+            .cloned()
+            .collect();
+        if !missing.is_empty() {
+            return Err(missing);
+        }
+        Ok((render(&self.title, values), render(&self.body, values)))
+    }
+
+    /// Create a note from the rendered template with the default labels and
+    /// categories assigned
+    pub async fn new_note(&self, pool: &web::Data<PgPool>, title: String, body: String, workspace_id: i32, user_id: i32) -> Result<Note, Error>{
+        let note = NewNote{
+            title,
+            body: Some(body),
+            kind: Some(self.kind),
+            remind_at: None,
+            recurrence: None,
+        };
+        let mut tx = pool.begin().await?;
+        let note = Note::insert(&mut tx, note, workspace_id, user_id).await?;
+        let mut labels = Vec::new();
+        for label_id in self.labels.iter() {
+            labels.push(NoteLabel::insert(&mut tx, note.id, *label_id, workspace_id).await?);
+        }
+        let mut categories = Vec::new();
+        for category_id in self.categories.iter() {
+            categories.push(NoteCategory::insert(&mut tx, note.id, *category_id, workspace_id).await?);
+        }
+        tx.commit().await?;
+        Note::created(pool, &note, workspace_id).await?;
+        for note_label in labels.iter() {
+            events::notify(pool.get_ref(), "label.assigned", workspace_id, note_label).await?;
+        }
+        for note_category in categories.iter() {
+            events::notify(pool.get_ref(), "category.assigned", workspace_id, note_category).await?;
+        }
+        Ok(note)
+    }
+}
+
+/// Replace the default labels and categories of the template. Every one of
+/// them must belong to the workspace.
+async fn set_defaults(tx: &mut Transaction<'_, Postgres>, id: i32, workspace_id: i32, labels: Option<Vec<i32>>, categories: Option<Vec<i32>>) -> Result<(), Error>{
+    for (ids, table, column, source) in [
+        (labels, "templates_labels", "label_id", "labels"),
+        (categories, "templates_categories", "category_id", "categories"),
+    ] {
+        let mut ids = match ids {
+            Some(ids) => ids,
+            None => continue,
+        };
+        ids.sort_unstable();
+        ids.dedup();
+        query(&format!("DELETE FROM {} WHERE template_id = $1", table))
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let inserted = query(&format!("INSERT INTO {} (template_id, {}) SELECT $1, id FROM {} WHERE workspace_id = $2 AND id = ANY($3)", table, column, source))
+            .bind(id)
+            .bind(workspace_id)
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if inserted != ids.len() as u64 {
+            return Err(Error::RowNotFound);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_render() {
+    let text = "Reunión {{date}} con {{ asistentes }} {{date}} {{sin cerrar";
+    assert_eq!(placeholders(text), vec!["date", "asistentes"]);
+    let values = HashMap::from([("date".to_string(), "2022-11-01".to_string())]);
+    assert_eq!(render(text, &values), "Reunión 2022-11-01 con {{ asistentes }} 2022-11-01 {{sin cerrar");
+}
+
+#[test]
+fn test_variables() {
+    let now = chrono::NaiveDate::from_ymd_opt(2022, 11, 1).unwrap().and_hms_opt(9, 30, 0).unwrap();
+    let values = HashMap::from([
+        ("date".to_string(), "ayer".to_string()),
+        ("asistentes".to_string(), "Ana".to_string()),
+    ]);
+    let variables = variables(values, "ana@example.com", now);
+    assert_eq!(variables["date"], "2022-11-01");
+    assert_eq!(variables["asistentes"], "Ana");
+    assert_eq!(variables["user.email"], "ana@example.com");
+}
@@ -2,6 +2,9 @@ use actix_web::web;
 use chrono::{NaiveDateTime, Utc};
 use sqlx::{query, FromRow, Error, Row, postgres::{PgPool, PgRow}};
 use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+use crate::model::workspace::Workspace;
 
 //https://github.com/juhaku/utoipa
 
@@ -13,37 +16,21 @@ pub struct User{
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
     pub login: bool,
+    pub disabled: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct IUser{
-    pub id: i32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Credentials{
+    #[schema(example = "tucorreo@tuservidor.es")]
     pub email: String,
+    #[schema(example = "secreto")]
     pub password: String,
 }
 
 
 impl User{
-    pub async fn all(pool: &web::Data<PgPool>) -> Result<Vec<User>, Error>{
-        query(r#"SELECT id, email, password, created_at, updated_at, login FROM users"#)
-            .map(|row: PgRow| User{
-                id: row.get("id"),
-                email: row.get("email"),
-                password: row.get("password"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
-                login: row.get("login"),
-            })
-            .fetch_all(pool.get_ref())
-            .await
-    }
-
     pub async fn get(pool: &web::Data<PgPool>, id: i32) -> Result<User, Error>{
-        query(r#"SELECT id, email, password, created_at, updated_at, login FROM users WHERE id = $1"#)
+        query(r#"SELECT id, email, password, created_at, updated_at, login, disabled FROM users WHERE id = $1"#)
             .bind(id)
             .map(|row: PgRow| User{
                 id: row.get("id"),
@@ -52,13 +39,14 @@ impl User{
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
                 login: row.get("login"),
+                disabled: row.get("disabled"),
             })
             .fetch_one(pool.get_ref())
             .await
     }
 
     pub async fn get_by_email(pool: &web::Data<PgPool>, email: &str) -> Result<User, Error>{
-        query(r#"SELECT id, email, password, created_at, updated_at, login FROM users WHERE email = $1"#)
+        query(r#"SELECT id, email, password, created_at, updated_at, login, disabled FROM users WHERE email = $1"#)
             .bind(email)
             .map(|row: PgRow| User{
                 id: row.get("id"),
@@ -67,18 +55,21 @@ impl User{
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
                 login: row.get("login"),
+                disabled: row.get("disabled"),
             })
             .fetch_one(pool.get_ref())
             .await
     }
 
+    /// Create the user together with its personal workspace
     pub async fn new(pool: &web::Data<PgPool>, credentials: Credentials) -> Result<User, Error>{
+        let mut tx = pool.begin().await?;
         let email = credentials.email;
         let password = format!("{:x}", md5::compute(credentials.password));
         let created_at = Utc::now().naive_utc();
         let updated_at = Utc::now().naive_utc();
         let login = false;
-        query(r#"INSERT INTO users (email, password, created_at, updated_at, login) VALUES ($1, $2, $3, $4, $5) RETURNING id, email, password, created_at, updated_at, login;"#,)
+        let user = query(r#"INSERT INTO users (email, password, created_at, updated_at, login) VALUES ($1, $2, $3, $4, $5) RETURNING id, email, password, created_at, updated_at, login, disabled;"#,)
             .bind(email)
             .bind(password)
             .bind(created_at)
@@ -91,13 +82,17 @@ impl User{
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
                 login: row.get("login"),
+                disabled: row.get("disabled"),
             })
-            .fetch_one(pool.get_ref())
-            .await
+            .fetch_one(&mut tx)
+            .await?;
+        Workspace::insert(&mut tx, &user.email, true, user.id).await?;
+        tx.commit().await?;
+        Ok(user)
     }
 
     pub async fn set_login(pool: &web::Data<PgPool>, id: i32, login: bool) -> Result<User, Error>{
-        query(r#"UPDATE users set login = $1 WHERE id = $2 RETURNING id, email, password, created_at, updated_at, login;"#,)
+        query(r#"UPDATE users set login = $1 WHERE id = $2 RETURNING id, email, password, created_at, updated_at, login, disabled;"#,)
             .bind(login)
             .bind(id)
             .map(|row: PgRow| User{
@@ -107,6 +102,59 @@ impl User{
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
                 login: row.get("login"),
+                disabled: row.get("disabled"),
+            })
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>) -> Result<Vec<User>, Error>{
+        query(r#"SELECT id, email, password, created_at, updated_at, login, disabled FROM users ORDER BY id"#)
+            .map(|row: PgRow| User{
+                id: row.get("id"),
+                email: row.get("email"),
+                password: row.get("password"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                login: row.get("login"),
+                disabled: row.get("disabled"),
+            })
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn set_disabled(pool: &web::Data<PgPool>, id: i32, disabled: bool) -> Result<User, Error>{
+        query(r#"UPDATE users set disabled = $1, updated_at = $2 WHERE id = $3 RETURNING id, email, password, created_at, updated_at, login, disabled;"#,)
+            .bind(disabled)
+            .bind(Utc::now().naive_utc())
+            .bind(id)
+            .map(|row: PgRow| User{
+                id: row.get("id"),
+                email: row.get("email"),
+                password: row.get("password"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                login: row.get("login"),
+                disabled: row.get("disabled"),
+            })
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn set_password(pool: &web::Data<PgPool>, id: i32, password: &str) -> Result<User, Error>{
+        let password = format!("{:x}", md5::compute(password));
+        query(r#"UPDATE users set password = $1, updated_at = $2 WHERE id = $3 RETURNING id, email, password, created_at, updated_at, login, disabled;"#,)
+            .bind(password)
+            .bind(Utc::now().naive_utc())
+            .bind(id)
+            .map(|row: PgRow| User{
+                id: row.get("id"),
+                email: row.get("email"),
+                password: row.get("password"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+                login: row.get("login"),
+                disabled: row.get("disabled"),
             })
             .fetch_one(pool.get_ref())
             .await
@@ -0,0 +1,394 @@
+use std::net::{IpAddr, SocketAddr};
+
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::{query, Error, Row, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::events::Event;
+
+/// The events that can be sent to a webhook
+pub const EVENTS: &[&str] = &[
+    "note.created", "note.updated", "note.deleted",
+    "label.created", "label.updated", "label.deleted",
+    "label.assigned", "label.unassigned",
+    "category.created", "category.updated", "category.deleted",
+    "category.assigned", "category.unassigned",
+];
+
+/// Length of the generated secrets
+const SECRET_LENGTH: usize = 32;
+
+/// An URL that gets the events of the workspace, signed with the secret
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Webhook{
+    pub id: i32,
+    #[schema(example = "https://example.com/hooks/notisbak")]
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature of the deliveries
+    pub secret: String,
+    /// Events sent, or all of them if empty
+    #[schema(example = json!(["note.created", "label.assigned"]))]
+    pub events: Vec<String>,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewWebhook{
+    #[schema(example = "https://example.com/hooks/notisbak")]
+    pub url: String,
+    /// Generated when missing
+    pub secret: Option<String>,
+    #[schema(example = json!(["note.created", "label.assigned"]))]
+    pub events: Option<Vec<String>>,
+}
+
+/// Changes of a webhook. Missing fields are not changed.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateWebhook{
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub active: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus{
+    /// Waiting to be sent, or to be sent again
+    Pending,
+    Delivered,
+    /// Given up after every attempt
+    Failed,
+}
+
+impl DeliveryStatus{
+    pub fn as_str(&self) -> &'static str{
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+/// An event sent, or to be sent, to a webhook
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery{
+    pub id: i32,
+    pub webhook_id: i32,
+    #[schema(example = "note.created")]
+    pub event: String,
+    pub payload: Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    /// Status of the last answer of the webhook
+    #[schema(example = 200)]
+    pub response_status: Option<i32>,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub delivered_at: Option<NaiveDateTime>,
+}
+
+/// A delivery to send now, with what is needed to send it
+pub struct DueDelivery{
+    pub id: i32,
+    pub url: String,
+    pub secret: String,
+    pub event: String,
+    pub workspace_id: i32,
+    pub payload: Value,
+    pub attempts: i32,
+    pub created_at: NaiveDateTime,
+}
+
+const COLUMNS: &str = "id, url, secret, events, active, created_at, updated_at";
+const DELIVERY_COLUMNS: &str = "id, webhook_id, event, payload, status, attempts, next_attempt_at, response_status, error, created_at, delivered_at";
+
+/// Check the url and the events of a webhook
+pub fn validate(url: Option<&str>, events: Option<&[String]>) -> Result<(), String>{
+    if let Some(url) = url {
+        match reqwest::Url::parse(url) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {},
+            _ => return Err(format!("Invalid url: {}", url)),
+        }
+    }
+    let unknown: Vec<&str> = events.unwrap_or_default().iter()
+        .map(String::as_str)
+        .filter(|event| !EVENTS.contains(event))
+        .collect();
+    match unknown.is_empty() {
+        true => Ok(()),
+        false => Err(format!("Unknown events: {}", unknown.join(", "))),
+    }
+}
+
+/// Whether the address is out of the server and its networks: not loopback,
+/// link-local, private, shared nor unspecified
+fn is_public(ip: IpAddr) -> bool{
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+                || ip.is_broadcast() || ip.is_documentation()
+                // Shared address space, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64))
+        },
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => !(ip.is_loopback() || ip.is_unspecified()
+                // Unique local, fc00::/7, and link-local, fe80::/10
+                || ip.segments()[0] & 0xfe00 == 0xfc00
+                || ip.segments()[0] & 0xffc0 == 0xfe80),
+        },
+    }
+}
+
+/// Resolve the host of the url and check that every address is public, so a
+/// webhook can not reach the server nor its network. Unless `allow_private`.
+pub async fn check_host(url: &str, allow_private: bool) -> Result<(), String>{
+    if allow_private {
+        return Ok(());
+    }
+    let parsed = reqwest::Url::parse(url).map_err(|_| format!("Invalid url: {}", url))?;
+    let port = parsed.port_or_known_default().unwrap_or(80);
+    let host = parsed.host_str().ok_or_else(|| format!("Invalid url: {}", url))?;
+    // IPv6 addresses come between brackets
+    let addresses: Vec<SocketAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| format!("Can not resolve {}: {}", host, e))?
+            .collect(),
+    };
+    match !addresses.is_empty() && addresses.iter().all(|address| is_public(address.ip())) {
+        true => Ok(()),
+        false => Err(format!("Not a public address: {}", url)),
+    }
+}
+
+fn secret() -> String{
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+impl Webhook{
+    fn from_row(row: PgRow) -> Webhook{
+        Webhook{
+            id: row.get("id"),
+            url: row.get("url"),
+            secret: row.get("secret"),
+            events: row.get("events"),
+            active: row.get("active"),
+            created_at: row.get("created_at"),
+            updated_at: row.get("updated_at"),
+        }
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Vec<Webhook>, Error>{
+        let sql = format!("SELECT {} FROM webhooks WHERE workspace_id = $1 ORDER BY id", COLUMNS);
+        query(&sql)
+            .bind(workspace_id)
+            .map(Webhook::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn get(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Webhook, Error>{
+        let sql = format!("SELECT {} FROM webhooks WHERE id = $1 AND workspace_id = $2", COLUMNS);
+        query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(Webhook::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn new(pool: &web::Data<PgPool>, webhook: NewWebhook, workspace_id: i32, user_id: i32) -> Result<Webhook, Error>{
+        let now = Utc::now().naive_utc();
+        let sql = format!("INSERT INTO webhooks (url, secret, events, workspace_id, user_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}", COLUMNS);
+        query(&sql)
+            .bind(webhook.url)
+            .bind(webhook.secret.filter(|secret| !secret.is_empty()).unwrap_or_else(secret))
+            .bind(webhook.events.unwrap_or_default())
+            .bind(workspace_id)
+            .bind(user_id)
+            .bind(now)
+            .bind(now)
+            .map(Webhook::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn update(pool: &web::Data<PgPool>, id: i32, webhook: UpdateWebhook, workspace_id: i32) -> Result<Webhook, Error>{
+        let sql = format!("UPDATE webhooks SET url = COALESCE($1, url), secret = COALESCE($2, secret), events = COALESCE($3, events), active = COALESCE($4, active), updated_at = $5 WHERE id = $6 AND workspace_id = $7 RETURNING {}", COLUMNS);
+        query(&sql)
+            .bind(webhook.url)
+            .bind(webhook.secret.filter(|secret| !secret.is_empty()))
+            .bind(webhook.events)
+            .bind(webhook.active)
+            .bind(Utc::now().naive_utc())
+            .bind(id)
+            .bind(workspace_id)
+            .map(Webhook::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn delete(pool: &web::Data<PgPool>, id: i32, workspace_id: i32) -> Result<Webhook, Error>{
+        let sql = format!("DELETE FROM webhooks WHERE id = $1 AND workspace_id = $2 RETURNING {}", COLUMNS);
+        query(&sql)
+            .bind(id)
+            .bind(workspace_id)
+            .map(Webhook::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+}
+
+impl WebhookDelivery{
+    fn from_row(row: PgRow) -> WebhookDelivery{
+        let status = match row.get::<String, _>("status").as_str() {
+            "delivered" => DeliveryStatus::Delivered,
+            "failed" => DeliveryStatus::Failed,
+            _ => DeliveryStatus::Pending,
+        };
+        WebhookDelivery{
+            id: row.get("id"),
+            webhook_id: row.get("webhook_id"),
+            event: row.get("event"),
+            payload: row.get("payload"),
+            status,
+            attempts: row.get("attempts"),
+            next_attempt_at: row.get("next_attempt_at"),
+            response_status: row.get("response_status"),
+            error: row.get("error"),
+            created_at: row.get("created_at"),
+            delivered_at: row.get("delivered_at"),
+        }
+    }
+
+    /// The last deliveries of the webhook, newest first
+    pub async fn all(pool: &web::Data<PgPool>, webhook_id: i32, workspace_id: i32, limit: i64) -> Result<Vec<WebhookDelivery>, Error>{
+        let sql = format!("SELECT {} FROM webhook_deliveries WHERE webhook_id = (SELECT id FROM webhooks WHERE id = $1 AND workspace_id = $2) ORDER BY id DESC LIMIT $3", DELIVERY_COLUMNS);
+        query(&sql)
+            .bind(webhook_id)
+            .bind(workspace_id)
+            .bind(limit)
+            .map(WebhookDelivery::from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    /// Queue the event for the active webhooks of its workspace that want it.
+    /// Internal events, out of [`EVENTS`], are never sent.
+    pub async fn enqueue(pool: &PgPool, event: &Event) -> Result<u64, Error>{
+        if !EVENTS.contains(&event.kind.as_str()) {
+            return Ok(0);
+        }
+        query(r#"INSERT INTO webhook_deliveries (webhook_id, event, payload, status, next_attempt_at, created_at)
+            SELECT id, $1, $2, 'pending', $3, $3 FROM webhooks
+            WHERE workspace_id = $4 AND active AND (cardinality(events) = 0 OR $1 = ANY(events))"#)
+            .bind(&event.kind)
+            .bind(&event.payload)
+            .bind(event.created_at)
+            .bind(event.workspace_id)
+            .execute(pool)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// Queue the delivery again, as a new one sent right now
+    pub async fn replay(pool: &web::Data<PgPool>, id: i32, webhook_id: i32, workspace_id: i32) -> Result<WebhookDelivery, Error>{
+        let sql = format!(r#"INSERT INTO webhook_deliveries (webhook_id, event, payload, status, next_attempt_at, created_at)
+            SELECT d.webhook_id, d.event, d.payload, 'pending', $4, $4 FROM webhook_deliveries d
+            INNER JOIN webhooks w ON w.id = d.webhook_id
+            WHERE d.id = $1 AND d.webhook_id = $2 AND w.workspace_id = $3
+            RETURNING {}"#, DELIVERY_COLUMNS);
+        query(&sql)
+            .bind(id)
+            .bind(webhook_id)
+            .bind(workspace_id)
+            .bind(Utc::now().naive_utc())
+            .map(WebhookDelivery::from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// Take the deliveries due at `now`. They are not due again until
+    /// `lease_until`, so nobody else sends them meanwhile.
+    pub async fn take_due(pool: &PgPool, now: NaiveDateTime, lease_until: NaiveDateTime, limit: i64) -> Result<Vec<DueDelivery>, Error>{
+        query(r#"UPDATE webhook_deliveries d SET next_attempt_at = $2
+            FROM webhooks w
+            WHERE w.id = d.webhook_id AND d.id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= $1
+                ORDER BY next_attempt_at LIMIT $3
+                FOR UPDATE SKIP LOCKED)
+            RETURNING d.id, w.url, w.secret, d.event, w.workspace_id, d.payload, d.attempts, d.created_at"#)
+            .bind(now)
+            .bind(lease_until)
+            .bind(limit)
+            .map(|row: PgRow| DueDelivery{
+                id: row.get("id"),
+                url: row.get("url"),
+                secret: row.get("secret"),
+                event: row.get("event"),
+                workspace_id: row.get("workspace_id"),
+                payload: row.get("payload"),
+                attempts: row.get("attempts"),
+                created_at: row.get("created_at"),
+            })
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Record an attempt. Without `next_attempt_at` a failed delivery is
+    /// given up.
+    pub async fn attempted(pool: &PgPool, id: i32, status: DeliveryStatus, response_status: Option<i32>, error: Option<String>, next_attempt_at: Option<NaiveDateTime>) -> Result<(), Error>{
+        let delivered_at = match status {
+            DeliveryStatus::Delivered => Some(Utc::now().naive_utc()),
+            _ => None,
+        };
+        query(r#"UPDATE webhook_deliveries SET status = $1, attempts = attempts + 1, response_status = $2, error = $3, next_attempt_at = $4, delivered_at = $5 WHERE id = $6"#)
+            .bind(status.as_str())
+            .bind(response_status)
+            .bind(error)
+            .bind(next_attempt_at)
+            .bind(delivered_at)
+            .bind(id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+    }
+}
+
+#[test]
+fn test_validate() {
+    let events = vec!["note.created".to_string(), "label.assigned".to_string()];
+    assert!(validate(Some("https://example.com/hook"), Some(&events)).is_ok());
+    assert!(validate(Some("ftp://example.com/hook"), None).is_err());
+    assert_eq!(validate(None, Some(&["note.read".to_string()])), Err("Unknown events: note.read".to_string()));
+}
+
+#[actix_web::test]
+async fn test_check_host() {
+    assert!(check_host("https://93.184.216.34/hook", false).await.is_ok());
+    for url in ["http://127.0.0.1:8080/hook", "http://169.254.169.254/latest", "http://10.0.0.7/",
+            "http://192.168.1.1/", "http://172.16.0.1/", "http://100.64.0.1/", "http://0.0.0.0/",
+            "http://[::1]/", "http://[fd00::1]/", "http://[fe80::1]/", "http://[::ffff:127.0.0.1]/",
+            "http://localhost:8080/hook"] {
+        assert!(check_host(url, false).await.is_err(), "{}", url);
+    }
+    assert!(check_host("http://127.0.0.1:8080/hook", true).await.is_ok());
+}
@@ -0,0 +1,399 @@
+use std::{fmt, str::FromStr};
+
+use actix_web::web;
+use chrono::{NaiveDateTime, Utc};
+use sqlx::{query, Error, Row, Postgres, Transaction, postgres::{PgPool, PgRow}};
+use serde::{Serialize, Deserialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role{
+    Owner,
+    Editor,
+    Viewer,
+}
+
+impl Role{
+    pub fn as_str(&self) -> &'static str{
+        match self {
+            Role::Owner => "owner",
+            Role::Editor => "editor",
+            Role::Viewer => "viewer",
+        }
+    }
+
+    pub fn can_write(&self) -> bool{
+        matches!(self, Role::Owner | Role::Editor)
+    }
+
+    pub fn can_manage(&self) -> bool{
+        matches!(self, Role::Owner)
+    }
+}
+
+impl fmt::Display for Role{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role{
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>{
+        match s {
+            "owner" => Ok(Role::Owner),
+            "editor" => Ok(Role::Editor),
+            "viewer" => Ok(Role::Viewer),
+            _ => Err(format!("Unknown role {}", s)),
+        }
+    }
+}
+
+/// A workspace as seen by one of its members
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Workspace{
+    #[schema(example = 1)]
+    pub id: i32,
+    #[schema(example = "Equipo")]
+    pub name: String,
+    pub personal: bool,
+    pub role: Role,
+    pub active: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewWorkspace{
+    #[schema(example = "Equipo")]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UpdateWorkspace{
+    pub id: i32,
+    #[schema(example = "Equipo")]
+    pub name: String,
+}
+
+/// The membership of a user in a workspace
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Member{
+    pub workspace_id: i32,
+    pub user_id: i32,
+    #[schema(example = "tucorreo@tuservidor.es")]
+    pub email: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewMember{
+    #[schema(example = "tucorreo@tuservidor.es")]
+    pub email: String,
+    pub role: Role,
+}
+
+fn role_from_row(row: &PgRow) -> Role{
+    row.get::<String, _>("role").parse().unwrap_or(Role::Viewer)
+}
+
+fn workspace_from_row(row: PgRow) -> Workspace{
+    Workspace{
+        id: row.get("id"),
+        name: row.get("name"),
+        personal: row.get("personal"),
+        role: role_from_row(&row),
+        active: row.get("active"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+fn member_from_row(row: PgRow) -> Member{
+    Member{
+        workspace_id: row.get("workspace_id"),
+        user_id: row.get("user_id"),
+        email: row.get("email"),
+        role: role_from_row(&row),
+    }
+}
+
+/// Why a change of the members of a workspace failed
+#[derive(Debug)]
+pub enum MemberError{
+    Sql(Error),
+    /// The change would leave the workspace without its creator or any owner
+    Owner,
+}
+
+impl From<Error> for MemberError{
+    fn from(error: Error) -> Self{
+        MemberError::Sql(error)
+    }
+}
+
+/// Whether the workspace keeps its creator and an owner after giving `role`
+/// to `user_id`, or removing it when `role` is `None`
+fn keeps_owner(creator: i32, owners: &[i32], user_id: i32, role: Option<Role>) -> bool{
+    if role == Some(Role::Owner){
+        return true;
+    }
+    user_id != creator && owners.iter().any(|id| *id != user_id)
+}
+
+const WORKSPACE_SELECT: &str = r#"SELECT w.id, w.name, w.personal, wu.role,
+    COALESCE(u.workspace_id = w.id, FALSE) AS active, w.created_at, w.updated_at
+    FROM workspaces w
+    INNER JOIN workspaces_users wu ON wu.workspace_id = w.id
+    INNER JOIN users u ON u.id = wu.user_id
+    "#;
+
+impl Workspace{
+    pub async fn all(pool: &web::Data<PgPool>, user_id: i32) -> Result<Vec<Workspace>, Error>{
+        let sql = format!("{} WHERE wu.user_id = $1 ORDER BY w.personal DESC, w.name", WORKSPACE_SELECT);
+        query(&sql)
+            .bind(user_id)
+            .map(workspace_from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    pub async fn get(pool: &web::Data<PgPool>, id: i32, user_id: i32) -> Result<Workspace, Error>{
+        let sql = format!("{} WHERE w.id = $1 AND wu.user_id = $2", WORKSPACE_SELECT);
+        query(&sql)
+            .bind(id)
+            .bind(user_id)
+            .map(workspace_from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    /// Create a workspace owned by `user_id`
+    pub async fn new(pool: &web::Data<PgPool>, name: &str, personal: bool, user_id: i32) -> Result<Workspace, Error>{
+        let mut tx = pool.begin().await?;
+        let id = Workspace::insert(&mut tx, name, personal, user_id).await?;
+        tx.commit().await?;
+        Workspace::get(pool, id, user_id).await
+    }
+
+    /// Insert the workspace and its owner within `tx`, returning its id
+    pub async fn insert(tx: &mut Transaction<'_, Postgres>, name: &str, personal: bool, user_id: i32) -> Result<i32, Error>{
+        let created_at = Utc::now().naive_utc();
+        let updated_at = Utc::now().naive_utc();
+        let id: i32 = query(r#"INSERT INTO workspaces (name, personal, user_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5) RETURNING id;"#)
+            .bind(name)
+            .bind(personal)
+            .bind(user_id)
+            .bind(created_at)
+            .bind(updated_at)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_one(&mut *tx)
+            .await?;
+        query(r#"INSERT INTO workspaces_users (workspace_id, user_id, role) VALUES ($1, $2, $3);"#)
+            .bind(id)
+            .bind(user_id)
+            .bind(Role::Owner.as_str())
+            .execute(&mut *tx)
+            .await?;
+        if personal {
+            query(r#"UPDATE users SET workspace_id = $1 WHERE id = $2 AND workspace_id IS NULL;"#)
+                .bind(id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        Ok(id)
+    }
+
+    pub async fn update(pool: &web::Data<PgPool>, workspace: UpdateWorkspace, user_id: i32) -> Result<Workspace, Error>{
+        let updated_at = Utc::now().naive_utc();
+        query(r#"UPDATE workspaces SET name = $1, updated_at = $2 WHERE id = $3 RETURNING id;"#)
+            .bind(&workspace.name)
+            .bind(updated_at)
+            .bind(workspace.id)
+            .fetch_one(pool.get_ref())
+            .await?;
+        Workspace::get(pool, workspace.id, user_id).await
+    }
+
+    /// Delete a shared workspace and everything it owns
+    pub async fn delete(pool: &web::Data<PgPool>, id: i32, user_id: i32) -> Result<Workspace, Error>{
+        let workspace = Workspace::get(pool, id, user_id).await?;
+        let mut tx = pool.begin().await?;
+        query(r#"DELETE FROM notes_labels WHERE note_id IN (SELECT id FROM notes WHERE workspace_id = $1);"#)
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        query(r#"DELETE FROM notes_categories WHERE note_id IN (SELECT id FROM notes WHERE workspace_id = $1);"#)
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        for sql in [
+            r#"DELETE FROM notes WHERE workspace_id = $1;"#,
+            r#"DELETE FROM templates WHERE workspace_id = $1;"#,
+            r#"DELETE FROM labels WHERE workspace_id = $1;"#,
+            r#"DELETE FROM categories WHERE workspace_id = $1;"#,
+            r#"DELETE FROM workspaces_users WHERE workspace_id = $1;"#,
+            r#"UPDATE users u SET workspace_id = w.id FROM workspaces w WHERE w.personal AND w.user_id = u.id AND u.workspace_id = $1;"#,
+            r#"DELETE FROM workspaces WHERE id = $1;"#,
+        ] {
+            query(sql)
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(workspace)
+    }
+
+    /// Make `id` the workspace the notes, labels and categories endpoints
+    /// operate on for `user_id`
+    pub async fn activate(pool: &web::Data<PgPool>, id: i32, user_id: i32) -> Result<Workspace, Error>{
+        query(r#"UPDATE users u SET workspace_id = wu.workspace_id FROM workspaces_users wu WHERE wu.workspace_id = $1 AND wu.user_id = u.id AND u.id = $2 RETURNING u.id;"#)
+            .bind(id)
+            .bind(user_id)
+            .fetch_one(pool.get_ref())
+            .await?;
+        Workspace::get(pool, id, user_id).await
+    }
+}
+
+impl Member{
+    /// The membership the user is working with right now.
+    ///
+    /// This is the active workspace, falling back to the personal workspace
+    /// when the user is no longer a member of the active one.
+    pub async fn active(pool: &web::Data<PgPool>, user_id: i32) -> Result<Member, Error>{
+        query(r#"SELECT wu.workspace_id, wu.user_id, u.email, wu.role
+            FROM users u
+            INNER JOIN workspaces_users wu ON wu.user_id = u.id
+            INNER JOIN workspaces w ON w.id = wu.workspace_id
+            WHERE u.id = $1
+            ORDER BY COALESCE(u.workspace_id = w.id, FALSE) DESC, w.personal DESC, w.id
+            LIMIT 1"#)
+            .bind(user_id)
+            .map(member_from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn get(pool: &web::Data<PgPool>, workspace_id: i32, user_id: i32) -> Result<Member, Error>{
+        query(r#"SELECT wu.workspace_id, wu.user_id, u.email, wu.role
+            FROM workspaces_users wu
+            INNER JOIN users u ON u.id = wu.user_id
+            WHERE wu.workspace_id = $1 AND wu.user_id = $2"#)
+            .bind(workspace_id)
+            .bind(user_id)
+            .map(member_from_row)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+
+    pub async fn all(pool: &web::Data<PgPool>, workspace_id: i32) -> Result<Vec<Member>, Error>{
+        query(r#"SELECT wu.workspace_id, wu.user_id, u.email, wu.role
+            FROM workspaces_users wu
+            INNER JOIN users u ON u.id = wu.user_id
+            WHERE wu.workspace_id = $1
+            ORDER BY u.email"#)
+            .bind(workspace_id)
+            .map(member_from_row)
+            .fetch_all(pool.get_ref())
+            .await
+    }
+
+    /// Add the user with `email` to the workspace or change its role
+    pub async fn set(pool: &web::Data<PgPool>, workspace_id: i32, member: NewMember) -> Result<Member, MemberError>{
+        let mut tx = pool.begin().await?;
+        let user_id: i32 = query(r#"SELECT id FROM users WHERE email = $1"#)
+            .bind(&member.email)
+            .map(|row: PgRow| row.get("id"))
+            .fetch_one(&mut tx)
+            .await?;
+        Member::check_owners(&mut tx, workspace_id, user_id, Some(member.role)).await?;
+        let member = query(r#"INSERT INTO workspaces_users (workspace_id, user_id, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (workspace_id, user_id) DO UPDATE SET role = EXCLUDED.role
+            RETURNING workspace_id, user_id, $4 AS email, role"#)
+            .bind(workspace_id)
+            .bind(user_id)
+            .bind(member.role.as_str())
+            .bind(&member.email)
+            .map(member_from_row)
+            .fetch_one(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(member)
+    }
+
+    pub async fn delete(pool: &web::Data<PgPool>, workspace_id: i32, user_id: i32) -> Result<Member, MemberError>{
+        let mut tx = pool.begin().await?;
+        Member::check_owners(&mut tx, workspace_id, user_id, None).await?;
+        let member = query(r#"DELETE FROM workspaces_users wu USING users u
+            WHERE wu.workspace_id = $1 AND wu.user_id = $2 AND u.id = wu.user_id
+            RETURNING wu.workspace_id, wu.user_id, u.email, wu.role"#)
+            .bind(workspace_id)
+            .bind(user_id)
+            .map(member_from_row)
+            .fetch_one(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(member)
+    }
+
+    /// Lock the workspace and refuse giving `role` to `user_id` (`None`
+    /// removes it) when the workspace would lose its creator or its last owner
+    async fn check_owners(tx: &mut Transaction<'_, Postgres>, workspace_id: i32, user_id: i32, role: Option<Role>) -> Result<(), MemberError>{
+        let creator: i32 = query(r#"SELECT user_id FROM workspaces WHERE id = $1 FOR UPDATE"#)
+            .bind(workspace_id)
+            .map(|row: PgRow| row.get("user_id"))
+            .fetch_one(&mut *tx)
+            .await?;
+        let owners: Vec<i32> = query(r#"SELECT user_id FROM workspaces_users WHERE workspace_id = $1 AND role = $2"#)
+            .bind(workspace_id)
+            .bind(Role::Owner.as_str())
+            .map(|row: PgRow| row.get("user_id"))
+            .fetch_all(&mut *tx)
+            .await?;
+        if keeps_owner(creator, &owners, user_id, role){
+            Ok(())
+        }else{
+            Err(MemberError::Owner)
+        }
+    }
+
+    pub fn can_write(&self) -> bool{
+        self.role.can_write()
+    }
+
+    pub fn can_manage(&self) -> bool{
+        self.role.can_manage()
+    }
+}
+
+#[test]
+fn test_role() {
+    assert_eq!("editor".parse::<Role>(), Ok(Role::Editor));
+    assert!("admin".parse::<Role>().is_err());
+    assert!(Role::Owner.can_manage() && Role::Owner.can_write());
+    assert!(Role::Editor.can_write() && !Role::Editor.can_manage());
+    assert!(!Role::Viewer.can_write());
+    assert_eq!(serde_json::to_string(&Role::Viewer).unwrap(), "\"viewer\"");
+}
+
+#[test]
+fn test_keeps_owner() {
+    // The creator 1 and a second owner 2, 3 is an editor
+    let owners = [1, 2];
+    assert!(keeps_owner(1, &owners, 2, Some(Role::Editor)));
+    assert!(keeps_owner(1, &owners, 2, None));
+    assert!(keeps_owner(1, &owners, 3, None));
+    assert!(keeps_owner(1, &owners, 3, Some(Role::Owner)));
+    assert!(!keeps_owner(1, &owners, 1, Some(Role::Viewer)));
+    assert!(!keeps_owner(1, &owners, 1, None));
+    // Without the creator among the members, the last owner stays
+    assert!(!keeps_owner(1, &[2], 2, Some(Role::Editor)));
+    assert!(!keeps_owner(1, &[2], 2, None));
+    assert!(keeps_owner(1, &[2, 3], 2, None));
+}
@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+    message::Mailbox, transport::smtp::authentication::Credentials};
+
+use crate::{config::Smtp, model::reminder::DueReminder};
+use super::Channel;
+
+/// Send reminders by email to the author of the note.
+///
+/// Configured by the `smtp` settings. Without TLS it talks plain SMTP, which
+/// is what local sinks like MailHog expect.
+pub struct EmailChannel{
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailChannel{
+    pub fn new(smtp: &Smtp) -> anyhow::Result<Option<Self>>{
+        let host = &smtp.host;
+        if host.is_empty() {
+            return Ok(None);
+        }
+        let from = match smtp.from.is_empty() {
+            true => format!("notisbak@{}", host),
+            false => smtp.from.clone(),
+        }.parse()?;
+        let mut builder = if smtp.tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        }else{
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        }.port(smtp.port);
+        if !smtp.user.is_empty() {
+            builder = builder.credentials(Credentials::new(smtp.user.clone(), smtp.password.clone()));
+        }
+        Ok(Some(Self{
+            mailer: builder.build(),
+            from,
+        }))
+    }
+}
+
+#[async_trait]
+impl Channel for EmailChannel{
+    fn name(&self) -> &'static str{
+        "email"
+    }
+
+    async fn send(&self, reminder: &DueReminder) -> anyhow::Result<()>{
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(reminder.email.parse()?)
+            .subject(format!("Recordatorio: {}", reminder.title))
+            .body(format!("{}\n\n{}", reminder.title, reminder.body))?;
+        self.mailer.send(message).await?;
+        Ok(())
+    }
+}
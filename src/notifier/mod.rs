@@ -0,0 +1,31 @@
+pub mod email;
+pub mod scheduler;
+pub mod stream;
+pub mod webhook;
+
+use async_trait::async_trait;
+
+use crate::{config::Config, model::reminder::DueReminder};
+
+/// A way to tell a user that one of its reminders is due
+#[async_trait]
+pub trait Channel: Send + Sync{
+    fn name(&self) -> &'static str;
+
+    async fn send(&self, reminder: &DueReminder) -> anyhow::Result<()>;
+}
+
+/// The channels enabled by the configuration. The realtime stream is always
+/// on.
+pub fn channels(config: &Config) -> Vec<Box<dyn Channel>>{
+    let mut channels: Vec<Box<dyn Channel>> = vec![Box::new(stream::StreamChannel)];
+    match email::EmailChannel::new(&config.smtp) {
+        Ok(Some(channel)) => channels.push(Box::new(channel)),
+        Ok(None) => {},
+        Err(e) => log::error!("Email reminders disabled: {}", e),
+    }
+    if let Some(channel) = webhook::WebhookChannel::new(&config.reminders.webhook_url) {
+        channels.push(Box::new(channel));
+    }
+    channels
+}
@@ -0,0 +1,45 @@
+use std::time::Duration;
+
+use actix_web::rt;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::model::reminder::Reminder;
+use super::Channel;
+
+/// Look for due reminders every `period` and send them through `channels`
+pub fn start(pool: PgPool, channels: Vec<Box<dyn Channel>>, period: Duration){
+    let names: Vec<&str> = channels.iter().map(|channel| channel.name()).collect();
+    log::info!("Reminders every {:?} through {}", period, names.join(", "));
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(period);
+        loop {
+            interval.tick().await;
+            let now = Utc::now().naive_utc();
+            let due = match Reminder::due(&pool, now).await {
+                Ok(due) => due,
+                Err(e) => {
+                    log::error!("Can not read due reminders: {}", e);
+                    continue;
+                },
+            };
+            // One by one, so a reminder that fails does not hold the rest
+            for note_id in due {
+                let reminder = match Reminder::take(&pool, note_id, now).await {
+                    Ok(Some(reminder)) => reminder,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log::error!("Can not take the reminder of note {}: {}", note_id, e);
+                        continue;
+                    },
+                };
+                for channel in channels.iter() {
+                    if let Err(e) = channel.send(&reminder).await {
+                        log::error!("Reminder {} not sent by {}: {}",
+                            reminder.note_id, channel.name(), e);
+                    }
+                }
+            }
+        }
+    });
+}
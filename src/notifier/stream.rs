@@ -0,0 +1,32 @@
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::events::{self, Event};
+use crate::model::reminder::DueReminder;
+use super::Channel;
+
+/// Publish reminders in the realtime event stream, only to the user they
+/// are for
+pub struct StreamChannel;
+
+#[async_trait]
+impl Channel for StreamChannel{
+    fn name(&self) -> &'static str{
+        "stream"
+    }
+
+    async fn send(&self, reminder: &DueReminder) -> anyhow::Result<()>{
+        events::publish(Event::for_user(
+            "reminder.due",
+            reminder.workspace_id,
+            reminder.user_id,
+            json!({
+                "note_id": reminder.note_id,
+                "title": reminder.title,
+                "remind_at": reminder.remind_at,
+                "recurrence": reminder.recurrence,
+            }),
+        ));
+        Ok(())
+    }
+}
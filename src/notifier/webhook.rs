@@ -0,0 +1,37 @@
+use async_trait::async_trait;
+
+use crate::model::reminder::DueReminder;
+use super::Channel;
+
+/// POST every reminder as JSON to `reminders.webhook_url`
+pub struct WebhookChannel{
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookChannel{
+    pub fn new(url: &str) -> Option<Self>{
+        Some(url)
+            .filter(|url| !url.is_empty())
+            .map(|url| Self{
+                client: reqwest::Client::new(),
+                url: url.to_string(),
+            })
+    }
+}
+
+#[async_trait]
+impl Channel for WebhookChannel{
+    fn name(&self) -> &'static str{
+        "webhook"
+    }
+
+    async fn send(&self, reminder: &DueReminder) -> anyhow::Result<()>{
+        self.client.post(&self.url)
+            .json(reminder)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! The OpenAPI document of the REST API, served at `/api-doc/openapi.json`
+use utoipa::{Modify, OpenApi, openapi::{KnownFormat, ObjectBuilder,
+    SchemaFormat, SchemaType, security::{Http, HttpAuthScheme, HttpBuilder,
+    SecurityRequirement, SecurityScheme}}};
+
+use actix_web::{get, HttpResponse};
+
+use crate::{exporter, importer, model, routes};
+
+#[derive(OpenApi)]
+#[openapi(
+    modifiers(&SecurityAddon, &ExternalTypes),
+    paths(
+        routes::labels::create_label,
+        routes::labels::read_label,
+        routes::labels::read_labels,
+        routes::labels::update_label,
+        routes::labels::delete_label,
+        routes::categories::create_category,
+        routes::categories::read_category,
+        routes::categories::read_categories,
+        routes::categories::update_category,
+        routes::categories::delete_category,
+        routes::notes::create_note,
+        routes::notes::read_note,
+        routes::notes::read_note_markdown,
+        routes::notes::read_notes,
+        routes::notes::read_labels_for_note,
+        routes::notes::read_categories_for_note,
+        routes::notes::update_note,
+        routes::notes::delete_note,
+        routes::notes::add_label_to_note,
+        routes::notes::add_category_to_note,
+        routes::notes::delete_label_from_note,
+        routes::notes::delete_category_from_note,
+        routes::note_items::read_items,
+        routes::note_items::create_item,
+        routes::note_items::update_item,
+        routes::note_items::reorder_items,
+        routes::note_items::check_item,
+        routes::note_items::uncheck_item,
+        routes::note_items::delete_item,
+        routes::note_links::read_links,
+        routes::note_links::read_backlinks,
+        routes::note_links::read_graph,
+        routes::templates::create_template,
+        routes::templates::read_template,
+        routes::templates::read_templates,
+        routes::templates::update_template,
+        routes::templates::delete_template,
+        routes::templates::create_note_from_template,
+        routes::attachments::read_attachments,
+        routes::attachments::read_attachment,
+        routes::imports::import_archive,
+        routes::imports::import_keep,
+        routes::imports::import_markdown,
+        routes::imports::import_enex,
+        routes::imports::import_joplin,
+        routes::imports::read_import_jobs,
+        routes::imports::read_import_job,
+        routes::exports::export_archive,
+        routes::exports::export_markdown,
+        routes::workspaces::create_workspace,
+        routes::workspaces::read_workspace,
+        routes::workspaces::read_workspaces,
+        routes::workspaces::update_workspace,
+        routes::workspaces::delete_workspace,
+        routes::workspaces::activate_workspace,
+        routes::workspaces::read_members,
+        routes::workspaces::set_member,
+        routes::workspaces::delete_member,
+        routes::reminders::read_reminders,
+        routes::reminders::set_reminder,
+        routes::reminders::delete_reminder,
+        routes::reminders::snooze_reminder,
+        routes::reminders::dismiss_reminder,
+        routes::events::read_events,
+        routes::app_tokens::create_token,
+        routes::app_tokens::read_tokens,
+        routes::app_tokens::delete_token,
+        routes::inbox::read_inbox,
+        routes::inbox::renew_inbox,
+        routes::webhooks::create_webhook,
+        routes::webhooks::read_webhooks,
+        routes::webhooks::read_webhook,
+        routes::webhooks::update_webhook,
+        routes::webhooks::delete_webhook,
+        routes::webhooks::read_deliveries,
+        routes::webhooks::replay_delivery,
+        routes::feeds::create_feed,
+        routes::feeds::read_feeds,
+        routes::feeds::delete_feed,
+        routes::feeds::read_feed,
+        routes::inbox::receive_mail,
+        routes::graphql::execute,
+        routes::graphql::read_schema,
+        routes::notes::root,
+        routes::users::login,
+        routes::users::register,
+        routes::users::login_with_app_token,
+        routes::health::healthz,
+        routes::health::readyz,
+        routes::health::read_metrics,
+    ),
+    components(
+        schemas(model::label::Label,
+                model::label::NewLabel,
+                model::category::Category,
+                model::category::NewCategory,
+                model::note::Note,
+                model::note::NewNote,
+                model::note::UpdateNote,
+                model::note::NoteDetail,
+                model::note::NoteKind,
+                model::note::Progress,
+                model::note_label::NoteLabel,
+                model::note_category::NoteCategory,
+                model::note_item::NoteItem,
+                model::note_item::NewNoteItem,
+                model::note_item::UpdateNoteItem,
+                model::note_item::ItemsOrder,
+                model::note_link::LinkedNote,
+                model::note_link::Edge,
+                model::note_link::Graph,
+                model::template::Template,
+                model::template::NewTemplate,
+                model::template::UpdateTemplate,
+                model::template::TemplateValues,
+                model::attachment::Attachment,
+                importer::Report,
+                importer::Failure,
+                model::import_job::ImportJob,
+                model::import_job::JobStatus,
+                importer::archive::Strategy,
+                importer::archive::Restored,
+                exporter::archive::Archive,
+                exporter::archive::ArchivedNote,
+                exporter::archive::ArchivedItem,
+                exporter::archive::ArchivedAttachment,
+                routes::exports::Format,
+                model::workspace::Role,
+                model::workspace::Workspace,
+                model::workspace::NewWorkspace,
+                model::workspace::UpdateWorkspace,
+                model::workspace::Member,
+                model::workspace::NewMember,
+                model::reminder::Reminder,
+                model::reminder::NewReminder,
+                model::reminder::Snooze,
+                model::app_token::AppToken,
+                model::app_token::NewAppToken,
+                model::app_token::CreatedAppToken,
+                model::inbox::Inbox,
+                model::webhook::Webhook,
+                model::webhook::NewWebhook,
+                model::webhook::UpdateWebhook,
+                model::webhook::WebhookDelivery,
+                model::webhook::DeliveryStatus,
+                model::feed::Feed,
+                model::feed::NewFeed,
+                model::feed::FeedSource,
+                model::user::Credentials,
+                routes::users::AuthResponse)
+    ),
+)]
+pub struct ApiDoc;
+
+/// The OpenAPI document, also without the Swagger UI
+#[get("/api-doc/openapi.json")]
+pub async fn read_openapi() -> HttpResponse{
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+/// Bearer JWT for every path but those that say otherwise, like the login
+struct SecurityAddon;
+
+impl Modify for SecurityAddon{
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi){
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_jwt_token",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "mail_pipe_secret",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
+        );
+        openapi.security = Some(vec![SecurityRequirement::new("api_jwt_token", Vec::<String>::new())]);
+    }
+}
+
+/// Types of chrono and serde_json in the models, referenced by name but
+/// without a schema of their own
+struct ExternalTypes;
+
+impl Modify for ExternalTypes{
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi){
+        let schemas = &mut openapi.components.get_or_insert_with(Default::default).schemas;
+        schemas.insert(
+            "NaiveDateTime".to_string(),
+            ObjectBuilder::new()
+                .schema_type(SchemaType::String)
+                .format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime)))
+                .description(Some("In UTC, without offset"))
+                .example(Some(serde_json::json!("2022-11-16T18:00:00")))
+                .into(),
+        );
+        schemas.insert(
+            "Value".to_string(),
+            ObjectBuilder::new()
+                .description(Some("A JSON object"))
+                .into(),
+        );
+    }
+}
+
+#[test]
+fn test_openapi() {
+    use serde_json::Value;
+
+    let json = ApiDoc::openapi().to_json().unwrap();
+    let spec: openapiv3::OpenAPI = serde_json::from_str(&json).expect("Not a valid OpenAPI 3 document");
+    let components = spec.components.as_ref().unwrap();
+    assert!(components.security_schemes.contains_key("api_jwt_token"));
+    assert!(spec.paths.paths.contains_key("/auth/login"));
+    assert!(spec.paths.paths.contains_key("/auth/register"));
+    assert!(spec.paths.paths.contains_key("/api/v1/"));
+    for schema in ["NoteLabel", "NoteCategory", "NoteDetail"] {
+        assert!(components.schemas.contains_key(schema), "{} not documented", schema);
+    }
+    // Every reference points to a documented schema
+    fn refs(value: &Value, found: &mut Vec<String>){
+        match value {
+            Value::Object(map) => map.iter().for_each(|(key, value)| match (key.as_str(), value) {
+                ("$ref", Value::String(reference)) => found.push(reference.clone()),
+                _ => refs(value, found),
+            }),
+            Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+            _ => {},
+        }
+    }
+    let mut found = Vec::new();
+    refs(&serde_json::from_str(&json).unwrap(), &mut found);
+    for reference in found {
+        let name = reference.trim_start_matches("#/components/schemas/");
+        assert!(components.schemas.contains_key(name), "{} not documented", reference);
+    }
+    // Every path under the token says what happens without it
+    for (path, item) in spec.paths.iter() {
+        for (method, operation) in item.as_item().unwrap().iter() {
+            let public = operation.security.as_ref().is_some_and(|security| security.iter().all(|requirement| requirement.is_empty()));
+            if !public {
+                assert!(operation.responses.responses.contains_key(&openapiv3::StatusCode::Code(401)),
+                    "{} {} does not document 401", method, path);
+            }
+        }
+    }
+}
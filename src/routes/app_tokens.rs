@@ -0,0 +1,90 @@
+use actix_web::{get, post, delete, web, error::{ErrorNotFound, ErrorConflict,
+    ErrorUnauthorized}, Error, HttpResponse};
+use actix_web_httpauth::{extractors::{AuthenticationError, basic::BasicAuth,
+    bearer::BearerAuth}, headers::www_authenticate::basic::Basic};
+use anyhow::Result;
+use sqlx::PgPool;
+use crate::model::{app_token::{AppToken, NewAppToken}, claims::Claims};
+
+/// The id of the user of the basic auth `credentials`: the email of the
+/// user and one of its app tokens
+pub async fn authenticate(pool: &web::Data<PgPool>, credentials: &BasicAuth) -> Result<i32, Error>{
+    let token = credentials.password().unwrap_or_default();
+    AppToken::authenticate(pool, credentials.user_id(), token)
+        .await
+        .map_err(|_| AuthenticationError::new(Basic::with_realm("notisbak")).into())
+}
+
+/// Create an app token
+///
+/// The token is only answered now. Clients that use basic auth, like the
+/// ones of the Nextcloud Notes API, take the email of the user and the
+/// token as password.
+#[utoipa::path(
+    context_path = "/api",
+    request_body = NewAppToken,
+    responses(
+        (status = 201, description = "Created successfully", body = CreatedAppToken),
+        (status = 401, description = "Error: Unauthorized"),
+    ),
+    tag = "tokens",
+)]
+#[post("/v1/tokens")]
+pub async fn create_token(pool: web::Data<PgPool>, token: web::Json<NewAppToken>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            AppToken::create(&pool, &token.name, user_id)
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorConflict)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "App tokens of the user", body = [AppToken]),
+        (status = 401, description = "Error: Unauthorized"),
+    ),
+    tag = "tokens",
+)]
+#[get("/v1/tokens")]
+pub async fn read_tokens(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            AppToken::all(&pool, user_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Revoke an app token
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the app token"),
+    ),
+    responses(
+        (status = 200, description = "Deleted successfully", body = AppToken),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "tokens",
+)]
+#[delete("/v1/tokens/{id}")]
+pub async fn delete_token(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            AppToken::delete(&pool, path.into_inner(), user_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -0,0 +1,70 @@
+use actix_web::{get, web, error::{ErrorNotFound, ErrorUnauthorized}, Error,
+    HttpResponse, http::header::{ContentDisposition, DispositionParam,
+    DispositionType}};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use sqlx::PgPool;
+use crate::model::{attachment::Attachment, claims::Claims};
+use crate::routes::workspaces::reader;
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
+    responses(
+        (status = 200, description = "Attachments of the note", body = [Attachment]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "notes",
+)]
+#[get("/v1/notes/{id}/attachments")]
+pub async fn read_attachments(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            Attachment::all(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Download an attachment
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("note_id", description = "The id of the note"),
+        ("attachment_id", description = "The id of the attachment"),
+    ),
+    responses(
+        (status = 200, description = "The content of the attachment", content_type = "application/octet-stream"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[get("/v1/notes/{note_id}/attachments/{attachment_id}")]
+pub async fn read_attachment(pool: web::Data<PgPool>, path: web::Path<(i32, i32)>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            let (note_id, attachment_id) = path.into_inner();
+            let (attachment, data) = Attachment::get(&pool, note_id, attachment_id, member.workspace_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            Ok(HttpResponse::Ok()
+                .content_type(attachment.mime_type)
+                .insert_header(ContentDisposition{
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(attachment.filename)],
+                })
+                .body(data))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -4,13 +4,15 @@ use actix_web_httpauth::extractors::bearer::BearerAuth;
 use anyhow::Result;
 use sqlx::PgPool;
 use crate::model::{category::{Category, NewCategory}, claims::Claims};
+use crate::routes::workspaces::{reader, writer};
 
 #[utoipa::path(
     context_path = "/api",
     request_body = NewCategory,
     responses(
         (status = 201, description = "Created successfully", body = Category),
-        (status = 403, description = "Error: Unauthorized"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 409, description = "Error: Conflict"),
     ),
     tag = "categories",
@@ -19,11 +21,12 @@ use crate::model::{category::{Category, NewCategory}, claims::Claims};
 pub async fn create_category(pool: web::Data<PgPool>, category: web::Json<NewCategory>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
             let name = category.into_inner().name;
-            Category::new(pool, &name, user_id)
+            Category::new(pool, &name, member.workspace_id, user_id)
                 .await
                 .map(|item| HttpResponse::Ok().json(item))
-                .map_err(|e| ErrorConflict(e))
+                .map_err(ErrorConflict)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -36,8 +39,9 @@ pub async fn create_category(pool: web::Data<PgPool>, category: web::Json<NewCat
     ),
     responses(
         (status = 200, description = "Get One", body = Category),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "categories",
 )]
@@ -45,11 +49,12 @@ pub async fn create_category(pool: web::Data<PgPool>, category: web::Json<NewCat
 pub async fn read_category(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, actix_web::Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
             let id = path.into_inner();
-            Category::get(pool, id, user_id)
+            Category::get(pool, id, member.workspace_id)
                 .await
                 .map(|item| HttpResponse::Ok().json(item))
-                .map_err(|e| ErrorNotFound(e))
+                .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -59,8 +64,9 @@ pub async fn read_category(pool: web::Data<PgPool>, path: web::Path<i32>, creden
     context_path = "/api",
     responses(
         (status = 200, description = "List all", body = [Category]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "categories",
 )]
@@ -68,10 +74,11 @@ pub async fn read_category(pool: web::Data<PgPool>, path: web::Path<i32>, creden
 pub async fn read_categories(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
-            Category::all(pool, user_id)
+            let member = reader(&pool, user_id).await?;
+            Category::all(pool, member.workspace_id)
                .await
                .map(|items| HttpResponse::Ok().json(items))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -82,19 +89,21 @@ pub async fn read_categories(pool: web::Data<PgPool>, credentials: BearerAuth) -
     request_body = Category,
     responses(
         (status = 201, description = "Updated successfully", body = Category),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "categories",
 )]
 #[put("/v1/categories")]
 pub async fn update_category(pool: web::Data<PgPool>, category: web::Json<Category>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
-        Ok(_user_id) => {
-            Category::update(pool, category.into_inner())
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Category::update(pool, category.into_inner(), member.workspace_id)
                .await
                .map(|item| HttpResponse::Ok().json(item))
-               .map_err(|e| ErrorConflict(e))
+               .map_err(ErrorConflict)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -107,20 +116,22 @@ pub async fn update_category(pool: web::Data<PgPool>, category: web::Json<Catego
     ),
     responses(
         (status = 201, description = "Deleted successfully", body = Category),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "categories",
 )]
 #[delete("/v1/categories/{id}")]
 pub async fn delete_category(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
-        Ok(_user_id) => {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
             let id = path.into_inner();
-            Category::delete(pool, id)
+            Category::delete(pool, id, member.workspace_id)
                .await
                .map(|item| HttpResponse::Ok().json(item))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -0,0 +1,49 @@
+use actix_web::{get, web, error::ErrorUnauthorized, Error, HttpResponse, web::Bytes};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use futures_util::stream;
+use sqlx::PgPool;
+use tokio::sync::broadcast::error::RecvError;
+use crate::events;
+use crate::model::claims::Claims;
+use crate::routes::workspaces::reader;
+
+/// Realtime events of the active workspace
+///
+/// A `text/event-stream` with one server sent event per change
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Stream of events", content_type = "text/event-stream"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "events",
+)]
+#[get("/v1/events")]
+pub async fn read_events(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let workspace_id = reader(&pool, user_id).await?.workspace_id;
+            let receiver = events::subscribe();
+            let stream = stream::unfold(receiver, move |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if event.is_for(workspace_id, user_id) => {
+                            let data = serde_json::to_string(&event).unwrap_or_default();
+                            let frame = format!("event: {}\ndata: {}\n\n", event.kind, data);
+                            return Some((Ok::<_, Error>(Bytes::from(frame)), receiver));
+                        },
+                        Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            });
+            Ok(HttpResponse::Ok()
+                .content_type("text/event-stream")
+                .insert_header(("Cache-Control", "no-cache"))
+                .streaming(stream))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -0,0 +1,134 @@
+use actix_web::{get, web, error::{ErrorInternalServerError, ErrorUnauthorized},
+    Error, HttpResponse, http::header::{ContentDisposition, DispositionParam,
+    DispositionType}, web::Bytes};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use chrono::Utc;
+use futures_util::{future, stream, StreamExt};
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+use crate::exporter::{self, archive, markdown};
+use crate::model::claims::Claims;
+use crate::routes::workspaces::reader;
+
+/// Export the notes of the workspace as markdown files
+///
+/// Each note is a file with YAML front matter for its title, labels,
+/// categories and dates, in the folder of its first category. Trashed notes
+/// are left out.
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Zip with a markdown file per note", content_type = "application/zip"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "export",
+)]
+#[get("/v1/export/markdown")]
+pub async fn export_markdown(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            let notes = exporter::notes(&pool, member.workspace_id)
+                .await
+                .map_err(ErrorInternalServerError)?;
+            let data = web::block(move || markdown::write(&notes))
+                .await
+                .map_err(ErrorInternalServerError)?
+                .map_err(ErrorInternalServerError)?;
+            Ok(HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header(ContentDisposition{
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename("notisbak.zip".to_string())],
+                })
+                .body(data))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format{
+    /// A single JSON document
+    #[default]
+    Json,
+    /// A header and then a label, category or note per line
+    Jsonl,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ExportOptions{
+    /// `json` by default
+    format: Option<Format>,
+}
+
+/// Export everything in the workspace as a versioned archive
+///
+/// The archive has the notes, trashed ones included, with their items,
+/// attachments and reminders, and the labels and categories they have. It
+/// can be restored with `POST /api/v1/import`. In JSON Lines it is streamed
+/// as it is read, for workspaces too big to hold at once.
+#[utoipa::path(
+    context_path = "/api",
+    params(ExportOptions),
+    responses(
+        (status = 200, description = "Archive of the workspace", body = Archive),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "export",
+)]
+#[get("/v1/export")]
+pub async fn export_archive(pool: web::Data<PgPool>, options: web::Query<ExportOptions>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let workspace_id = reader(&pool, user_id).await?.workspace_id;
+            let attachment = |filename: &str| ContentDisposition{
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename(filename.to_string())],
+            };
+            match options.format.unwrap_or_default() {
+                Format::Json => {
+                    let archive = archive::load(&pool, workspace_id)
+                        .await
+                        .map_err(ErrorInternalServerError)?;
+                    Ok(HttpResponse::Ok()
+                        .content_type("application/json")
+                        .insert_header(attachment("notisbak.json"))
+                        .body(serde_json::to_vec(&archive).map_err(ErrorInternalServerError)?))
+                },
+                Format::Jsonl => {
+                    let (labels, categories) = archive::labels_and_categories(&pool, workspace_id)
+                        .await
+                        .map_err(ErrorInternalServerError)?;
+                    let head = archive::jsonl_head(Utc::now().naive_utc(), labels, categories)
+                        .map_err(ErrorInternalServerError)?;
+                    // A page of notes after another, until there are no more
+                    let notes = stream::try_unfold(0, move |after| {
+                        let pool = pool.clone();
+                        async move {
+                            let notes = archive::notes_after(&pool, workspace_id, after)
+                                .await
+                                .map_err(ErrorInternalServerError)?;
+                            let last = match notes.last() {
+                                Some(note) => note.id,
+                                None => return Ok(None),
+                            };
+                            let lines = archive::jsonl_notes(notes).map_err(ErrorInternalServerError)?;
+                            Ok::<_, Error>(Some((Bytes::from(lines), last)))
+                        }
+                    });
+                    Ok(HttpResponse::Ok()
+                        .content_type("application/x-ndjson")
+                        .insert_header(attachment("notisbak.jsonl"))
+                        .streaming(stream::once(future::ok(Bytes::from(head))).chain(notes)))
+                },
+            }
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
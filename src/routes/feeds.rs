@@ -0,0 +1,183 @@
+use actix_web::{get, post, delete, web, error::{ErrorInternalServerError,
+    ErrorNotFound, ErrorUnauthorized}, Error, HttpRequest, HttpResponse,
+    http::header};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::IntoParams;
+use crate::exporter::feed::{self, Channel, Entry};
+use crate::model::{feed::{Feed, FeedSource, NewFeed}, claims::Claims};
+use crate::routes::{nextcloud::matches, workspaces::{reader, writer}};
+
+/// Notes in a feed, the last changed ones
+const ENTRIES: i64 = 50;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct FeedOptions{
+    /// The token of the feed, not needed when it is public
+    token: Option<String>,
+}
+
+/// Publish a label or a category as a feed
+///
+/// The notes with the label, or in the category, can be read at `atom` and
+/// `rss`. Feeds that are not public need the token. Publishing it again only
+/// changes whether it is public.
+#[utoipa::path(
+    context_path = "/api",
+    request_body = NewFeed,
+    responses(
+        (status = 201, description = "Published successfully", body = Feed),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Label or category not found"),
+    ),
+    tag = "feeds",
+)]
+#[post("/v1/feeds")]
+pub async fn create_feed(pool: web::Data<PgPool>, feed: web::Json<NewFeed>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Feed::new(&pool, feed.into_inner(), member.workspace_id, user_id)
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Feeds of the active workspace", body = [Feed]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "feeds",
+)]
+#[get("/v1/feeds")]
+pub async fn read_feeds(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            Feed::all(&pool, member.workspace_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Stop publishing a feed
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the feed"),
+    ),
+    responses(
+        (status = 200, description = "Deleted successfully", body = Feed),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "feeds",
+)]
+#[delete("/v1/feeds/{id}")]
+pub async fn delete_feed(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Feed::delete(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Whether the client has the feed changed last at `updated`
+fn not_modified(req: &HttpRequest, etag: &str, updated: NaiveDateTime) -> bool{
+    if let Some(matched) = matches(req, header::IF_NONE_MATCH, etag) {
+        return matched;
+    }
+    req.headers().get(header::IF_MODIFIED_SINCE)
+        .and_then(|since| since.to_str().ok())
+        .and_then(|since| NaiveDateTime::parse_from_str(since, "%a, %d %b %Y %H:%M:%S GMT").ok())
+        .is_some_and(|since| updated.and_utc().timestamp() <= since.and_utc().timestamp())
+}
+
+/// A feed of notes, like `/feeds/labels/1.atom` or `/feeds/categories/2.rss`
+#[utoipa::path(
+    params(
+        ("source", description = "labels or categories"),
+        ("file", description = "The id of the label or category and the format, atom or rss", example = "1.atom"),
+        FeedOptions,
+    ),
+    responses(
+        (status = 200, description = "The feed", content_type = "application/atom+xml"),
+        (status = 304, description = "Not modified since If-None-Match or If-Modified-Since"),
+        (status = 404, description = "Error: Not found, or wrong token"),
+    ),
+    security(()),
+    tag = "feeds",
+)]
+#[get("/feeds/{source}/{file}")]
+pub async fn read_feed(req: HttpRequest, pool: web::Data<PgPool>, path: web::Path<(String, String)>, options: web::Query<FeedOptions>) -> Result<HttpResponse, Error>{
+    let (kind, file) = path.into_inner();
+    let (id, format) = file.split_once('.').ok_or_else(|| ErrorNotFound("Not found"))?;
+    let source = FeedSource::from_path(&kind).ok_or_else(|| ErrorNotFound("Not found"))?;
+    let id: i32 = id.parse().map_err(|_| ErrorNotFound("Not found"))?;
+    if format != "atom" && format != "rss" {
+        return Err(ErrorNotFound("Not found"));
+    }
+    let (item, workspace_id) = Feed::find(&pool, source, id)
+        .await
+        .map_err(ErrorNotFound)?;
+    if !item.public && options.token.as_deref() != Some(item.token.as_str()) {
+        return Err(ErrorNotFound("Not found"));
+    }
+    let notes = item.notes(&pool, workspace_id, ENTRIES)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let mut entries = Vec::new();
+    for note in notes.iter() {
+        entries.push(Entry{
+            id: note.id,
+            title: note.title.clone(),
+            markdown: note.to_markdown(&pool, workspace_id)
+                .await
+                .map_err(ErrorInternalServerError)?,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+        });
+    }
+    let info = req.connection_info();
+    let channel = Channel{
+        title: item.name.clone(),
+        link: format!("{}://{}{}", info.scheme(), info.host(), req.uri()),
+        id: format!("urn:notisbak:feed:{}:{}", kind, id),
+        updated: entries.iter().map(|entry| entry.updated_at).max().unwrap_or(item.created_at),
+    };
+    let (body, content_type) = match format {
+        "atom" => (feed::atom(&channel, &entries), "application/atom+xml; charset=utf-8"),
+        _ => (feed::rss(&channel, &entries), "application/rss+xml; charset=utf-8"),
+    };
+    let etag = format!("{:x}", md5::compute(&body));
+    let not_modified = not_modified(&req, &etag, channel.updated);
+    let mut response = match not_modified {
+        true => HttpResponse::NotModified(),
+        false => HttpResponse::Ok(),
+    };
+    response.insert_header((header::ETAG, format!("\"{}\"", etag)))
+        .insert_header((header::LAST_MODIFIED, feed::rfc822(channel.updated)));
+    Ok(match not_modified {
+        true => response.finish(),
+        false => response.content_type(content_type).body(body),
+    })
+}
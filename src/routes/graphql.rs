@@ -0,0 +1,44 @@
+use actix_web::{get, post, web, Error, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use async_graphql::Request;
+use sqlx::PgPool;
+use crate::graphql::{self, NotisbakSchema};
+use crate::routes::{authorize, workspaces::reader};
+
+/// Run a GraphQL query or mutation in the active workspace of the user
+///
+/// The body has the `query`, with optional `operationName` and `variables`.
+/// Errors of the query come in the `errors` of a 200 response.
+#[utoipa::path(
+    request_body(content = Value, description = "The GraphQL request", content_type = "application/json"),
+    responses(
+        (status = 200, description = "The data and errors of the request", body = Value),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden, or user disabled"),
+    ),
+    tag = "graphql",
+)]
+#[post("/graphql")]
+pub async fn execute(pool: web::Data<PgPool>, schema: web::Data<NotisbakSchema>, request: web::Json<Request>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    // Out of the scope of the validator, so it checks the user itself
+    let user_id = authorize(&pool, credentials).await?;
+    let member = reader(&pool, user_id).await?;
+    let request = graphql::session(request.into_inner(), pool, &member);
+    Ok(HttpResponse::Ok().json(schema.execute(request).await))
+}
+
+/// The GraphQL schema, in SDL
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The schema", content_type = "text/plain"),
+    ),
+    security(()),
+    tag = "graphql",
+)]
+#[get("/graphql")]
+pub async fn read_schema(schema: web::Data<NotisbakSchema>) -> Result<HttpResponse, Error>{
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(schema.sdl()))
+}
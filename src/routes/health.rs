@@ -0,0 +1,72 @@
+use actix_web::{get, web, error::ErrorInternalServerError, Error, HttpResponse};
+use serde_json::json;
+use sqlx::{query, PgPool};
+use crate::admin::{self, MigrationState};
+use crate::config::Config;
+use crate::metrics;
+
+/// The process is up
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Up"),
+    ),
+    security(()),
+    tag = "health",
+)]
+#[get("/healthz")]
+pub async fn healthz() -> HttpResponse{
+    HttpResponse::Ok().json(json!({"status": "ok"}))
+}
+
+/// The database answers and has every migration of the binary, so the
+/// server can take requests
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Ready"),
+        (status = 503, description = "Error: The database does not answer, or it has migrations to apply or changed"),
+    ),
+    security(()),
+    tag = "health",
+)]
+#[get("/readyz")]
+pub async fn readyz(pool: web::Data<PgPool>) -> HttpResponse{
+    if let Err(e) = query("SELECT 1").execute(pool.get_ref()).await {
+        log::warn!("Not ready: {}", e);
+        return HttpResponse::ServiceUnavailable().json(json!({"status": "unavailable", "database": "unreachable"}));
+    }
+    match admin::migration_status(&pool).await {
+        Ok(status) if status.iter().all(|migration| migration.state == MigrationState::Applied) => {
+            HttpResponse::Ok().json(json!({"status": "ok"}))
+        },
+        Ok(status) => {
+            let migrations: Vec<String> = status.iter()
+                .filter(|migration| migration.state != MigrationState::Applied)
+                .map(|migration| format!("{} {}", migration.version, migration.state.as_str()))
+                .collect();
+            HttpResponse::ServiceUnavailable().json(json!({"status": "unavailable", "migrations": migrations}))
+        },
+        Err(e) => {
+            log::warn!("Not ready: {}", e);
+            HttpResponse::ServiceUnavailable().json(json!({"status": "unavailable", "database": "unreachable"}))
+        },
+    }
+}
+
+/// Metrics in the text format of Prometheus: the requests, the pool of
+/// connections and the totals of the server
+#[utoipa::path(
+    responses(
+        (status = 200, description = "The metrics", content_type = "text/plain; version=0.0.4"),
+        (status = 500, description = "Error: Internal Server Error"),
+    ),
+    security(()),
+    tag = "health",
+)]
+#[get("/metrics")]
+pub async fn read_metrics(pool: web::Data<PgPool>, config: web::Data<Config>) -> Result<HttpResponse, Error>{
+    let metrics = metrics::render(&pool, config.database.max_connections).await
+        .map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics))
+}
@@ -0,0 +1,268 @@
+use std::io::{BufReader, Seek, Write};
+
+use actix_web::{get, post, web, error::{ErrorBadRequest, ErrorNotFound,
+    ErrorInternalServerError, ErrorPayloadTooLarge, ErrorUnauthorized}, Error, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::IntoParams;
+use crate::config;
+use crate::exporter::archive;
+use crate::importer::{self, Report, enex, joplin, keep, markdown, archive::Strategy};
+use crate::model::{import_job::ImportJob, claims::Claims};
+use crate::routes::workspaces::writer;
+
+/// Import a Google Keep Takeout archive
+///
+/// Notes imported before are skipped, so the same archive can be imported
+/// again safely
+#[utoipa::path(
+    context_path = "/api",
+    request_body(content = String, description = "Takeout zip file", content_type = "application/zip"),
+    responses(
+        (status = 200, description = "Notes created, skipped and failed", body = Report),
+        (status = 400, description = "Error: Not a zip file"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "import",
+)]
+#[post("/v1/import/keep")]
+pub async fn import_keep(pool: web::Data<PgPool>, data: Bytes, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let (notes, report) = web::block(move || {
+                let mut report = Report::default();
+                keep::parse(&data, &mut report).map(|notes| (notes, report))
+            })
+                .await
+                .map_err(ErrorInternalServerError)?
+                .map_err(ErrorBadRequest)?;
+            let report = importer::import(pool, notes, member.workspace_id, user_id, report).await;
+            Ok(HttpResponse::Ok().json(report))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Import a zip of markdown files, like an Obsidian vault
+///
+/// The `tags` of the YAML front matter are the labels of the note and its
+/// folder the category, unless the front matter lists `categories`. Dates
+/// come from `created` and `updated`, or else from the files.
+#[utoipa::path(
+    context_path = "/api",
+    request_body(content = String, description = "Zip of markdown files", content_type = "application/zip"),
+    responses(
+        (status = 200, description = "Notes created, skipped and failed", body = Report),
+        (status = 400, description = "Error: Not a zip file"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "import",
+)]
+#[post("/v1/import/markdown")]
+pub async fn import_markdown(pool: web::Data<PgPool>, data: Bytes, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let (notes, report) = web::block(move || {
+                let mut report = Report::default();
+                markdown::parse(&data, &mut report).map(|notes| (notes, report))
+            })
+                .await
+                .map_err(ErrorInternalServerError)?
+                .map_err(ErrorBadRequest)?;
+            let report = importer::import(pool, notes, member.workspace_id, user_id, report).await;
+            Ok(HttpResponse::Ok().json(report))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RestoreOptions{
+    /// What to do with the notes already in the workspace, `skip` by default
+    strategy: Option<Strategy>,
+}
+
+/// Restore an archive made with `GET /api/v1/export`
+///
+/// The archive may come from another workspace or instance, in JSON or in
+/// JSON Lines. Labels and categories are merged by name. A note conflicts
+/// with one imported from the same source or else with the same title,
+/// and the strategy tells what to do then. Either all the archive is
+/// restored or nothing is.
+#[utoipa::path(
+    context_path = "/api",
+    params(RestoreOptions),
+    request_body(content = Archive, description = "Archive in JSON or JSON Lines"),
+    responses(
+        (status = 200, description = "Notes created, overwritten and skipped", body = Restored),
+        (status = 400, description = "Error: Not a valid archive"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "import",
+)]
+#[post("/v1/import")]
+pub async fn import_archive(pool: web::Data<PgPool>, data: Bytes, options: web::Query<RestoreOptions>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let archive = web::block(move || archive::parse(&data))
+                .await
+                .map_err(ErrorInternalServerError)?
+                .map_err(ErrorBadRequest)?;
+            let strategy = options.strategy.unwrap_or_default();
+            importer::archive::restore(&pool, archive, strategy, member.workspace_id, user_id)
+                .await
+                .map(|restored| HttpResponse::Ok().json(restored))
+                .map_err(ErrorInternalServerError)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Import a Joplin export
+///
+/// Takes a JEX file or a zip of a RAW export. Notebooks are categories,
+/// nested ones like `Parent/Child`, tags are labels and resources are
+/// attachments. Links between the notes point to their new ids.
+#[utoipa::path(
+    context_path = "/api",
+    request_body(content = String, description = "JEX file or zip of a RAW export", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Notes created, skipped and failed", body = Report),
+        (status = 400, description = "Error: Not a Joplin export"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "import",
+)]
+#[post("/v1/import/joplin")]
+pub async fn import_joplin(pool: web::Data<PgPool>, data: Bytes, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let (notes, report) = web::block(move || {
+                let mut report = Report::default();
+                joplin::parse(&data, &mut report).map(|notes| (notes, report))
+            })
+                .await
+                .map_err(ErrorInternalServerError)?
+                .map_err(ErrorBadRequest)?;
+            let report = importer::import(pool.clone(), notes, member.workspace_id, user_id, report).await;
+            joplin::link_notes(&pool, member.workspace_id)
+                .await
+                .map_err(ErrorInternalServerError)?;
+            Ok(HttpResponse::Ok().json(report))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct EnexOptions{
+    /// Category for the notes, usually the name of the exported notebook
+    notebook: Option<String>,
+}
+
+/// Import an Evernote export
+///
+/// The file is imported in the background. The job answered tells how the
+/// import goes.
+#[utoipa::path(
+    context_path = "/api",
+    params(EnexOptions),
+    request_body(content = String, description = "ENEX file", content_type = "application/xml"),
+    responses(
+        (status = 202, description = "Import started", body = ImportJob),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 413, description = "Error: Larger than the limit of the uploads"),
+    ),
+    tag = "import",
+)]
+#[post("/v1/import/enex")]
+pub async fn import_enex(pool: web::Data<PgPool>, mut payload: web::Payload, options: web::Query<EnexOptions>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            // The export may be too big for memory, so it waits on disk, as
+            // big as the limit of the uploads
+            let limit = config::get().limits.upload_bytes() as u64;
+            let mut file = tempfile::tempfile().map_err(ErrorInternalServerError)?;
+            let mut total = 0;
+            while let Some(chunk) = payload.next().await {
+                let chunk = chunk?;
+                total += chunk.len() as u64;
+                if total > limit {
+                    return Err(ErrorPayloadTooLarge(format!("Larger than {} MB", config::get().limits.upload)));
+                }
+                file.write_all(&chunk).map_err(ErrorInternalServerError)?;
+            }
+            file.rewind().map_err(ErrorInternalServerError)?;
+            let job = ImportJob::new(&pool, "enex", total, member.workspace_id, user_id)
+                .await
+                .map_err(ErrorInternalServerError)?;
+            let notebook = options.into_inner().notebook;
+            importer::spawn(pool.clone(), job.id, member.workspace_id, user_id, move |sender| {
+                enex::parse(BufReader::new(file), notebook.as_deref(),
+                    |parsed| sender.blocking_send(parsed).is_ok())
+            });
+            Ok(HttpResponse::Accepted().json(job))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Import jobs of the user, newest first", body = [ImportJob]),
+        (status = 401, description = "Error: Unauthorized"),
+    ),
+    tag = "import",
+)]
+#[get("/v1/import/jobs")]
+pub async fn read_import_jobs(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            ImportJob::all(&pool, user_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the import job"),
+    ),
+    responses(
+        (status = 200, description = "Status, progress and report of the import", body = ImportJob),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "import",
+)]
+#[get("/v1/import/jobs/{id}")]
+pub async fn read_import_job(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            ImportJob::get(&pool, path.into_inner(), user_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
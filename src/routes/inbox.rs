@@ -0,0 +1,113 @@
+use actix_web::{get, post, web, error::{ErrorBadRequest, ErrorForbidden,
+    ErrorInternalServerError, ErrorNotFound, ErrorServiceUnavailable,
+    ErrorUnauthorized}, Error, HttpResponse, web::Bytes};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use sqlx::PgPool;
+use crate::config::Config;
+use crate::mailbox::{Recipient, Rejected};
+use crate::model::{claims::Claims, inbox::Inbox};
+
+/// Compare the secrets in a time that does not depend on where they differ
+fn same_secret(given: &[u8], secret: &[u8]) -> bool{
+    given.len() == secret.len()
+        && given.iter().zip(secret).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Read the inbox of the user
+///
+/// Mail sent to the address becomes a note in the active workspace. Adding
+/// `+label` to the address puts that label on the note.
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "The inbox of the user", body = Inbox),
+        (status = 401, description = "Error: Unauthorized"),
+    ),
+    tag = "inbox",
+)]
+#[get("/v1/inbox")]
+pub async fn read_inbox(pool: web::Data<PgPool>, config: web::Data<Config>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            Inbox::get(&pool, user_id, &config.mail.domain)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorInternalServerError)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Change the address of the inbox
+///
+/// Mail sent to the old address is not taken anymore.
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "The inbox with its new address", body = Inbox),
+        (status = 401, description = "Error: Unauthorized"),
+    ),
+    tag = "inbox",
+)]
+#[post("/v1/inbox/renew")]
+pub async fn renew_inbox(pool: web::Data<PgPool>, config: web::Data<Config>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            Inbox::renew(&pool, user_id, &config.mail.domain)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorInternalServerError)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// A raw message piped by the MTA for the inbox at `recipient`. The MTA
+/// authenticates with the secret of `mail.pipe_secret` (`MAIL_PIPE_SECRET`),
+/// without which this is disabled.
+#[utoipa::path(
+    params(
+        ("recipient", description = "The address of the inbox", example = "notes+k3jd81hs0c@notes.example.com"),
+    ),
+    request_body(content = String, description = "The raw message", content_type = "message/rfc822"),
+    responses(
+        (status = 200, description = "Delivered as a note", body = Report),
+        (status = 400, description = "Error: Invalid message"),
+        (status = 401, description = "Error: Wrong secret"),
+        (status = 403, description = "Error: Read only workspace"),
+        (status = 404, description = "Error: Unknown recipient or disabled user, or delivery by pipe disabled"),
+        (status = 503, description = "Error: Can not be delivered now"),
+    ),
+    security(("mail_pipe_secret" = [])),
+    tag = "inbox",
+)]
+#[post("/mail/{recipient}")]
+pub async fn receive_mail(pool: web::Data<PgPool>, config: web::Data<Config>, path: web::Path<String>, body: Bytes, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    let secret = &config.mail.pipe_secret;
+    if secret.is_empty() {
+        return Err(ErrorNotFound("Not found"));
+    }
+    if !same_secret(credentials.token().as_bytes(), secret.as_bytes()) {
+        return Err(ErrorUnauthorized("Wrong secret"));
+    }
+    let recipient = match Recipient::find(&pool, &path.into_inner(), &config.mail.domain).await {
+        Ok(recipient) => recipient,
+        Err(e @ Rejected::Failed(_)) => return Err(ErrorServiceUnavailable(e)),
+        Err(e) => return Err(ErrorNotFound(e)),
+    };
+    match recipient.deliver(&pool, &body).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(report)),
+        Err(e @ Rejected::ReadOnly) => Err(ErrorForbidden(e)),
+        Err(e @ Rejected::Failed(_)) => Err(ErrorServiceUnavailable(e)),
+        Err(e) => Err(ErrorBadRequest(e)),
+    }
+}
+
+#[test]
+fn test_same_secret() {
+    assert!(same_secret(b"s3cret", b"s3cret"));
+    assert!(!same_secret(b"s3creT", b"s3cret"));
+    assert!(!same_secret(b"s3cre", b"s3cret"));
+    assert!(!same_secret(b"", b"s3cret"));
+}
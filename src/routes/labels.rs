@@ -1,18 +1,21 @@
 use actix_web::{get, post, put, delete, web, error::{ErrorNotFound,
-    ErrorConflict, ErrorUnauthorized}, Error, HttpResponse};
+    ErrorBadRequest, ErrorConflict, ErrorUnauthorized}, Error, HttpResponse};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use anyhow::Result;
 use sqlx::PgPool;
 use crate::model::{label::Label, claims::Claims};
+use crate::routes::workspaces::{reader, writer};
 use serde_json::Value;
 
 #[utoipa::path(
     context_path = "/api",
     request_body = NewLabel,
     responses(
-        (status = 201, description = "Created successfully", body = Label),
-        (status = 401, description = "Error: Conflict"),
-        (status = 409, description = "Error: Unauthorized")
+        (status = 200, description = "Created successfully", body = Label),
+        (status = 400, description = "Error: Invalid label, without name"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 409, description = "Error: Conflict"),
     ),
     tag  = "labels"
 )]
@@ -20,12 +23,15 @@ use serde_json::Value;
 pub async fn create_label(pool: web::Data<PgPool>, body: String, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
-            let content: Value = serde_json::from_str(&body).unwrap();
-            let name = content.get("name").as_ref().unwrap().as_str().unwrap();
-            Label::new(&pool, name, user_id)
+            let member = writer(&pool, user_id).await?;
+            let content: Value = serde_json::from_str(&body).map_err(ErrorBadRequest)?;
+            let name = content.get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ErrorBadRequest("The name of the label is required"))?;
+            Label::new(&pool, name, member.workspace_id, user_id)
                 .await
                 .map(|item| HttpResponse::Ok().json(item))
-                .map_err(|e| ErrorConflict(e))
+                .map_err(ErrorConflict)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -38,8 +44,9 @@ pub async fn create_label(pool: web::Data<PgPool>, body: String, credentials: Be
     ),
     responses(
         (status = 200, description = "Get One", body = Label),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 409, description = "Error: Unauthorized")
     ),
     tag  = "labels"
 )]
@@ -47,11 +54,12 @@ pub async fn create_label(pool: web::Data<PgPool>, body: String, credentials: Be
 pub async fn read_label(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
             let id = path.into_inner();
-            Label::get(pool, id, user_id)
+            Label::get(pool, id, member.workspace_id)
                .await
                .map(|item| HttpResponse::Ok().json(item))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -61,8 +69,9 @@ pub async fn read_label(pool: web::Data<PgPool>, path: web::Path<i32>, credentia
     context_path = "/api",
     responses(
         (status = 200, description = "List all", body = [Label]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag  = "labels",
 )]
@@ -70,10 +79,11 @@ pub async fn read_label(pool: web::Data<PgPool>, path: web::Path<i32>, credentia
 pub async fn read_labels(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
-            Label::all(pool, user_id)
+            let member = reader(&pool, user_id).await?;
+            Label::all(pool, member.workspace_id)
                .await
                .map(|items| HttpResponse::Ok().json(items))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -85,19 +95,21 @@ pub async fn read_labels(pool: web::Data<PgPool>, credentials: BearerAuth) -> Re
     request_body = Label,
     responses(
         (status = 201, description = "Updated successfully", body = Label),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag  = "labels"
 )]
 #[put("/v1/labels")]
 pub async fn update_label(pool: web::Data<PgPool>, label: web::Json<Label>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
-        Ok(_user_id) => {
-            Label::update(pool, label.into_inner())
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Label::update(pool, label.into_inner(), member.workspace_id)
                .await
                .map(|item| HttpResponse::Ok().json(item))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -110,20 +122,22 @@ pub async fn update_label(pool: web::Data<PgPool>, label: web::Json<Label>, cred
     ),
     responses(
         (status = 200, description = "Deleted successfully", body = Label),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag  = "labels"
 )]
 #[delete("/v1/labels/{id}")]
 pub async fn delete_label(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
-        Ok(_user_id) => {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
             let id = path.into_inner();
-            Label::delete(pool, id)
+            Label::delete(pool, id, member.workspace_id)
                .await
                .map(|item| HttpResponse::Ok().json(item))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -1,4 +1,209 @@
+pub mod app_tokens;
+pub mod attachments;
 pub mod categories;
+pub mod events;
+pub mod exports;
+pub mod feeds;
+pub mod graphql;
+pub mod health;
+pub mod imports;
+pub mod inbox;
 pub mod labels;
+pub mod nextcloud;
+pub mod note_items;
+pub mod note_links;
 pub mod notes;
+pub mod reminders;
+pub mod templates;
 pub mod users;
+pub mod webdav;
+pub mod webhooks;
+pub mod workspaces;
+
+use actix_web::{web, dev::ServiceRequest, error::{ErrorForbidden,
+    ErrorInternalServerError, ErrorUnauthorized}, Error, HttpResponse};
+use actix_web_httpauth::{extractors::bearer::BearerAuth,
+    middleware::HttpAuthentication};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::{SwaggerUi, Url};
+
+use sqlx::PgPool;
+use crate::{config::Features, model::{claims::Claims, user::User}, openapi};
+
+/// Every route of the server. The application needs the pool, the GraphQL
+/// schema, the configuration and the payload limit as data, and
+/// `metrics::record` to count the requests.
+pub fn config(cfg: &mut web::ServiceConfig){
+    config_with(cfg, &Features::default())
+}
+
+/// The routes of the features turned on
+pub fn config_with(cfg: &mut web::ServiceConfig, features: &Features){
+    let auth = HttpAuthentication::bearer(validator);
+    let mut auth_scope = web::scope("auth")
+        .service(users::login)
+        .service(users::login_with_app_token);
+    let mut api = web::scope("api")
+        .wrap(auth.clone())
+        .service(users::login);
+    if features.registration {
+        auth_scope = auth_scope.service(users::register);
+        api = api.service(users::register);
+    }else{
+        // Not for the authenticated scope below, that would answer 401
+        auth_scope = auth_scope.route("/register", web::post().to(HttpResponse::NotFound));
+    }
+    if features.feeds {
+        api = api
+            .service(feeds::create_feed)
+            .service(feeds::read_feeds)
+            .service(feeds::delete_feed);
+        cfg.service(feeds::read_feed);
+    }
+    if features.metrics {
+        cfg.service(health::read_metrics);
+    }
+    cfg
+        .service(health::healthz)
+        .service(health::readyz)
+        .service(auth_scope)
+        .service(api
+            .service(notes::root)
+            .service(notes::create_note)
+            .service(notes::read_note)
+            .service(notes::read_note_markdown)
+            .service(notes::read_notes)
+            .service(notes::read_labels_for_note)
+            .service(notes::read_categories_for_note)
+            .service(notes::update_note)
+            .service(notes::delete_note)
+            .service(notes::add_label_to_note)
+            .service(notes::add_category_to_note)
+            .service(notes::delete_label_from_note)
+            .service(notes::delete_category_from_note)
+            .service(note_items::read_items)
+            .service(note_items::create_item)
+            .service(note_items::update_item)
+            .service(note_items::reorder_items)
+            .service(note_items::check_item)
+            .service(note_items::uncheck_item)
+            .service(note_items::delete_item)
+            .service(note_links::read_links)
+            .service(note_links::read_backlinks)
+            .service(note_links::read_graph)
+            .service(templates::create_template)
+            .service(templates::read_template)
+            .service(templates::read_templates)
+            .service(templates::update_template)
+            .service(templates::delete_template)
+            .service(templates::create_note_from_template)
+            .service(attachments::read_attachments)
+            .service(attachments::read_attachment)
+            .service(imports::import_archive)
+            .service(imports::import_keep)
+            .service(imports::import_markdown)
+            .service(imports::import_enex)
+            .service(imports::import_joplin)
+            .service(imports::read_import_jobs)
+            .service(imports::read_import_job)
+            .service(exports::export_archive)
+            .service(exports::export_markdown)
+            .service(categories::create_category)
+            .service(categories::read_category)
+            .service(categories::read_categories)
+            .service(categories::update_category)
+            .service(categories::delete_category)
+            .service(labels::create_label)
+            .service(labels::read_label)
+            .service(labels::read_labels)
+            .service(labels::update_label)
+            .service(labels::delete_label)
+            .service(workspaces::create_workspace)
+            .service(workspaces::read_workspace)
+            .service(workspaces::read_workspaces)
+            .service(workspaces::update_workspace)
+            .service(workspaces::delete_workspace)
+            .service(workspaces::activate_workspace)
+            .service(workspaces::read_members)
+            .service(workspaces::set_member)
+            .service(workspaces::delete_member)
+            .service(reminders::read_reminders)
+            .service(reminders::set_reminder)
+            .service(reminders::delete_reminder)
+            .service(reminders::snooze_reminder)
+            .service(reminders::dismiss_reminder)
+            .service(events::read_events)
+            .service(app_tokens::create_token)
+            .service(app_tokens::read_tokens)
+            .service(app_tokens::delete_token)
+            .service(inbox::read_inbox)
+            .service(inbox::renew_inbox)
+            .service(webhooks::create_webhook)
+            .service(webhooks::read_webhooks)
+            .service(webhooks::read_webhook)
+            .service(webhooks::update_webhook)
+            .service(webhooks::delete_webhook)
+            .service(webhooks::read_deliveries)
+            .service(webhooks::replay_delivery)
+        )
+        .service(inbox::receive_mail);
+    if features.nextcloud {
+        cfg
+            .service(web::scope("index.php/apps/notes/api/v1")
+                .service(nextcloud::read_notes)
+                .service(nextcloud::read_note)
+                .service(nextcloud::create_note)
+                .service(nextcloud::update_note)
+                .service(nextcloud::delete_note)
+                .service(nextcloud::read_settings)
+                .service(nextcloud::update_settings)
+            )
+            .service(nextcloud::capabilities);
+    }
+    if features.graphql {
+        cfg
+            .service(graphql::execute)
+            .service(graphql::read_schema);
+    }
+    if features.webdav {
+        cfg.service(web::scope("dav")
+            .default_service(web::to(webdav::dav))
+        );
+    }
+    // The Swagger UI serves the document itself
+    if features.swagger {
+        cfg.service(
+            SwaggerUi::new("/swagger-ui/{_:.*}")
+                .urls(vec![
+                    (Url::new("notisbak", "/api-doc/openapi.json"),
+                    openapi::ApiDoc::openapi()),
+                ])
+        );
+    }else{
+        cfg.service(openapi::read_openapi);
+    }
+}
+
+/// The user of a valid token of the API, unless it has been disabled since
+/// the token was issued
+pub async fn authorize(pool: &web::Data<PgPool>, credentials: BearerAuth) -> Result<i32, Error>{
+    let user_id = Claims::get_index(credentials).map_err(ErrorUnauthorized)?;
+    match User::get(pool, user_id).await {
+        Ok(user) if user.disabled => Err(ErrorForbidden("User disabled")),
+        Ok(_) => Ok(user_id),
+        Err(sqlx::Error::RowNotFound) => Err(ErrorUnauthorized("Unknown user")),
+        Err(e) => Err(ErrorInternalServerError(e)),
+    }
+}
+
+/// Let through only the requests with a valid token of the API
+async fn validator(req: ServiceRequest, credentials: BearerAuth) -> Result<ServiceRequest, (Error, ServiceRequest)>{
+    let pool = match req.app_data::<web::Data<PgPool>>() {
+        Some(pool) => pool.clone(),
+        None => return Err((ErrorInternalServerError("Without database"), req)),
+    };
+    match authorize(&pool, credentials).await {
+        Ok(_) => Ok(req),
+        Err(e) => Err((e, req)),
+    }
+}
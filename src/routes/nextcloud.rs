@@ -0,0 +1,188 @@
+//! The Nextcloud Notes API v1, so its Android and iOS clients can use
+//! notisbak. Clients authenticate with basic auth, the email of the user
+//! and an app token. See
+//! https://github.com/nextcloud/notes/blob/master/docs/api/v1.md
+use actix_web::{get, post, put, delete, web, error::{ErrorBadRequest,
+    ErrorForbidden, ErrorInternalServerError, ErrorNotFound,
+    ErrorPreconditionFailed}, Error, HttpRequest, HttpResponse,
+    http::header::{self, HeaderValue}};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use crate::model::nextcloud::{NextcloudNote, NoteChanges};
+use crate::routes::{app_tokens::authenticate, workspaces::{reader, writer}};
+
+/// Versions of the API that are served
+const API_VERSIONS: &str = "1.1";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListOptions{
+    category: Option<String>,
+    /// Fields left out of the notes, separated by commas
+    exclude: Option<String>,
+    /// Notes not changed since this unix time only have their id
+    prune_before: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NoteOptions{
+    exclude: Option<String>,
+}
+
+fn excluded(exclude: &Option<String>) -> Vec<&str>{
+    exclude.as_deref()
+        .map(|exclude| exclude.split(',').map(str::trim).collect())
+        .unwrap_or_default()
+}
+
+/// Whether the `header` of the request has the `etag`, or any with `*`
+pub fn matches(req: &HttpRequest, header: header::HeaderName, etag: &str) -> Option<bool>{
+    let value = req.headers().get(header)?.to_str().ok()?;
+    Some(value.split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/").trim_matches('"'))
+        .any(|tag| tag == "*" || tag == etag))
+}
+
+/// Answer `value` with its `etag`, or just 304 if the client has it
+fn conditional(req: &HttpRequest, etag: &str, value: Value) -> HttpResponse{
+    let mut response = match matches(req, header::IF_NONE_MATCH, etag) {
+        Some(true) => HttpResponse::NotModified().finish(),
+        _ => HttpResponse::Ok().json(value),
+    };
+    if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", etag)) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    if let Ok(versions) = HeaderValue::from_str(API_VERSIONS) {
+        response.headers_mut().insert(header::HeaderName::from_static("x-notes-api-versions"), versions);
+    }
+    response
+}
+
+#[get("/notes")]
+pub async fn read_notes(req: HttpRequest, pool: web::Data<PgPool>, options: web::Query<ListOptions>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    let user_id = authenticate(&pool, &credentials).await?;
+    let member = reader(&pool, user_id).await?;
+    let mut notes = NextcloudNote::all(&pool, member.workspace_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    if let Some(category) = options.category.as_deref() {
+        notes.retain(|note| note.category == category);
+    }
+    let etags: Vec<&str> = notes.iter().map(|note| note.etag.as_str()).collect();
+    let etag = format!("{:x}", md5::compute(format!("{:?}{:?}{:?}", etags, options.exclude, options.prune_before)));
+    let exclude = excluded(&options.exclude);
+    let notes: Vec<Value> = notes.iter()
+        .map(|note| match options.prune_before {
+            Some(prune_before) if note.modified < prune_before => json!({"id": note.id}),
+            _ => note.to_value(&exclude),
+        })
+        .collect();
+    Ok(conditional(&req, &etag, json!(notes)))
+}
+
+#[get("/notes/{id}")]
+pub async fn read_note(req: HttpRequest, pool: web::Data<PgPool>, path: web::Path<i32>, options: web::Query<NoteOptions>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    let user_id = authenticate(&pool, &credentials).await?;
+    let member = reader(&pool, user_id).await?;
+    let note = NextcloudNote::get(&pool, path.into_inner(), member.workspace_id)
+        .await
+        .map_err(ErrorNotFound)?;
+    Ok(conditional(&req, &note.etag, note.to_value(&excluded(&options.exclude))))
+}
+
+#[post("/notes")]
+pub async fn create_note(pool: web::Data<PgPool>, changes: web::Json<NoteChanges>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    let user_id = authenticate(&pool, &credentials).await?;
+    let member = writer(&pool, user_id).await?;
+    NextcloudNote::create(&pool, changes.into_inner(), member.workspace_id, user_id)
+        .await
+        .map(|note| HttpResponse::Ok().json(note))
+        .map_err(ErrorBadRequest)
+}
+
+/// Change a note. With `If-Match`, only if nobody changed it meanwhile.
+#[put("/notes/{id}")]
+pub async fn update_note(req: HttpRequest, pool: web::Data<PgPool>, path: web::Path<i32>, changes: web::Json<NoteChanges>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    let user_id = authenticate(&pool, &credentials).await?;
+    let member = writer(&pool, user_id).await?;
+    let note = NextcloudNote::get(&pool, path.into_inner(), member.workspace_id)
+        .await
+        .map_err(ErrorNotFound)?;
+    if matches(&req, header::IF_MATCH, &note.etag) == Some(false) {
+        return Err(ErrorPreconditionFailed(json!(note)));
+    }
+    if note.readonly && (changes.title.is_some() || changes.content.is_some()) {
+        return Err(ErrorForbidden("Checklists can not be changed as text"));
+    }
+    // With an etag, the note is changed only if it is still the one read
+    let version = match matches(&req, header::IF_MATCH, "") {
+        Some(false) => Some(note.version),
+        _ => None,
+    };
+    match NextcloudNote::update(&pool, note.id, changes.into_inner(), member.workspace_id, user_id, version).await {
+        Ok(note) => Ok(HttpResponse::Ok().json(note)),
+        Err(sqlx::Error::RowNotFound) => {
+            let note = NextcloudNote::get(&pool, note.id, member.workspace_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            Err(ErrorPreconditionFailed(json!(note)))
+        },
+        Err(e) => Err(ErrorBadRequest(e)),
+    }
+}
+
+#[delete("/notes/{id}")]
+pub async fn delete_note(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    let user_id = authenticate(&pool, &credentials).await?;
+    let member = writer(&pool, user_id).await?;
+    NextcloudNote::delete(&pool, path.into_inner(), member.workspace_id)
+        .await
+        .map(|_| HttpResponse::Ok().json(json!({})))
+        .map_err(ErrorNotFound)
+}
+
+/// Settings of the Nextcloud app. Notes are not files here, so they are
+/// fixed.
+fn settings() -> Value{
+    json!({
+        "notesPath": "Notes",
+        "fileSuffix": ".md",
+        "noteMode": "edit",
+    })
+}
+
+#[get("/settings")]
+pub async fn read_settings(pool: web::Data<PgPool>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    authenticate(&pool, &credentials).await?;
+    Ok(HttpResponse::Ok().json(settings()))
+}
+
+#[put("/settings")]
+pub async fn update_settings(pool: web::Data<PgPool>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    authenticate(&pool, &credentials).await?;
+    Ok(HttpResponse::Ok().json(settings()))
+}
+
+/// How clients find out that the notes app is installed and which API
+/// versions it has
+#[get("/ocs/v2.php/cloud/capabilities")]
+pub async fn capabilities(pool: web::Data<PgPool>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    authenticate(&pool, &credentials).await?;
+    Ok(HttpResponse::Ok().json(json!({
+        "ocs": {
+            "meta": {"status": "ok", "statuscode": 200, "message": "OK"},
+            "data": {
+                "version": {"major": 25, "minor": 0, "micro": 0, "string": "25.0.0"},
+                "capabilities": {
+                    "notes": {
+                        "api_version": API_VERSIONS.split(',').collect::<Vec<&str>>(),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                },
+            },
+        },
+    })))
+}
@@ -0,0 +1,200 @@
+use actix_web::{get, post, put, delete, web, error::{ErrorNotFound,
+    ErrorUnauthorized, ErrorBadRequest}, Error, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use sqlx::PgPool;
+use crate::model::{note_item::{NoteItem, NewNoteItem, UpdateNoteItem,
+    ItemsOrder}, claims::Claims};
+use crate::routes::workspaces::{reader, writer};
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the checklist note"),
+    ),
+    responses(
+        (status = 200, description = "Items of the checklist in order", body = [NoteItem]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[get("/v1/notes/{id}/items")]
+pub async fn read_items(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            NoteItem::all(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the checklist note"),
+    ),
+    request_body = NewNoteItem,
+    responses(
+        (status = 201, description = "Item added", body = NoteItem),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Checklist not found"),
+    ),
+    tag = "notes",
+)]
+#[post("/v1/notes/{id}/items")]
+pub async fn create_item(pool: web::Data<PgPool>, path: web::Path<i32>, item: web::Json<NewNoteItem>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            NoteItem::new(&pool, path.into_inner(), member.workspace_id, item.into_inner())
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the checklist note"),
+    ),
+    request_body = UpdateNoteItem,
+    responses(
+        (status = 200, description = "Item updated", body = NoteItem),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[put("/v1/notes/{id}/items")]
+pub async fn update_item(pool: web::Data<PgPool>, path: web::Path<i32>, item: web::Json<UpdateNoteItem>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            NoteItem::update(&pool, path.into_inner(), member.workspace_id, item.into_inner())
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the checklist note"),
+    ),
+    request_body = ItemsOrder,
+    responses(
+        (status = 200, description = "Items in their new order", body = [NoteItem]),
+        (status = 400, description = "Error: Bad request"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "notes",
+)]
+#[put("/v1/notes/{id}/items/order")]
+pub async fn reorder_items(pool: web::Data<PgPool>, path: web::Path<i32>, order: web::Json<ItemsOrder>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            NoteItem::reorder(&pool, path.into_inner(), member.workspace_id, &order.ids)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorBadRequest)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+async fn set_checked(pool: web::Data<PgPool>, path: web::Path<(i32, i32)>, credentials: BearerAuth, checked: bool) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let (note_id, item_id) = path.into_inner();
+            NoteItem::set_checked(&pool, note_id, member.workspace_id, item_id, checked)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("note_id", description = "The id of the checklist note"),
+        ("item_id", description = "The id of the item"),
+    ),
+    responses(
+        (status = 200, description = "Item checked", body = NoteItem),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[post("/v1/notes/{note_id}/items/{item_id}/check")]
+pub async fn check_item(pool: web::Data<PgPool>, path: web::Path<(i32, i32)>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    set_checked(pool, path, credentials, true).await
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("note_id", description = "The id of the checklist note"),
+        ("item_id", description = "The id of the item"),
+    ),
+    responses(
+        (status = 200, description = "Item unchecked", body = NoteItem),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[post("/v1/notes/{note_id}/items/{item_id}/uncheck")]
+pub async fn uncheck_item(pool: web::Data<PgPool>, path: web::Path<(i32, i32)>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    set_checked(pool, path, credentials, false).await
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("note_id", description = "The id of the checklist note"),
+        ("item_id", description = "The id of the item"),
+    ),
+    responses(
+        (status = 200, description = "Item deleted", body = NoteItem),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[delete("/v1/notes/{note_id}/items/{item_id}")]
+pub async fn delete_item(pool: web::Data<PgPool>, path: web::Path<(i32, i32)>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let (note_id, item_id) = path.into_inner();
+            NoteItem::delete(&pool, note_id, member.workspace_id, item_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
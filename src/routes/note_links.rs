@@ -0,0 +1,98 @@
+use actix_web::{get, web, error::{ErrorNotFound, ErrorUnauthorized}, Error,
+    HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use sqlx::PgPool;
+use crate::model::{note::Note, note_link::NoteLink, claims::Claims};
+use crate::routes::workspaces::reader;
+
+/// Notes linked from a note with `[[title]]` or `[[id]]`
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
+    responses(
+        (status = 200, description = "Notes this note links to", body = [LinkedNote]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[get("/v1/notes/{id}/links")]
+pub async fn read_links(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            let id = path.into_inner();
+            Note::get(pool.clone(), id, member.workspace_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            NoteLink::links(&pool, id, member.workspace_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Notes linking to a note
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
+    responses(
+        (status = 200, description = "Notes that link to this note", body = [LinkedNote]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes",
+)]
+#[get("/v1/notes/{id}/backlinks")]
+pub async fn read_backlinks(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            let id = path.into_inner();
+            Note::get(pool.clone(), id, member.workspace_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            NoteLink::backlinks(&pool, id, member.workspace_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Graph of the notes of the active workspace
+///
+/// Every note is a node and every link an edge from the linking note to
+/// the linked one
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Notes and links between them", body = Graph),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "notes",
+)]
+#[get("/v1/graph")]
+pub async fn read_graph(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            NoteLink::graph(&pool, member.workspace_id)
+                .await
+                .map(|graph| HttpResponse::Ok().json(graph))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -1,13 +1,59 @@
 use actix_web::{get, post, put, delete, web, error::{ErrorNotFound,
-    ErrorConflict, ErrorUnauthorized, ErrorBadRequest}, Error, HttpResponse};
-use actix_web::{App, test};
+    ErrorUnauthorized, ErrorBadRequest, ErrorConflict}, Error, HttpResponse};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use anyhow::Result;
 use sqlx::PgPool;
-use crate::model::{note::{Note, NewNote}, category::Category,
-    note_label::NoteLabel, note_category::NoteCategory, label::Label, claims::Claims};
-use serde_json::Value;
+use crate::model::{note::{self, Note, NewNote, NoteKind, NoteDetail, Include}, category::Category,
+    note_label::NoteLabel, note_category::NoteCategory, label::Label,
+    reminder::validate_recurrence, claims::Claims};
+use crate::routes::workspaces::{reader, writer};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use utoipa::IntoParams;
 
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct UpdateOptions{
+    /// Rewrite the `[[title]]` links to the note when its title changes
+    rewrite_links: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct IncludeOptions{
+    /// Relations to embed, comma separated: `labels`, `categories`
+    #[param(example = "labels,categories")]
+    include: Option<String>,
+}
+
+impl IncludeOptions{
+    fn relations(&self) -> Result<Include, Error>{
+        self.include.as_deref()
+            .unwrap_or_default()
+            .parse()
+            .map_err(ErrorBadRequest)
+    }
+}
+
+/// Most notes in a page
+const MAX_PAGE: i64 = 500;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PageOptions{
+    /// Read the notes in pages of this size, by id. Without it, all at once.
+    #[param(example = 50)]
+    limit: Option<i64>,
+    /// The id of the last note of the previous page
+    after: Option<i32>,
+}
+
+/// Check that the token is valid
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Valid token", body = String),
+        (status = 401, description = "Error: Unauthorized"),
+    ),
+    tag = "auth",
+)]
 #[get("/v1/")]
 pub async fn root() -> Result<HttpResponse, Error>{
     Ok(HttpResponse::Ok().body("Hello world, Rust!"))
@@ -15,12 +61,14 @@ pub async fn root() -> Result<HttpResponse, Error>{
 
 #[actix_web::test]
 async fn test_index() {
+    use actix_web::{App, test};
+
     let app = test::init_service(
         App::new().service(root)
     ).await;
 
     let req = test::TestRequest::get()
-        .uri("/")
+        .uri("/v1/")
         .to_request();
 
     let result = test::call_and_read_body(&app, req).await;
@@ -31,9 +79,10 @@ async fn test_index() {
     context_path = "/api",
     request_body = NewNote,
     responses(
-        (status = 201, description = "Created successfully", body = NewNote),
-        (status = 401, description = "Error: Conflict"),
-        (status = 409, description = "Error: Unauthorized")
+        (status = 201, description = "Created successfully", body = Note),
+        (status = 400, description = "Error: Invalid recurrence"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
     ),
     tag = "notes"
 )]
@@ -41,10 +90,12 @@ async fn test_index() {
 pub async fn create_note(pool: web::Data<PgPool>, note: web::Json<NewNote>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
-            Note::new(pool, note.into_inner(), user_id)
+            let member = writer(&pool, user_id).await?;
+            validate_recurrence(&note.recurrence).map_err(ErrorBadRequest)?;
+            Note::new(pool, note.into_inner(), member.workspace_id, user_id)
                .await
                .map(|item| HttpResponse::Created().json(item))
-               .map_err(|e| ErrorNotFound("Not found"))
+               .map_err(|_| ErrorNotFound("Not found"))
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -54,45 +105,112 @@ pub async fn create_note(pool: web::Data<PgPool>, note: web::Json<NewNote>, cred
     context_path = "/api",
     params(
         ("id", description = "The id of the note"),
+        IncludeOptions,
     ),
     responses(
-        (status = 200, description = "Get One", body = Note),
+        (status = 200, description = "Get One, with the relations in include", body = NoteDetail),
+        (status = 400, description = "Error: Unknown relation in include"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 409, description = "Error: Unauthorized")
     ),
     tag = "notes"
 )]
 #[get("/v1/notes/{id}")]
-pub async fn read_note(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
+pub async fn read_note(pool: web::Data<PgPool>, path: web::Path<i32>, options: web::Query<IncludeOptions>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
             let id = path.into_inner();
-            Note::get(pool, id, user_id)
+            let include = options.relations()?;
+            if include.is_empty() {
+                return Note::get(pool, id, member.workspace_id)
+                    .await
+                    .map(|item| HttpResponse::Ok().json(item))
+                    .map_err(ErrorNotFound);
+            }
+            NoteDetail::get(&pool, id, member.workspace_id, include)
                .await
                .map(|item| HttpResponse::Ok().json(item))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
 }
 
+/// Get a note as markdown
+///
+/// Checklists are rendered as a GFM task list
 #[utoipa::path(
     context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
     responses(
-        (status = 200, description = "List all", body = [Note]),
+        (status = 200, description = "The note as markdown", content_type = "text/markdown"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "notes"
+)]
+#[get("/v1/notes/{id}/markdown")]
+pub async fn read_note_markdown(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            let note = Note::get(pool.clone(), path.into_inner(), member.workspace_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            note.to_markdown(&pool, member.workspace_id)
+                .await
+                .map(|text| HttpResponse::Ok()
+                    .content_type("text/markdown; charset=utf-8")
+                    .body(text))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        IncludeOptions,
+        PageOptions,
+    ),
+    responses(
+        (status = 200, description = "List all, or a page, with the relations in include", body = [NoteDetail]),
+        (status = 400, description = "Error: Unknown relation in include"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "notes"
 )]
 #[get("/v1/notes")]
-pub async fn read_notes(pool: web::Data<PgPool>, credentials: BearerAuth)->Result<HttpResponse, Error>{
+pub async fn read_notes(pool: web::Data<PgPool>, options: web::Query<IncludeOptions>, page: web::Query<PageOptions>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
-            Note::all(pool, user_id)
+            let member = reader(&pool, user_id).await?;
+            let include = options.relations()?;
+            if page.limit.is_some() || page.after.is_some() {
+                let limit = page.limit.unwrap_or(MAX_PAGE).clamp(1, MAX_PAGE);
+                return NoteDetail::page(&pool, member.workspace_id, include, page.after, limit)
+                    .await
+                    .map(|some_notes| HttpResponse::Ok().json(some_notes))
+                    .map_err(ErrorNotFound);
+            }
+            if include.is_empty() {
+                return Note::all(pool, member.workspace_id)
+                    .await
+                    .map(|some_notes| HttpResponse::Ok().json(some_notes))
+                    .map_err(ErrorNotFound);
+            }
+            NoteDetail::all(&pool, member.workspace_id, include)
                 .await
                 .map(|some_notes| HttpResponse::Ok().json(some_notes))
-                .map_err(|e| ErrorNotFound(e))
+                .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -106,8 +224,9 @@ pub async fn read_notes(pool: web::Data<PgPool>, credentials: BearerAuth)->Resul
     ),
     responses(
         (status = 200, description = "All categories for note", body = [Category]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "notes"
 )]
@@ -116,11 +235,12 @@ pub async fn read_categories_for_note(pool: web::Data<PgPool>,
         path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
             let id = path.into_inner();
-            Category::get_categories_for_note(pool, id, user_id)
+            Category::get_categories_for_note(pool, id, member.workspace_id)
                .await
                .map(|items| HttpResponse::Ok().json(items))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -133,8 +253,9 @@ pub async fn read_categories_for_note(pool: web::Data<PgPool>,
     ),
     responses(
         (status = 200, description = "All labels for ntoe", body = [Label]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "notes"
 )]
@@ -143,11 +264,12 @@ pub async fn read_labels_for_note(pool: web::Data<PgPool>,
         path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
             let id = path.into_inner();
-            Label::get_labels_for_note(pool, id, user_id)
+            Label::get_labels_for_note(pool, id, member.workspace_id)
                .await
                .map(|labels| HttpResponse::Ok().json(labels))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -156,23 +278,48 @@ pub async fn read_labels_for_note(pool: web::Data<PgPool>,
 
 #[utoipa::path(
     context_path = "/api",
+    params(UpdateOptions),
     request_body = Note,
     responses(
         (status = 200, description = "Updated successfully", body = Note),
+        (status = 400, description = "Error: Invalid note, without id or with an unknown kind"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
+        (status = 409, description = "Error: The note is no longer at the version given, with the current one", body = Note),
     ),
     tag = "notes",
 )]
 #[put("/v1/notes")]
-pub async fn update_note(pool: web::Data<PgPool>, post: String, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+pub async fn update_note(pool: web::Data<PgPool>, post: String, options: web::Query<UpdateOptions>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
-            let content: Value = serde_json::from_str(&post).unwrap();
-            Note::update(pool, content, user_id)
-               .await
-               .map(|note| HttpResponse::Ok().json(note))
-               .map_err(|e| ErrorNotFound(e))
+            let member = writer(&pool, user_id).await?;
+            let content: Value = serde_json::from_str(&post).map_err(ErrorBadRequest)?;
+            if note::note_id(&content).is_none() {
+                return Err(ErrorBadRequest("The id of the note is missing or invalid"));
+            }
+            if let Some(kind) = content.get("kind").filter(|kind| !kind.is_null()){
+                kind.as_str()
+                    .unwrap_or_default()
+                    .parse::<NoteKind>()
+                    .map_err(ErrorBadRequest)?;
+            }
+            // With a version, the note is changed only if it is still at it
+            let id = note::note_id(&content).unwrap_or_default();
+            let version = content.get("version")
+                .and_then(Value::as_i64)
+                .and_then(|version| i32::try_from(version).ok());
+            match Note::update_if(pool.clone(), content, member.workspace_id, options.rewrite_links.unwrap_or(false), version).await {
+                Ok(note) => Ok(HttpResponse::Ok().json(note)),
+                Err(sqlx::Error::RowNotFound) if version.is_some() => {
+                    let note = Note::get(pool, id, member.workspace_id)
+                        .await
+                        .map_err(ErrorNotFound)?;
+                    Err(ErrorConflict(json!(note)))
+                },
+                Err(e) => Err(ErrorNotFound(e)),
+            }
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -185,8 +332,9 @@ pub async fn update_note(pool: web::Data<PgPool>, post: String, credentials: Bea
     ),
     responses(
         (status = 200, description = "Deleted successfully", body = Label),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
         (status = 404, description = "Error: Not found"),
-        (status = 403, description = "Error: Unauthorized")
     ),
     tag = "notes",
 )]
@@ -195,11 +343,12 @@ pub async fn delete_note(pool: web::Data<PgPool>,
         path: web::Path<i32>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
         Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
             let id = path.into_inner();
-            Note::delete(pool, id, user_id)
+            Note::delete(pool, id, member.workspace_id)
                .await
                .map(|note| HttpResponse::Ok().json(note))
-               .map_err(|e| ErrorNotFound(e))
+               .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -213,8 +362,10 @@ pub async fn delete_note(pool: web::Data<PgPool>,
         ("label_id", description = "The id of the label"),
     ),
     responses(
-        (status = 201, description = "Add label note", body = NoteLabel),
-        (status = 400, description = "Bad request", body = NoteLabel),
+        (status = 200, description = "Label added to the note", body = NoteLabel),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Note or label not found"),
     ),
     tag = "notes",
 )]
@@ -222,13 +373,14 @@ pub async fn delete_note(pool: web::Data<PgPool>,
 pub async fn add_label_to_note(pool: web::Data<PgPool>,
         path: web::Path<(i32, i32)>, credentials: BearerAuth)->Result<HttpResponse, Error>{
     match Claims::get_index(credentials) {
-        Ok(_user_id) => {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
             let note_id = path.0;
             let label_id = path.1;
-            NoteLabel::new(pool, note_id, label_id)
+            NoteLabel::new(pool, note_id, label_id, member.workspace_id)
                 .await
                 .map(|note_label| HttpResponse::Ok().json(note_label))
-                .map_err(|e| ErrorNotFound(e))
+                .map_err(ErrorNotFound)
         },
         Err(e) => Err(ErrorUnauthorized(e)),
     }
@@ -244,8 +396,10 @@ pub async fn add_label_to_note(pool: web::Data<PgPool>,
         ("label_id", description = "The id of the label"),
     ),
     responses(
-        (status = 201, description = "Add label note", body = NoteLabel),
-        (status = 400, description = "Bad request", body = NoteLabel),
+        (status = 200, description = "Label removed from the note", body = NoteLabel),
+        (status = 400, description = "Error: The note does not have the label"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
     ),
     tag = "notes",
 )]
@@ -253,12 +407,18 @@ pub async fn add_label_to_note(pool: web::Data<PgPool>,
 #[delete("/v1/notes/{note_id}/labels/{label_id}")]
 pub async fn delete_label_from_note(pool: web::Data<PgPool>,
         path: web::Path<(i32, i32)>, credentials: BearerAuth)->Result<HttpResponse, Error>{
-    let note_id = path.0;
-    let label_id = path.1;
-    NoteLabel::delete(pool, note_id, label_id)
-        .await
-        .map(|note_label| HttpResponse::Ok().json(note_label))
-        .map_err(|e| ErrorBadRequest(e))
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let note_id = path.0;
+            let label_id = path.1;
+            NoteLabel::delete(pool, note_id, label_id, member.workspace_id)
+                .await
+                .map(|note_label| HttpResponse::Ok().json(note_label))
+                .map_err(ErrorBadRequest)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
 }
 
 #[utoipa::path(
@@ -268,21 +428,28 @@ pub async fn delete_label_from_note(pool: web::Data<PgPool>,
         ("category_id", description = "The id of the category"),
     ),
     responses(
-        (status = 201, description = "Add category note", body = NoteCategory),
-        (status = 400, description = "Bad request", body = NoteLabel),
+        (status = 200, description = "Category added to the note", body = NoteCategory),
+        (status = 400, description = "Error: Note or category not found"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
     ),
     tag = "notes",
 )]
 #[put("/v1/notes/{note_id}/categories/{category_id}")]
 pub async fn add_category_to_note(pool: web::Data<PgPool>,
         path: web::Path<(i32, i32)>, credentials: BearerAuth)->Result<HttpResponse, Error>{
-    let note_id = path.0;
-    let category_id = path.1;
-    let user_id = Claims::get_index(credentials).unwrap();
-    NoteCategory::new(pool, note_id, category_id)
-        .await
-        .map(|note_category| HttpResponse::Ok().json(note_category))
-        .map_err(|_| ErrorBadRequest("Bad Request"))
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let note_id = path.0;
+            let category_id = path.1;
+            NoteCategory::new(pool, note_id, category_id, member.workspace_id)
+                .await
+                .map(|note_category| HttpResponse::Ok().json(note_category))
+                .map_err(|_| ErrorBadRequest("Bad Request"))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
 }
 
 /// Remove a category from note by ids
@@ -295,19 +462,27 @@ pub async fn add_category_to_note(pool: web::Data<PgPool>,
         ("category_id", description = "The id of the category"),
     ),
     responses(
-        (status = 201, description = "Add category to note", body = NoteCategory),
-        (status = 400, description = "Bad request", body = NoteCategory),
+        (status = 200, description = "Category removed from the note", body = NoteCategory),
+        (status = 400, description = "Error: The note does not have the category"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
     ),
     tag = "notes",
 )]
 
 #[delete("/v1/notes/{note_id}/categories/{category_id}")]
 pub async fn delete_category_from_note(pool: web::Data<PgPool>,
-        path: web::Path<(i32, i32)>)->Result<HttpResponse, Error>{
-    let note_id = path.0;
-    let category_id = path.1;
-    NoteCategory::delete(pool, note_id, category_id)
-        .await
-        .map(|note_category| HttpResponse::Ok().json(note_category))
-        .map_err(|_| ErrorBadRequest("Bad Request"))
+        path: web::Path<(i32, i32)>, credentials: BearerAuth)->Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let note_id = path.0;
+            let category_id = path.1;
+            NoteCategory::delete(pool, note_id, category_id, member.workspace_id)
+                .await
+                .map(|note_category| HttpResponse::Ok().json(note_category))
+                .map_err(|_| ErrorBadRequest("Bad Request"))
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
 }
@@ -0,0 +1,175 @@
+use actix_web::{get, post, put, delete, web, error::{ErrorNotFound,
+    ErrorUnauthorized, ErrorBadRequest}, Error, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use chrono::{Duration, NaiveDateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::IntoParams;
+use crate::model::{reminder::{Reminder, NewReminder, Snooze,
+    validate_recurrence}, claims::Claims};
+use crate::routes::workspaces::{reader, writer};
+
+/// Most days to look ahead, ten years
+const MAX_DAYS: i64 = 3660;
+/// Longest snooze in minutes, a year
+const MAX_SNOOZE: i64 = 525_600;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct Upcoming{
+    /// How many days ahead to look, 7 by default and 3660 at most
+    days: Option<i64>,
+}
+
+fn from_now(delta: Option<Duration>) -> Option<NaiveDateTime>{
+    Utc::now().naive_utc().checked_add_signed(delta?)
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(Upcoming),
+    responses(
+        (status = 200, description = "Reminders due in the next days, fired ones included", body = [Reminder]),
+        (status = 400, description = "Error: Invalid days"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "reminders",
+)]
+#[get("/v1/reminders")]
+pub async fn read_reminders(pool: web::Data<PgPool>, upcoming: web::Query<Upcoming>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            let until = Some(upcoming.days.unwrap_or(7))
+                .filter(|days| (0..=MAX_DAYS).contains(days))
+                .and_then(|days| from_now(Duration::try_days(days)))
+                .ok_or_else(|| ErrorBadRequest(format!("Days must be between 0 and {}", MAX_DAYS)))?;
+            Reminder::upcoming(&pool, member.workspace_id, until)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
+    request_body = NewReminder,
+    responses(
+        (status = 200, description = "Reminder set", body = Reminder),
+        (status = 400, description = "Error: Invalid recurrence"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "reminders",
+)]
+#[put("/v1/notes/{id}/reminder")]
+pub async fn set_reminder(pool: web::Data<PgPool>, path: web::Path<i32>, reminder: web::Json<NewReminder>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            validate_recurrence(&reminder.recurrence).map_err(ErrorBadRequest)?;
+            Reminder::set(&pool, path.into_inner(), member.workspace_id, reminder.into_inner())
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
+    responses(
+        (status = 200, description = "Reminder removed", body = Reminder),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "reminders",
+)]
+#[delete("/v1/notes/{id}/reminder")]
+pub async fn delete_reminder(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Reminder::unset(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
+    request_body = Snooze,
+    responses(
+        (status = 200, description = "Reminder snoozed", body = Reminder),
+        (status = 400, description = "Error: Invalid minutes"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "reminders",
+)]
+#[post("/v1/reminders/{id}/snooze")]
+pub async fn snooze_reminder(pool: web::Data<PgPool>, path: web::Path<i32>, snooze: web::Json<Snooze>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let snoozed_until = Some(snooze.minutes)
+                .filter(|minutes| (1..=MAX_SNOOZE).contains(minutes))
+                .and_then(|minutes| from_now(Duration::try_minutes(minutes)))
+                .ok_or_else(|| ErrorBadRequest(format!("Minutes must be between 1 and {}", MAX_SNOOZE)))?;
+            Reminder::snooze(&pool, path.into_inner(), member.workspace_id, snoozed_until)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Dismiss a reminder
+///
+/// Recurrent reminders move to their next occurrence, the rest are removed
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the note"),
+    ),
+    responses(
+        (status = 200, description = "Reminder dismissed", body = Reminder),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "reminders",
+)]
+#[post("/v1/reminders/{id}/dismiss")]
+pub async fn dismiss_reminder(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Reminder::dismiss(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -0,0 +1,181 @@
+use actix_web::{get, post, put, delete, web, error::{ErrorNotFound,
+    ErrorConflict, ErrorUnauthorized, ErrorBadRequest}, Error, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+use crate::model::{template::{Template, NewTemplate, UpdateTemplate,
+    TemplateValues, variables}, claims::Claims};
+use crate::routes::workspaces::{reader, writer};
+
+#[utoipa::path(
+    context_path = "/api",
+    request_body = NewTemplate,
+    responses(
+        (status = 201, description = "Created successfully", body = Template),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 409, description = "Error: Conflict"),
+    ),
+    tag = "templates",
+)]
+#[post("/v1/templates")]
+pub async fn create_template(pool: web::Data<PgPool>, template: web::Json<NewTemplate>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Template::new(&pool, template.into_inner(), member.workspace_id, user_id)
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorConflict)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the template"),
+    ),
+    responses(
+        (status = 200, description = "Get One", body = Template),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "templates",
+)]
+#[get("/v1/templates/{id}")]
+pub async fn read_template(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            Template::get(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "List all", body = [Template]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "templates",
+)]
+#[get("/v1/templates")]
+pub async fn read_templates(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = reader(&pool, user_id).await?;
+            Template::all(&pool, member.workspace_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    request_body = UpdateTemplate,
+    responses(
+        (status = 200, description = "Updated successfully", body = Template),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+        (status = 409, description = "Error: Conflict"),
+    ),
+    tag = "templates",
+)]
+#[put("/v1/templates")]
+pub async fn update_template(pool: web::Data<PgPool>, template: web::Json<UpdateTemplate>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Template::get(&pool, template.id, member.workspace_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            Template::update(&pool, template.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorConflict)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the template"),
+    ),
+    responses(
+        (status = 200, description = "Deleted successfully", body = Template),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "templates",
+)]
+#[delete("/v1/templates/{id}")]
+pub async fn delete_template(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Template::delete(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Create a note from a template
+///
+/// The placeholders are filled in with the builtin values and the values
+/// given for the prompts, and the default labels and categories of the
+/// template are assigned to the note
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the template"),
+    ),
+    request_body = TemplateValues,
+    responses(
+        (status = 201, description = "Created successfully", body = Note),
+        (status = 400, description = "Error: Missing values for prompts"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "templates",
+)]
+#[post("/v1/notes/from-template/{id}")]
+pub async fn create_note_from_template(pool: web::Data<PgPool>, path: web::Path<i32>, values: Option<web::Json<TemplateValues>>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let template = Template::get(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            let values = values.map(|values| values.into_inner().values).unwrap_or_default();
+            let variables = variables(values, &member.email, Utc::now().naive_utc());
+            let (title, body) = template.render(&variables)
+                .map_err(|missing| ErrorBadRequest(format!("Missing values for prompts: {}", missing.join(", "))))?;
+            template.new_note(&pool, title, body, member.workspace_id, user_id)
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -1,42 +1,53 @@
-use actix_web::{post, get, web, Error, HttpResponse, HttpRequest};
+use actix_web::{post, web, Error, HttpResponse,
+    error::{ErrorConflict, ErrorForbidden, ErrorInternalServerError,
+    ErrorUnauthorized}};
+use actix_web_httpauth::extractors::basic::BasicAuth;
 use serde::{Serialize, Deserialize};
 use sqlx::PgPool;
-use jsonwebtoken::{encode, Header, EncodingKey};
+use utoipa::ToSchema;
 
-use crate::model::user::{Credentials, User};
+use crate::model::{user::{Credentials, User}, claims::Claims};
+use crate::routes::app_tokens::authenticate;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub exp: usize,
-}
-impl Claims{
-    pub fn new(index: i32)-> Self{
-        Self{
-            sub: index.to_string(),
-            exp: usize::try_from(jsonwebtoken::get_current_timestamp()).unwrap() + 50000,
-        }
-    }
-}
-
-#[derive(Serialize, Deserialize)]
-struct Response{
-    code: String,
-    message: String,
-    token: Option<String>,
+/// Outcome of a login or a registration, with the token for the API
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AuthResponse{
+    #[schema(example = "Ok")]
+    pub code: String,
+    #[schema(example = "Valid credentials")]
+    pub message: String,
+    /// JWT to send as `Authorization: Bearer`
+    pub token: Option<String>,
 }
 
-
+/// Log in with email and password
+#[utoipa::path(
+    context_path = "/auth",
+    request_body = Credentials,
+    responses(
+        (status = 200, description = "Valid credentials", body = AuthResponse),
+        (status = 401, description = "Error: Invalid credentials"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    security(()),
+    tag = "auth",
+)]
 #[post("/login")]
-pub async fn login(req: HttpRequest, pool: web::Data<PgPool>, credentials: web::Json<Credentials>) -> Result<HttpResponse, Error>{
-    println!("{:?}", req);
-    let user = User::get_by_email(&pool, &credentials.email).await.unwrap();
+pub async fn login(pool: web::Data<PgPool>, credentials: web::Json<Credentials>) -> Result<HttpResponse, Error>{
+    let user = User::get_by_email(&pool, &credentials.email)
+        .await
+        .map_err(|_| ErrorUnauthorized("Invalid credentials"))?;
     let password = format!("{:x}", md5::compute(&credentials.password));
     if user.password == password{
-        User::set_login(&pool, user.id, true).await.unwrap();
+        if user.disabled {
+            return Err(ErrorForbidden("User disabled"));
+        }
+        User::set_login(&pool, user.id, true)
+            .await
+            .map_err(ErrorInternalServerError)?;
         let claims = Claims::new(user.id);
-        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret("SECRETO".as_ref())).unwrap();
-        return Ok(HttpResponse::Ok().json(Response{
+        let token = claims.get_token().map_err(ErrorUnauthorized)?;
+        return Ok(HttpResponse::Ok().json(AuthResponse{
             code: "Ok".to_string(),
             message: "Valid credentials".to_string(),
             token: Some(token),
@@ -45,30 +56,51 @@ pub async fn login(req: HttpRequest, pool: web::Data<PgPool>, credentials: web::
     Err(actix_web::error::ErrorUnauthorized("Invalid credentials".to_string()))
 }
 
+/// Get a token of the API with an app token
+///
+/// For clients that keep an app token instead of the password, sent as
+/// basic auth with the email of the user.
+#[utoipa::path(
+    context_path = "/auth",
+    responses(
+        (status = 200, description = "Valid app token", body = AuthResponse),
+        (status = 401, description = "Error: Invalid app token, or user disabled"),
+    ),
+    security(()),
+    tag = "auth",
+)]
+#[post("/token")]
+pub async fn login_with_app_token(pool: web::Data<PgPool>, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    let user_id = authenticate(&pool, &credentials).await?;
+    let token = Claims::new(user_id).get_token().map_err(ErrorUnauthorized)?;
+    Ok(HttpResponse::Ok().json(AuthResponse{
+        code: "Ok".to_string(),
+        message: "Valid app token".to_string(),
+        token: Some(token),
+    }))
+}
+
+/// Register a user, with a personal workspace
+#[utoipa::path(
+    context_path = "/auth",
+    request_body = Credentials,
+    responses(
+        (status = 201, description = "User created", body = AuthResponse),
+        (status = 409, description = "Error: Conflict"),
+    ),
+    security(()),
+    tag = "auth",
+)]
 #[post("/register")]
-pub async fn register(req: HttpRequest, pool: web::Data<PgPool>, credentials: web::Json<Credentials>) -> Result<HttpResponse, Error>{
-    println!("{:?}", req);
+pub async fn register(pool: web::Data<PgPool>, credentials: web::Json<Credentials>) -> Result<HttpResponse, Error>{
     let user = User::new(&pool, credentials.into_inner())
         .await
-        .unwrap();
-        let claims = Claims::new(user.id);
-        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret("SECRETO".as_ref())).unwrap();
-        return Ok(HttpResponse::Created().json(Response{
-            code: "Ok".to_string(),
-            message: "User created".to_string(),
-            token: Some(token),
-        }));
-}
-
-
-#[get("/validate")]
-pub async fn validate(req: HttpRequest, pool: web::Data<PgPool>) -> Result<HttpResponse, Error>{
-    println!("{:?}", req);
-    return Ok(HttpResponse::Created().json(Response{
+        .map_err(ErrorConflict)?;
+    let claims = Claims::new(user.id);
+    let token = claims.get_token().map_err(ErrorUnauthorized)?;
+    Ok(HttpResponse::Created().json(AuthResponse{
         code: "Ok".to_string(),
         message: "User created".to_string(),
-        token: Some("algo".to_string()),
-    }));
+        token: Some(token),
+    }))
 }
-
-
@@ -0,0 +1,309 @@
+//! A WebDAV view of the notes, so they can be mounted as a folder of
+//! markdown files. Categories are folders and notes are `Title.md` files.
+//! Clients authenticate with basic auth, the email of the user and an app
+//! token.
+use actix_web::{web, error::{ErrorBadRequest, ErrorConflict, ErrorForbidden,
+    ErrorInternalServerError, ErrorMethodNotAllowed, ErrorNotFound,
+    ErrorPreconditionFailed}, Error, HttpRequest, HttpResponse,
+    http::{StatusCode, Uri, header::{self, HeaderValue}}, web::Bytes};
+use actix_web_httpauth::extractors::basic::BasicAuth;
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::escape::escape;
+use serde_json::json;
+use sqlx::PgPool;
+use crate::exporter::markdown::file_name;
+use crate::model::category::Category;
+use crate::model::dav::{self, DavNote, Resource, Tree};
+use crate::model::note::{Note, NewNote};
+use crate::model::note_category::NoteCategory;
+use crate::routes::{app_tokens::authenticate, nextcloud::matches,
+    workspaces::{reader, writer}};
+
+/// Where the scope is mounted
+const PREFIX: &str = "/dav";
+const ALLOW: &str = "OPTIONS, PROPFIND, GET, HEAD, PUT, DELETE, MOVE, MKCOL";
+/// What is left as it is in the hrefs
+const HREF: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.')
+    .remove(b'~').remove(b'(').remove(b')');
+
+/// The path in the tree of a path of the server, without slashes at the ends
+fn tree_path(path: &str) -> Option<String>{
+    let path = path.strip_prefix(PREFIX)?;
+    percent_decode_str(path.trim_matches('/'))
+        .decode_utf8()
+        .ok()
+        .map(|path| path.to_string())
+}
+
+fn href(path: &str, folder: bool) -> String{
+    let mut href = PREFIX.to_string();
+    for part in path.split('/').filter(|part| !part.is_empty()) {
+        href.push('/');
+        href.extend(utf8_percent_encode(part, HREF));
+    }
+    if folder {
+        href.push('/');
+    }
+    href
+}
+
+fn http_date(time: NaiveDateTime) -> String{
+    time.and_utc().format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn with_etag(mut response: HttpResponse, etag: &str) -> HttpResponse{
+    if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", etag)) {
+        response.headers_mut().insert(header::ETAG, etag);
+    }
+    response
+}
+
+/// The `<d:response>` of a resource in a PROPFIND
+fn response(path: &str, resource: &Resource) -> String{
+    let props = match resource {
+        Resource::Folder(_) => {
+            let name = dav::split(path).1;
+            format!("<d:displayname>{}</d:displayname><d:resourcetype><d:collection/></d:resourcetype>",
+                escape(name))
+        },
+        Resource::File(note) => format!(concat!(
+            "<d:displayname>{}</d:displayname><d:resourcetype/>",
+            "<d:getcontentlength>{}</d:getcontentlength>",
+            "<d:getcontenttype>text/markdown; charset=utf-8</d:getcontenttype>",
+            "<d:getetag>\"{}\"</d:getetag>",
+            "<d:getlastmodified>{}</d:getlastmodified>",
+            "<d:creationdate>{}</d:creationdate>"),
+            escape(dav::split(path).1), note.content.len(), note.etag(),
+            http_date(note.updated_at), note.created_at.and_utc().format("%Y-%m-%dT%H:%M:%SZ")),
+    };
+    format!("<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        escape(&href(path, matches!(resource, Resource::Folder(_)))), props)
+}
+
+/// Every request under the scope, dispatched on its method
+pub async fn dav(req: HttpRequest, pool: web::Data<PgPool>, body: Bytes, credentials: BasicAuth) -> Result<HttpResponse, Error>{
+    let user_id = authenticate(&pool, &credentials).await?;
+    let path = tree_path(req.path()).ok_or_else(|| ErrorBadRequest("Wrong path"))?;
+    let member = match req.method().as_str() {
+        "OPTIONS" | "PROPFIND" | "GET" | "HEAD" => reader(&pool, user_id).await?,
+        _ => writer(&pool, user_id).await?,
+    };
+    let workspace_id = member.workspace_id;
+    // The folder of the path, or the path itself, and where a note moves to
+    let destination = destination(&req);
+    let mut folders = vec![path.as_str(), dav::split(&path).0];
+    if let Some(destination) = destination.as_deref() {
+        folders.push(dav::split(destination).0);
+    }
+    let tree = Tree::load(&pool, workspace_id, &folders)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    match req.method().as_str() {
+        "OPTIONS" => Ok(HttpResponse::Ok()
+            .insert_header(("DAV", "1"))
+            .insert_header((header::ALLOW, ALLOW))
+            .finish()),
+        "PROPFIND" => propfind(&req, &tree, &path),
+        "GET" | "HEAD" => match tree.get(&path) {
+            Some(Resource::File(note)) => Ok(with_etag(HttpResponse::Ok()
+                .content_type("text/markdown; charset=utf-8")
+                .insert_header((header::LAST_MODIFIED, http_date(note.updated_at)))
+                .body(note.content.clone()), &note.etag())),
+            Some(Resource::Folder(_)) => Err(ErrorMethodNotAllowed("Folders can only be listed with PROPFIND")),
+            None => Err(ErrorNotFound("Not found")),
+        },
+        "PUT" => put(&req, &pool, &tree, &path, body, workspace_id, user_id).await,
+        "DELETE" => delete(&pool, &tree, &path, workspace_id).await,
+        "MOVE" => move_note(&req, &pool, &tree, &path, destination, workspace_id, user_id).await,
+        "MKCOL" => mkcol(&pool, &tree, &path, workspace_id, user_id).await,
+        _ => Ok(HttpResponse::MethodNotAllowed()
+            .insert_header((header::ALLOW, ALLOW))
+            .finish()),
+    }
+}
+
+/// The path in the tree of the `Destination` of a MOVE
+fn destination(req: &HttpRequest) -> Option<String>{
+    req.headers().get("Destination")
+        .and_then(|destination| destination.to_str().ok())
+        .and_then(|destination| destination.parse::<Uri>().ok())
+        .and_then(|destination| tree_path(destination.path()))
+}
+
+/// The resource and, unless `Depth` is 0, what it has. An infinite depth is
+/// taken as 1.
+fn propfind(req: &HttpRequest, tree: &Tree, path: &str) -> Result<HttpResponse, Error>{
+    let resource = tree.get(path).ok_or_else(|| ErrorNotFound("Not found"))?;
+    let depth = req.headers().get("Depth").and_then(|depth| depth.to_str().ok());
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?><d:multistatus xmlns:d="DAV:">"#);
+    xml.push_str(&response(path, &resource));
+    if depth != Some("0") && matches!(resource, Resource::Folder(_)) {
+        for (child, resource) in tree.children(path) {
+            xml.push_str(&response(child, &resource));
+        }
+    }
+    xml.push_str("</d:multistatus>");
+    Ok(HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(xml))
+}
+
+/// Write the text of a note, or create it in the category of the folder
+#[allow(clippy::too_many_arguments)]
+async fn put(req: &HttpRequest, pool: &web::Data<PgPool>, tree: &Tree, path: &str, body: Bytes, workspace_id: i32, user_id: i32) -> Result<HttpResponse, Error>{
+    let text = String::from_utf8(body.to_vec()).map_err(ErrorBadRequest)?;
+    match tree.get(path) {
+        Some(Resource::File(note)) => {
+            if matches(req, header::IF_MATCH, &note.etag()) == Some(false)
+                || matches(req, header::IF_NONE_MATCH, &note.etag()) == Some(true) {
+                return Err(ErrorPreconditionFailed("The note has changed"));
+            }
+            if note.readonly() {
+                return Err(ErrorForbidden("Checklists can not be changed as text"));
+            }
+            // With an etag, the note is written only if it is still that one
+            let version = match matches(req, header::IF_MATCH, "") {
+                Some(false) => Some(note.version),
+                _ => None,
+            };
+            Note::update_if(pool.clone(), json!({"id": note.id, "body": text}), workspace_id, false, version)
+                .await
+                .map_err(|e| match e {
+                    sqlx::Error::RowNotFound => ErrorPreconditionFailed("The note has changed"),
+                    e => ErrorInternalServerError(e),
+                })?;
+            let etag = DavNote::etag_of(pool, note.id, workspace_id)
+                .await
+                .map_err(ErrorInternalServerError)?;
+            Ok(with_etag(HttpResponse::NoContent().finish(), &etag))
+        },
+        Some(Resource::Folder(_)) => Err(ErrorMethodNotAllowed("Folders can not be written")),
+        None => {
+            if matches(req, header::IF_MATCH, "") == Some(true) {
+                return Err(ErrorPreconditionFailed("There is no note"));
+            }
+            let (folder, name) = dav::split(path);
+            let title = name.strip_suffix(".md")
+                .filter(|title| !title.trim().is_empty())
+                .ok_or_else(|| ErrorForbidden("Only markdown files can be created"))?;
+            let category = tree.category(folder)
+                .ok_or_else(|| ErrorConflict("The folder does not exist"))?;
+            let note = Note::new(pool.clone(), NewNote{
+                title: title.trim().to_string(),
+                body: Some(text),
+                kind: None,
+                remind_at: None,
+                recurrence: None,
+            }, workspace_id, user_id)
+                .await
+                .map_err(ErrorInternalServerError)?;
+            if !category.is_empty() {
+                NoteCategory::set_only(pool, note.id, &category, workspace_id, user_id)
+                    .await
+                    .map_err(ErrorInternalServerError)?;
+            }
+            let etag = DavNote::etag_of(pool, note.id, workspace_id)
+                .await
+                .map_err(ErrorInternalServerError)?;
+            Ok(with_etag(HttpResponse::Created().finish(), &etag))
+        },
+    }
+}
+
+/// Move a note to the trash, or delete the category of an empty folder
+async fn delete(pool: &web::Data<PgPool>, tree: &Tree, path: &str, workspace_id: i32) -> Result<HttpResponse, Error>{
+    match tree.get(path) {
+        Some(Resource::File(note)) => {
+            Note::update(pool.clone(), json!({"id": note.id, "trashed": true}), workspace_id, false)
+                .await
+                .map_err(ErrorInternalServerError)?;
+        },
+        Some(Resource::Folder(category)) => {
+            if path.is_empty() || !tree.children(path).is_empty() {
+                return Err(ErrorForbidden("Only empty folders can be deleted"));
+            }
+            if let Some((id, _)) = category {
+                Category::delete(pool.clone(), *id, workspace_id)
+                    .await
+                    .map_err(ErrorInternalServerError)?;
+            }
+        },
+        None => return Err(ErrorNotFound("Not found")),
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Rename a note after its new file name and put it in the category of its
+/// new folder
+#[allow(clippy::too_many_arguments)]
+async fn move_note(req: &HttpRequest, pool: &web::Data<PgPool>, tree: &Tree, path: &str, destination: Option<String>, workspace_id: i32, user_id: i32) -> Result<HttpResponse, Error>{
+    let note = match tree.get(path) {
+        Some(Resource::File(note)) => note,
+        Some(Resource::Folder(_)) => return Err(ErrorForbidden("Folders can not be moved")),
+        None => return Err(ErrorNotFound("Not found")),
+    };
+    let destination = destination.ok_or_else(|| ErrorBadRequest("Wrong destination"))?;
+    if destination == path {
+        return Err(ErrorForbidden("The destination is the note"));
+    }
+    let (folder, name) = dav::split(&destination);
+    let stem = name.strip_suffix(".md")
+        .filter(|stem| !stem.trim().is_empty())
+        .ok_or_else(|| ErrorForbidden("Notes are markdown files"))?;
+    let category = tree.category(folder)
+        .ok_or_else(|| ErrorConflict("The folder does not exist"))?;
+    let overwritten = match tree.get(&destination) {
+        Some(Resource::File(other)) => {
+            let overwrite = req.headers().get("Overwrite").and_then(|overwrite| overwrite.to_str().ok());
+            if overwrite == Some("F") {
+                return Err(ErrorPreconditionFailed("The destination exists"));
+            }
+            Note::update(pool.clone(), json!({"id": other.id, "trashed": true}), workspace_id, false)
+                .await
+                .map_err(ErrorInternalServerError)?;
+            true
+        },
+        Some(Resource::Folder(_)) => return Err(ErrorForbidden("The destination is a folder")),
+        None => false,
+    };
+    let name = file_name(&note.title);
+    if stem != name && stem != format!("{} ({})", name, note.id) {
+        Note::update(pool.clone(), json!({"id": note.id, "title": stem.trim()}), workspace_id, true)
+            .await
+            .map_err(ErrorInternalServerError)?;
+    }
+    if folder != dav::split(path).0 {
+        NoteCategory::set_only(pool, note.id, &category, workspace_id, user_id)
+            .await
+            .map_err(ErrorInternalServerError)?;
+    }
+    Ok(match overwritten {
+        true => HttpResponse::NoContent().finish(),
+        false => HttpResponse::Created().finish(),
+    })
+}
+
+/// Create the category of a folder, nested in the one of its parent
+async fn mkcol(pool: &web::Data<PgPool>, tree: &Tree, path: &str, workspace_id: i32, user_id: i32) -> Result<HttpResponse, Error>{
+    if tree.get(path).is_some() {
+        return Err(ErrorMethodNotAllowed("It already exists"));
+    }
+    let (parent, name) = dav::split(path);
+    let parent = tree.category(parent)
+        .ok_or_else(|| ErrorConflict("The parent folder does not exist"))?;
+    Category::new(pool.clone(), &dav::join(&parent, name.trim()), workspace_id, user_id)
+        .await
+        .map(|_| HttpResponse::Created().finish())
+        .map_err(ErrorInternalServerError)
+}
+
+#[test]
+fn test_paths() {
+    assert_eq!(tree_path("/dav/Casa/Lista%20de%20la%20compra.md"), Some("Casa/Lista de la compra.md".to_string()));
+    assert_eq!(tree_path("/dav/"), Some(String::new()));
+    assert_eq!(tree_path("/api/v1/notes"), None);
+    assert_eq!(href("Casa/Lista (2).md", false), "/dav/Casa/Lista%20(2).md");
+    assert_eq!(href("", true), "/dav/");
+}
@@ -0,0 +1,222 @@
+use actix_web::{get, post, put, delete, web, error::{ErrorBadRequest,
+    ErrorNotFound, ErrorUnauthorized}, Error, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::IntoParams;
+use crate::config;
+use crate::model::{webhook::{Webhook, NewWebhook, UpdateWebhook,
+    WebhookDelivery, check_host, validate}, claims::Claims};
+use crate::routes::workspaces::writer;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeliveryLog{
+    /// How many deliveries to read, 50 by default
+    limit: Option<i64>,
+}
+
+/// Register a webhook
+///
+/// The events of the workspace are POSTed to the url as JSON. The body is
+/// signed in the `X-Notisbak-Signature` header as `sha256=` and the hex
+/// HMAC-SHA256 of the body with the secret.
+#[utoipa::path(
+    context_path = "/api",
+    request_body = NewWebhook,
+    responses(
+        (status = 201, description = "Created successfully", body = Webhook),
+        (status = 400, description = "Error: Invalid url or events, or url of a private address"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "webhooks",
+)]
+#[post("/v1/webhooks")]
+pub async fn create_webhook(pool: web::Data<PgPool>, webhook: web::Json<NewWebhook>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            validate(Some(&webhook.url), webhook.events.as_deref()).map_err(ErrorBadRequest)?;
+            check_host(&webhook.url, config::get().webhooks.allow_private).await.map_err(ErrorBadRequest)?;
+            Webhook::new(&pool, webhook.into_inner(), member.workspace_id, user_id)
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorBadRequest)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "Webhooks of the active workspace", body = [Webhook]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "webhooks",
+)]
+#[get("/v1/webhooks")]
+pub async fn read_webhooks(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Webhook::all(&pool, member.workspace_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the webhook"),
+    ),
+    responses(
+        (status = 200, description = "The webhook", body = Webhook),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "webhooks",
+)]
+#[get("/v1/webhooks/{id}")]
+pub async fn read_webhook(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Webhook::get(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the webhook"),
+    ),
+    request_body = UpdateWebhook,
+    responses(
+        (status = 200, description = "Updated successfully", body = Webhook),
+        (status = 400, description = "Error: Invalid url or events, or url of a private address"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "webhooks",
+)]
+#[put("/v1/webhooks/{id}")]
+pub async fn update_webhook(pool: web::Data<PgPool>, path: web::Path<i32>, webhook: web::Json<UpdateWebhook>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            validate(webhook.url.as_deref(), webhook.events.as_deref()).map_err(ErrorBadRequest)?;
+            if let Some(url) = webhook.url.as_deref() {
+                check_host(url, config::get().webhooks.allow_private).await.map_err(ErrorBadRequest)?;
+            }
+            Webhook::update(&pool, path.into_inner(), webhook.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the webhook"),
+    ),
+    responses(
+        (status = 200, description = "Deleted successfully", body = Webhook),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "webhooks",
+)]
+#[delete("/v1/webhooks/{id}")]
+pub async fn delete_webhook(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            Webhook::delete(&pool, path.into_inner(), member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Read the deliveries of a webhook
+///
+/// The newest first, pending ones included.
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the webhook"),
+        DeliveryLog,
+    ),
+    responses(
+        (status = 200, description = "Deliveries of the webhook", body = [WebhookDelivery]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "webhooks",
+)]
+#[get("/v1/webhooks/{id}/deliveries")]
+pub async fn read_deliveries(pool: web::Data<PgPool>, path: web::Path<i32>, log: web::Query<DeliveryLog>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            WebhookDelivery::all(&pool, path.into_inner(), member.workspace_id, log.limit.unwrap_or(50))
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Send a delivery again
+///
+/// The event is queued again as a new delivery, sent right away.
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the webhook"),
+        ("delivery_id", description = "The id of the delivery"),
+    ),
+    responses(
+        (status = 201, description = "The new delivery", body = WebhookDelivery),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "webhooks",
+)]
+#[post("/v1/webhooks/{id}/deliveries/{delivery_id}/replay")]
+pub async fn replay_delivery(pool: web::Data<PgPool>, path: web::Path<(i32, i32)>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let member = writer(&pool, user_id).await?;
+            let (id, delivery_id) = path.into_inner();
+            WebhookDelivery::replay(&pool, delivery_id, id, member.workspace_id)
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -0,0 +1,309 @@
+use actix_web::{get, post, put, delete, web, error::{ErrorNotFound,
+    ErrorConflict, ErrorUnauthorized, ErrorForbidden, ErrorBadRequest}, Error,
+    HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use anyhow::Result;
+use sqlx::PgPool;
+use crate::model::{workspace::{Workspace, NewWorkspace, UpdateWorkspace,
+    Member, MemberError, NewMember}, claims::Claims};
+
+/// Membership of the user in its active workspace, allowed to read
+pub async fn reader(pool: &web::Data<PgPool>, user_id: i32) -> Result<Member, Error>{
+    Member::active(pool, user_id)
+        .await
+        .map_err(|_| ErrorForbidden("Without workspace"))
+}
+
+/// Membership of the user in its active workspace, allowed to write
+pub async fn writer(pool: &web::Data<PgPool>, user_id: i32) -> Result<Member, Error>{
+    let member = reader(pool, user_id).await?;
+    if member.can_write(){
+        Ok(member)
+    }else{
+        Err(ErrorForbidden("Read only workspace"))
+    }
+}
+
+/// Membership of the user in the workspace `id`, allowed to manage it
+async fn manager(pool: &web::Data<PgPool>, id: i32, user_id: i32) -> Result<Member, Error>{
+    let member = Member::get(pool, id, user_id)
+        .await
+        .map_err(ErrorNotFound)?;
+    if member.can_manage(){
+        Ok(member)
+    }else{
+        Err(ErrorForbidden("Only owners can manage a workspace"))
+    }
+}
+
+fn member_error(error: MemberError) -> Error{
+    match error {
+        MemberError::Sql(e) => ErrorNotFound(e),
+        MemberError::Owner => ErrorBadRequest("The workspace must keep its creator and an owner"),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    request_body = NewWorkspace,
+    responses(
+        (status = 201, description = "Created successfully", body = Workspace),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 409, description = "Error: Conflict"),
+    ),
+    tag = "workspaces",
+)]
+#[post("/v1/workspaces")]
+pub async fn create_workspace(pool: web::Data<PgPool>, workspace: web::Json<NewWorkspace>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            Workspace::new(&pool, &workspace.name, false, user_id)
+                .await
+                .map(|item| HttpResponse::Created().json(item))
+                .map_err(ErrorConflict)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the workspace"),
+    ),
+    responses(
+        (status = 200, description = "Get One", body = Workspace),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "workspaces",
+)]
+#[get("/v1/workspaces/{id}")]
+pub async fn read_workspace(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            Workspace::get(&pool, path.into_inner(), user_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    responses(
+        (status = 200, description = "List all the workspaces of the user", body = [Workspace]),
+        (status = 401, description = "Error: Unauthorized"),
+    ),
+    tag = "workspaces",
+)]
+#[get("/v1/workspaces")]
+pub async fn read_workspaces(pool: web::Data<PgPool>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            Workspace::all(&pool, user_id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    request_body = UpdateWorkspace,
+    responses(
+        (status = 200, description = "Updated successfully", body = Workspace),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "workspaces",
+)]
+#[put("/v1/workspaces")]
+pub async fn update_workspace(pool: web::Data<PgPool>, workspace: web::Json<UpdateWorkspace>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            manager(&pool, workspace.id, user_id).await?;
+            Workspace::update(&pool, workspace.into_inner(), user_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorConflict)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the workspace"),
+    ),
+    responses(
+        (status = 200, description = "Deleted successfully", body = Workspace),
+        (status = 400, description = "Error: Personal workspaces can not be deleted"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+    ),
+    tag = "workspaces",
+)]
+#[delete("/v1/workspaces/{id}")]
+pub async fn delete_workspace(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let id = path.into_inner();
+            manager(&pool, id, user_id).await?;
+            let workspace = Workspace::get(&pool, id, user_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            if workspace.personal{
+                return Err(ErrorBadRequest("Personal workspaces can not be deleted"));
+            }
+            Workspace::delete(&pool, id, user_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Switch the active workspace
+///
+/// The notes, labels and categories endpoints operate on the active workspace
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the workspace"),
+    ),
+    responses(
+        (status = 200, description = "Workspace activated", body = Workspace),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "workspaces",
+)]
+#[put("/v1/workspaces/{id}/active")]
+pub async fn activate_workspace(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            Workspace::activate(&pool, path.into_inner(), user_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the workspace"),
+    ),
+    responses(
+        (status = 200, description = "All the members of the workspace", body = [Member]),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "workspaces",
+)]
+#[get("/v1/workspaces/{id}/members")]
+pub async fn read_members(pool: web::Data<PgPool>, path: web::Path<i32>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let id = path.into_inner();
+            Member::get(&pool, id, user_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            Member::all(&pool, id)
+                .await
+                .map(|items| HttpResponse::Ok().json(items))
+                .map_err(ErrorNotFound)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+/// Add a member to a workspace or change its role
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the workspace"),
+    ),
+    request_body = NewMember,
+    responses(
+        (status = 200, description = "Member added or updated", body = Member),
+        (status = 400, description = "Error: Personal workspaces can not be shared or the workspace would lose its creator or last owner"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: User not found"),
+    ),
+    tag = "workspaces",
+)]
+#[put("/v1/workspaces/{id}/members")]
+pub async fn set_member(pool: web::Data<PgPool>, path: web::Path<i32>, member: web::Json<NewMember>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let id = path.into_inner();
+            let owner = manager(&pool, id, user_id).await?;
+            let workspace = Workspace::get(&pool, id, user_id)
+                .await
+                .map_err(ErrorNotFound)?;
+            if workspace.personal{
+                return Err(ErrorBadRequest("Personal workspaces can not be shared"));
+            }
+            if owner.email == member.email{
+                return Err(ErrorBadRequest("Owners can not change their own role"));
+            }
+            Member::set(&pool, id, member.into_inner())
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(member_error)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
+
+#[utoipa::path(
+    context_path = "/api",
+    params(
+        ("id", description = "The id of the workspace"),
+        ("user_id", description = "The id of the member"),
+    ),
+    responses(
+        (status = 200, description = "Member removed", body = Member),
+        (status = 400, description = "Error: Owners can not leave their workspace or the workspace would lose its creator or last owner"),
+        (status = 401, description = "Error: Unauthorized"),
+        (status = 403, description = "Error: Forbidden"),
+        (status = 404, description = "Error: Not found"),
+    ),
+    tag = "workspaces",
+)]
+#[delete("/v1/workspaces/{id}/members/{user_id}")]
+pub async fn delete_member(pool: web::Data<PgPool>, path: web::Path<(i32, i32)>, credentials: BearerAuth) -> Result<HttpResponse, Error>{
+    match Claims::get_index(credentials) {
+        Ok(user_id) => {
+            let (id, member_id) = path.into_inner();
+            // Members can leave by themselves, owners can remove anybody else
+            if member_id == user_id{
+                let member = Member::get(&pool, id, user_id)
+                    .await
+                    .map_err(ErrorNotFound)?;
+                if member.can_manage(){
+                    return Err(ErrorBadRequest("Owners can not leave their workspace"));
+                }
+            }else{
+                manager(&pool, id, user_id).await?;
+            }
+            Member::delete(&pool, id, member_id)
+                .await
+                .map(|item| HttpResponse::Ok().json(item))
+                .map_err(member_error)
+        },
+        Err(e) => Err(ErrorUnauthorized(e)),
+    }
+}
@@ -0,0 +1,123 @@
+//! Outgoing webhooks. The events are queued for the webhooks that want them
+//! as they are published, see [`crate::events::notify`], and sent from the
+//! queue, signed with HMAC-SHA256 of the body in `X-Notisbak-Signature`.
+//! Failed deliveries are retried with exponential backoff until they are
+//! given up.
+use std::time::Duration;
+
+use actix_web::rt;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use sqlx::PgPool;
+
+use crate::config;
+use crate::model::webhook::{check_host, DeliveryStatus, DueDelivery, WebhookDelivery};
+
+/// Attempts before a delivery is given up
+const MAX_ATTEMPTS: i32 = 8;
+/// Wait after the first failed attempt, doubled after each one
+const BASE_DELAY: i64 = 30;
+/// How long a taken delivery is kept from others while it is sent
+const LEASE: i64 = 60;
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Deliveries sent at once
+const BATCH: i64 = 20;
+
+/// `sha256=` and the hex HMAC-SHA256 of the body with the secret
+pub fn signature(secret: &str, body: &[u8]) -> String{
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC takes keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Seconds to wait after the `attempts` failed so far, or `None` to give up
+fn backoff(attempts: i32) -> Option<i64>{
+    match attempts {
+        attempts if attempts >= MAX_ATTEMPTS => None,
+        attempts => Some(BASE_DELAY << (attempts - 1).max(0)),
+    }
+}
+
+/// Send the due deliveries every `period`
+pub fn start(pool: PgPool, period: Duration){
+    rt::spawn(async move {
+        // A redirect could point to an address the url can not
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(TIMEOUT)
+            .build()
+            .expect("HTTP client");
+        let mut interval = rt::time::interval(period);
+        loop {
+            interval.tick().await;
+            let now = Utc::now().naive_utc();
+            let due = match WebhookDelivery::take_due(&pool, now, now + chrono::Duration::seconds(LEASE), BATCH).await {
+                Ok(due) => due,
+                Err(e) => {
+                    log::error!("Can not read due webhook deliveries: {}", e);
+                    continue;
+                },
+            };
+            for delivery in due.iter() {
+                send(&pool, &client, delivery).await;
+            }
+        }
+    });
+}
+
+async fn send(pool: &PgPool, client: &reqwest::Client, delivery: &DueDelivery){
+    let body = json!({
+        "id": delivery.id,
+        "event": delivery.event,
+        "workspace_id": delivery.workspace_id,
+        "created_at": delivery.created_at,
+        "payload": delivery.payload,
+    }).to_string();
+    // The host may resolve to another address than when it was checked
+    if let Err(error) = check_host(&delivery.url, config::get().webhooks.allow_private).await {
+        let result = WebhookDelivery::attempted(pool, delivery.id, DeliveryStatus::Failed, None, Some(error), None).await;
+        if let Err(e) = result {
+            log::error!("Can not record webhook delivery {}: {}", delivery.id, e);
+        }
+        return;
+    }
+    let response = client.post(&delivery.url)
+        .header("Content-Type", "application/json")
+        .header("X-Notisbak-Event", &delivery.event)
+        .header("X-Notisbak-Delivery", delivery.id.to_string())
+        .header("X-Notisbak-Signature", signature(&delivery.secret, body.as_bytes()))
+        .body(body)
+        .send()
+        .await;
+    let (response_status, error) = match response {
+        Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+        Ok(response) => (Some(response.status().as_u16() as i32), Some(format!("Answered {}", response.status()))),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    let result = match error {
+        None => WebhookDelivery::attempted(pool, delivery.id, DeliveryStatus::Delivered, response_status, None, None).await,
+        Some(error) => match backoff(delivery.attempts + 1) {
+            Some(delay) => {
+                let next = Utc::now().naive_utc() + chrono::Duration::seconds(delay);
+                WebhookDelivery::attempted(pool, delivery.id, DeliveryStatus::Pending, response_status, Some(error), Some(next)).await
+            },
+            None => WebhookDelivery::attempted(pool, delivery.id, DeliveryStatus::Failed, response_status, Some(error), None).await,
+        },
+    };
+    if let Err(e) = result {
+        log::error!("Can not record webhook delivery {}: {}", delivery.id, e);
+    }
+}
+
+#[test]
+fn test_signature() {
+    // From RFC 4231, test case 2
+    assert_eq!(signature("Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    assert_eq!(backoff(1), Some(30));
+    assert_eq!(backoff(3), Some(120));
+    assert_eq!(backoff(MAX_ATTEMPTS), None);
+}